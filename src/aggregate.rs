@@ -0,0 +1,257 @@
+//! Summaries of the values of the nodes that a link spans, kept per link next to the link lengths
+//! of a [SpacedList](crate::SpacedList).
+
+use core::ops::Add;
+use num_traits::Zero;
+
+/// An associative summary of node values, with an identity, such as their sum, minimum, maximum or
+/// count. A [SpacedList](crate::SpacedList) keeps the aggregate of the nodes each link spans, and
+/// answers [aggregate](crate::SpacedList::aggregate) queries for ranges from those.
+///
+/// `combine` is called with the aggregate of earlier nodes first, so it does not need to be
+/// commutative.
+pub trait Aggregate: Copy + PartialEq {
+    /// The value of each node.
+    type Value: Copy + PartialEq + Default;
+    /// The updates that can be applied to all values in a range at once, see
+    /// [update](crate::SpacedList::update).
+    type Update: Update<Self> + PartialEq;
+
+    fn identity() -> Self;
+
+    fn of(value: Self::Value) -> Self;
+
+    fn combine(self, other: Self) -> Self;
+}
+
+/// No values and no aggregates, which is the default and stores nothing.
+impl Aggregate for () {
+    type Value = ();
+    type Update = ();
+
+    fn identity() -> Self {}
+
+    fn of(_: ()) -> Self {}
+
+    fn combine(self, _: Self) -> Self {}
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Sum<T>(pub T);
+
+impl<T> Aggregate for Sum<T>
+    where T: Copy + PartialEq + Default + Add<Output=T> + Zero {
+    type Value = T;
+    type Update = RangeUpdate<T>;
+
+    fn identity() -> Self {
+        Sum(T::zero())
+    }
+
+    fn of(value: T) -> Self {
+        Sum(value)
+    }
+
+    fn combine(self, other: Self) -> Self {
+        Sum(self.0 + other.0)
+    }
+}
+
+/// The least value, or None if there are no nodes.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Min<T>(pub Option<T>);
+
+impl<T: Copy + Ord + Default + Add<Output=T> + Zero> Aggregate for Min<T> {
+    type Value = T;
+    type Update = RangeUpdate<T>;
+
+    fn identity() -> Self {
+        Min(None)
+    }
+
+    fn of(value: T) -> Self {
+        Min(Some(value))
+    }
+
+    fn combine(self, other: Self) -> Self {
+        match (self.0, other.0) {
+            (Some(a), Some(b)) => Min(Some(a.min(b))),
+            (a, b) => Min(a.or(b)),
+        }
+    }
+}
+
+/// The greatest value, or None if there are no nodes.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Max<T>(pub Option<T>);
+
+impl<T: Copy + Ord + Default + Add<Output=T> + Zero> Aggregate for Max<T> {
+    type Value = T;
+    type Update = RangeUpdate<T>;
+
+    fn identity() -> Self {
+        Max(None)
+    }
+
+    fn of(value: T) -> Self {
+        Max(Some(value))
+    }
+
+    fn combine(self, other: Self) -> Self {
+        Max(self.0.max(other.0))
+    }
+}
+
+/// The number of nodes, which have no values of their own.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Count(pub usize);
+
+impl Aggregate for Count {
+    type Value = ();
+    type Update = ();
+
+    fn identity() -> Self {
+        Count(0)
+    }
+
+    fn of(_: ()) -> Self {
+        Count(1)
+    }
+
+    fn combine(self, other: Self) -> Self {
+        Count(self.0 + other.0)
+    }
+}
+
+/// An update of all node values in a range, which a [SpacedList](crate::SpacedList) applies to the
+/// aggregates of the links spanning the range right away, and to the links below them and the
+/// values only when it needs to.
+pub trait Update<A: Aggregate>: Copy {
+    /// The update that changes nothing.
+    fn identity() -> Self;
+
+    /// Returns the update that applies this one and then `later`.
+    fn compose(self, later: Self) -> Self;
+
+    fn apply(self, value: A::Value) -> A::Value;
+
+    /// Applies this update to `aggregate`, the aggregate of `count` values.
+    fn apply_to_aggregate(self, aggregate: A, count: usize) -> A;
+}
+
+/// No updates, for aggregates whose values cannot be updated in ranges.
+impl<A: Aggregate> Update<A> for () {
+    fn identity() -> Self {}
+
+    fn compose(self, _: Self) -> Self {}
+
+    fn apply(self, value: A::Value) -> A::Value {
+        value
+    }
+
+    fn apply_to_aggregate(self, aggregate: A, _: usize) -> A {
+        aggregate
+    }
+}
+
+/// Adds to or sets all values in a range.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RangeUpdate<T> {
+    Add(T),
+    Set(T),
+}
+
+impl<T: Copy + Add<Output=T> + Zero> RangeUpdate<T> {
+    fn identity() -> Self {
+        RangeUpdate::Add(T::zero())
+    }
+
+    fn compose(self, later: Self) -> Self {
+        match (self, later) {
+            (_, RangeUpdate::Set(value)) => RangeUpdate::Set(value),
+            (RangeUpdate::Add(first), RangeUpdate::Add(second)) => RangeUpdate::Add(first + second),
+            (RangeUpdate::Set(first), RangeUpdate::Add(second)) => RangeUpdate::Set(first + second),
+        }
+    }
+
+    fn apply(self, value: T) -> T {
+        match self {
+            RangeUpdate::Add(addend) => value + addend,
+            RangeUpdate::Set(new_value) => new_value,
+        }
+    }
+}
+
+impl<T> Update<Sum<T>> for RangeUpdate<T>
+    where T: Copy + PartialEq + Default + Add<Output=T> + Zero {
+    fn identity() -> Self {
+        RangeUpdate::identity()
+    }
+
+    fn compose(self, later: Self) -> Self {
+        RangeUpdate::compose(self, later)
+    }
+
+    fn apply(self, value: T) -> T {
+        RangeUpdate::apply(self, value)
+    }
+
+    fn apply_to_aggregate(self, Sum(sum): Sum<T>, count: usize) -> Sum<T> {
+        match self {
+            RangeUpdate::Add(addend) => Sum(sum + times(addend, count)),
+            RangeUpdate::Set(value) => Sum(times(value, count)),
+        }
+    }
+}
+
+/// Returns `value` added up `count` times, by doubling and adding, so that `count` does not have to
+/// fit into `T`. No intermediate sum is greater in magnitude than the result.
+fn times<T: Copy + Add<Output=T> + Zero>(mut value: T, mut count: usize) -> T {
+    let mut result = T::zero();
+    loop {
+        if count & 1 == 1 {
+            result = result + value;
+        }
+        count >>= 1;
+        if count == 0 {
+            return result;
+        }
+        value = value + value;
+    }
+}
+
+impl<T: Copy + Ord + Default + Add<Output=T> + Zero> Update<Min<T>> for RangeUpdate<T> {
+    fn identity() -> Self {
+        RangeUpdate::identity()
+    }
+
+    fn compose(self, later: Self) -> Self {
+        RangeUpdate::compose(self, later)
+    }
+
+    fn apply(self, value: T) -> T {
+        RangeUpdate::apply(self, value)
+    }
+
+    fn apply_to_aggregate(self, Min(min): Min<T>, _: usize) -> Min<T> {
+        Min(min.map(|min| self.apply(min)))
+    }
+}
+
+impl<T: Copy + Ord + Default + Add<Output=T> + Zero> Update<Max<T>> for RangeUpdate<T> {
+    fn identity() -> Self {
+        RangeUpdate::identity()
+    }
+
+    fn compose(self, later: Self) -> Self {
+        RangeUpdate::compose(self, later)
+    }
+
+    fn apply(self, value: T) -> T {
+        RangeUpdate::apply(self, value)
+    }
+
+    fn apply_to_aggregate(self, Max(max): Max<T>, _: usize) -> Max<T> {
+        Max(max.map(|max| self.apply(max)))
+    }
+}
@@ -0,0 +1,443 @@
+//! An alternative layout of [SpacedList](crate::SpacedList), in which a list and all of its
+//! (nested) sublists share three contiguous vectors instead of owning two vectors each.
+//!
+//! Every list, including the root, is described by a [ListHeader] in `lists` and referenced by its
+//! index in there. The link lengths of a list live in a block of `link_lengths`, and the ids of its
+//! sublists live in a block of `sublists`. Both blocks only ever take one of a few sizes (one per
+//! depth), so blocks that were left behind when a list grew are kept in per-depth free lists and
+//! handed out again to the next list that grows to that depth.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::default::default;
+use core::num::NonZeroUsize;
+use crate::{link_index, LinkIndicesAbove, Spacing, SpacedListBackend, zero};
+
+/// Index of a list in `ArenaSpacedList::lists`. The root list always has the id 0, so the id of a
+/// sublist is never zero.
+type ListId = usize;
+
+// region list header
+#[derive(Copy, Clone)]
+struct ListHeader<S: Spacing> {
+    size: usize,
+    capacity: usize,
+    depth: usize,
+    length: S,
+    /// Offset of the block of `2^depth - 1` link lengths of this list.
+    links: usize,
+    /// Offset of the block of `2^(depth - 1)` sublist slots of this list, one after every node.
+    sublists: usize,
+}
+
+impl<S: Spacing> ListHeader<S> {
+    pub(crate) fn new() -> Self {
+        Self {
+            size: 1,
+            capacity: 1,
+            depth: 0,
+            length: zero(),
+            links: 0,
+            sublists: 0,
+        }
+    }
+}
+// endregion
+
+// region traversal result
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ArenaTraversalResult<S: Spacing> {
+    pub(crate) list: ListId,
+    pub(crate) position: S,
+    pub(crate) index: usize,
+}
+// endregion
+
+// region arena spaced list
+/// A [SpacedList](crate::SpacedList) whose sublists are stored in shared, contiguous storage and
+/// referenced by index, so that inserting into many small sublists does not allocate for each
+/// one of them.
+pub struct ArenaSpacedList<S: Spacing> {
+    lists: Vec<ListHeader<S>>,
+    link_lengths: Vec<S>,
+    sublists: Vec<Option<NonZeroUsize>>,
+    /// Offsets of unused blocks in `link_lengths`, by depth.
+    free_link_blocks: Vec<Vec<usize>>,
+    /// Offsets of unused blocks in `sublists`, by depth.
+    free_sublist_blocks: Vec<Vec<usize>>,
+}
+
+impl<S: Spacing> Default for ArenaSpacedList<S> {
+    fn default() -> Self {
+        Self {
+            lists: vec![ListHeader::new()],
+            link_lengths: vec![],
+            sublists: vec![],
+            free_link_blocks: vec![],
+            free_sublist_blocks: vec![],
+        }
+    }
+}
+
+impl<S: Spacing> ArenaSpacedList<S> {
+    pub(crate) fn new() -> Self {
+        default()
+    }
+
+    fn link(&self, list: ListId, node_index: usize, degree: usize) -> S {
+        self.link_lengths[self.lists[list].links + link_index(node_index, degree)]
+    }
+
+    /// Returns the sublist at `index` of `list`, or None if there is none (there never is one
+    /// after the last node)
+    fn sublist(&self, list: ListId, index: usize) -> Option<ListId> {
+        let header = &self.lists[list];
+        if index + 1 >= header.size {
+            return None;
+        }
+        self.sublists[header.sublists + index].map(NonZeroUsize::get)
+    }
+
+    /// Returns the sublist at `index` of `list`, or None if there is no sublist at `index` or that
+    /// sublist is empty
+    fn not_empty_sublist(&self, list: ListId, index: usize) -> Option<ListId> {
+        self.sublist(list, index).filter(|&sublist| self.lists[sublist].size > 1)
+    }
+
+    /// Returns the sublist at `index` of `list`, creating an empty one if absent
+    fn sublist_or_insert(&mut self, list: ListId, index: usize) -> ListId {
+        if let Some(sublist) = self.sublist(list, index) {
+            return sublist;
+        }
+        let sublist = self.lists.len();
+        self.lists.push(ListHeader::new());
+        self.sublists[self.lists[list].sublists + index] = NonZeroUsize::new(sublist);
+        sublist
+    }
+
+    fn allocate_link_block(&mut self, depth: usize) -> usize {
+        let len = (1 << depth) - 1;
+        match self.free_link_blocks.get_mut(depth).and_then(Vec::pop) {
+            Some(offset) => {
+                self.link_lengths[offset..offset + len].fill(zero());
+                offset
+            }
+            None => {
+                let offset = self.link_lengths.len();
+                self.link_lengths.resize(offset + len, zero());
+                offset
+            }
+        }
+    }
+
+    fn allocate_sublist_block(&mut self, depth: usize) -> usize {
+        let len = 1 << (depth - 1);
+        match self.free_sublist_blocks.get_mut(depth).and_then(Vec::pop) {
+            Some(offset) => {
+                self.sublists[offset..offset + len].fill(None);
+                offset
+            }
+            None => {
+                let offset = self.sublists.len();
+                self.sublists.resize(offset + len, None);
+                offset
+            }
+        }
+    }
+
+    fn free_blocks(&mut self, depth: usize, links: usize, sublists: usize) {
+        if depth == 0 {
+            return;
+        }
+        if self.free_link_blocks.len() <= depth {
+            self.free_link_blocks.resize_with(depth + 1, Vec::new);
+            self.free_sublist_blocks.resize_with(depth + 1, Vec::new);
+        }
+        self.free_link_blocks[depth].push(links);
+        self.free_sublist_blocks[depth].push(sublists);
+    }
+
+    /// Moves the blocks of `list` to blocks of the next depth, like `SpacedList::make_space` grows
+    /// `link_lengths`.
+    fn make_space(&mut self, list: ListId) {
+        let ListHeader { size, capacity, depth, length, links, sublists } = self.lists[list];
+        if size < 2 || capacity >= size {
+            return;
+        }
+        let new_links = self.allocate_link_block(depth + 1);
+        let new_sublists = self.allocate_sublist_block(depth + 1);
+        let old_links_len = (1 << depth) - 1;
+        self.link_lengths.copy_within(links..links + old_links_len, new_links);
+        self.link_lengths[new_links + old_links_len] = length;
+        if depth > 0 {
+            self.sublists.copy_within(sublists..sublists + (1 << (depth - 1)), new_sublists);
+        }
+        self.free_blocks(depth, links, sublists);
+
+        let header = &mut self.lists[list];
+        header.capacity = (1 << depth) + 1;
+        header.depth = depth + 1;
+        header.links = new_links;
+        header.sublists = new_sublists;
+    }
+
+    fn append_node(&mut self, list: ListId, distance: S) {
+        assert!(distance > zero());
+        self.lists[list].size += 1;
+        self.make_space(list);
+        let ListHeader { size, depth, links, .. } = self.lists[list];
+        self.lists[list].length += distance;
+        for link_index in LinkIndicesAbove::new(size - 1 - 1).take(depth) {
+            self.link_lengths[links + link_index] += distance
+        }
+    }
+
+    pub(crate) fn insert(&mut self, mut position: S) {
+        let mut list = 0;
+        loop {
+            assert!(position > zero());
+            let length = self.lists[list].length;
+            if position >= length {
+                self.append_node(list, position - length);
+                return;
+            }
+            // zero() < position < length
+            let (node_position, index) = self.descend(list, position, true);
+            list = self.sublist_or_insert(list, index);
+            position = position - node_position;
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.lists[0].size == 1
+    }
+}
+
+impl<S: Spacing> ArenaSpacedList<S> {
+    /// Returns the position and index of the last node in `list`, not in sublists, that is before
+    /// `target_position`, or at it if `inclusive` is true. Assumes there is such a node.
+    fn descend(&self, list: ListId, target_position: S, inclusive: bool) -> (S, usize) {
+        let ListHeader { size, depth, links, .. } = self.lists[list];
+        let mut position = S::zero();
+        let mut index = 0usize;
+        for degree in (0..depth).rev() {
+            let possibly_next_index = index + (1 << degree);
+            if possibly_next_index < size {
+                let possibly_next_position =
+                    position + self.link_lengths[links + link_index(index, degree)];
+                if possibly_next_position < target_position
+                    || inclusive && possibly_next_position == target_position {
+                    position = possibly_next_position;
+                    index = possibly_next_index;
+                }
+            }
+        }
+        (position, index)
+    }
+
+    /// Returns the last node before (the greatest less than) `target_position` in this list,
+    /// including sublists, or None if `target_position` is zero or negative.
+    pub(crate) fn node_before(&self, target_position: S) -> Option<Vec<ArenaTraversalResult<S>>> {
+        self.node_before_in(0, target_position)
+    }
+
+    fn node_before_in(&self, list: ListId, target_position: S)
+                      -> Option<Vec<ArenaTraversalResult<S>>> {
+        if target_position <= zero() {
+            return None;
+        }
+        let (position, index) = self.descend(list, target_position, false);
+        let mut result = vec![ArenaTraversalResult { list, position, index }];
+        if let Some(sublist) = self.not_empty_sublist(list, index) {
+            let sublist_result = self.node_before_in(sublist, target_position - position)?;
+            if sublist_result.len() > 1 || sublist_result[0].index != 0 {
+                result.extend(sublist_result)
+            }
+        }
+        Some(result)
+    }
+
+    /// Returns the last node at or before (the greatest less than or equal to) `target_position` in
+    /// this list, including sublists, or None if `target_position` is negative.
+    pub(crate) fn node_at_or_before(&self, target_position: S) -> Option<Vec<ArenaTraversalResult<S>>> {
+        self.node_at_or_before_in(0, target_position)
+    }
+
+    fn node_at_or_before_in(&self, list: ListId, target_position: S)
+                            -> Option<Vec<ArenaTraversalResult<S>>> {
+        if target_position < zero() {
+            return None;
+        }
+        let (position, index) = self.descend(list, target_position, true);
+        let mut result = vec![ArenaTraversalResult { list, position, index }];
+        match self.not_empty_sublist(list, index) {
+            Some(sublist) if position != target_position =>
+                result.extend(self.node_at_or_before_in(sublist, target_position - position)?),
+            _ => ()
+        }
+        Some(result)
+    }
+
+    /// Returns the node at `target_position` in this list, including sublists, or None if this list
+    /// does not contain a node at `target_position`.
+    pub(crate) fn node_at(&self, target_position: S) -> Option<Vec<ArenaTraversalResult<S>>> {
+        self.node_at_in(0, target_position)
+    }
+
+    fn node_at_in(&self, list: ListId, target_position: S) -> Option<Vec<ArenaTraversalResult<S>>> {
+        if target_position < zero() {
+            return None;
+        }
+        let (position, index) = self.descend(list, target_position, true);
+        let mut result = vec![ArenaTraversalResult { list, position, index }];
+        if position != target_position {
+            let sublist = self.not_empty_sublist(list, index)?;
+            result.extend(self.node_at_in(sublist, target_position - position)?)
+        }
+        Some(result)
+    }
+
+    /// Returns the first node at or after (the least greater than or equal to) `target_position` in
+    /// this list, including sublists, or None if `target_position > self.length`.
+    pub(crate) fn node_at_or_after(&self, target_position: S) -> Option<Vec<ArenaTraversalResult<S>>> {
+        self.node_at_or_after_in(0, target_position)
+    }
+
+    fn node_at_or_after_in(&self, list: ListId, target_position: S)
+                           -> Option<Vec<ArenaTraversalResult<S>>> {
+        if target_position < zero() {
+            return Some(vec![ArenaTraversalResult { list, position: zero(), index: 0 }]);
+        }
+        if target_position > self.lists[list].length {
+            return None;
+        }
+        let (position, index) = self.descend(list, target_position, true);
+        let mut result = vec![ArenaTraversalResult { list, position, index }];
+        if position != target_position {
+            if let Some(sublist) = self.not_empty_sublist(list, index) {
+                if let Some(sublist_result) =
+                    self.node_at_or_after_in(sublist, target_position - position) {
+                    result.extend(sublist_result);
+                    return Some(result);
+                }
+            }
+            return Some(vec![ArenaTraversalResult {
+                list,
+                position: position + self.link(list, index, 0),
+                index: index + 1,
+            }]);
+        }
+        Some(result)
+    }
+
+    /// Returns the first node after (the least greater than) `target_position` in this list,
+    /// including sublists, or None if `target_position >= self.length`.
+    pub(crate) fn node_after(&self, target_position: S) -> Option<Vec<ArenaTraversalResult<S>>> {
+        self.node_after_in(0, target_position)
+    }
+
+    fn node_after_in(&self, list: ListId, target_position: S)
+                     -> Option<Vec<ArenaTraversalResult<S>>> {
+        if target_position < zero() {
+            return Some(vec![ArenaTraversalResult { list, position: zero(), index: 0 }]);
+        }
+        if target_position >= self.lists[list].length {
+            return None;
+        }
+        let (position, index) = self.descend(list, target_position, true);
+        let mut result = vec![ArenaTraversalResult { list, position, index }];
+        if let Some(sublist) = self.not_empty_sublist(list, index) {
+            if let Some(sublist_result) = self.node_after_in(sublist, target_position - position) {
+                result.extend(sublist_result);
+                return Some(result);
+            }
+        }
+        Some(vec![ArenaTraversalResult {
+            list,
+            position: position + self.link(list, index, 0),
+            index: index + 1,
+        }])
+    }
+}
+// endregion
+
+// region backend
+fn path_position<S: Spacing>(path: Vec<ArenaTraversalResult<S>>) -> S {
+    path.iter().fold(zero(), |position, result| position + result.position)
+}
+
+impl<S: Spacing> SpacedListBackend<S> for ArenaSpacedList<S> {
+    type Positions<'a> = ArenaPositions<'a, S> where S: 'a;
+
+    fn insert(&mut self, position: S) {
+        ArenaSpacedList::insert(self, position)
+    }
+
+    fn position_before(&self, target_position: S) -> Option<S> {
+        self.node_before(target_position).map(path_position)
+    }
+
+    fn position_at_or_before(&self, target_position: S) -> Option<S> {
+        self.node_at_or_before(target_position).map(path_position)
+    }
+
+    fn position_at(&self, target_position: S) -> Option<S> {
+        self.node_at(target_position).map(path_position)
+    }
+
+    fn position_at_or_after(&self, target_position: S) -> Option<S> {
+        self.node_at_or_after(target_position).map(path_position)
+    }
+
+    fn position_after(&self, target_position: S) -> Option<S> {
+        self.node_after(target_position).map(path_position)
+    }
+
+    fn positions(&self) -> ArenaPositions<'_, S> {
+        ArenaPositions {
+            list: self,
+            stack: vec![(0, 0, zero())],
+        }
+    }
+}
+
+/// Iterator over the positions of all nodes of an [ArenaSpacedList], including sublists.
+pub struct ArenaPositions<'a, S: Spacing> {
+    list: &'a ArenaSpacedList<S>,
+    /// The lists being iterated, innermost last, each with the index and the absolute position of
+    /// the next node to visit in it.
+    stack: Vec<(ListId, usize, S)>,
+}
+
+impl<S: Spacing> Iterator for ArenaPositions<'_, S> {
+    type Item = S;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let nesting = self.stack.len();
+            let (list, index, position) = self.stack.last_mut()?;
+            let size = self.list.lists[*list].size;
+            if *index >= size {
+                self.stack.pop();
+                continue;
+            }
+            let list = *list;
+            let node_index = *index;
+            let node_position = *position;
+            // node zero of a sublist is the node it hangs off, which has been visited already
+            let visited = node_index == 0 && nesting > 1;
+            *index += 1;
+            if node_index + 1 < size {
+                *position += self.list.link(list, node_index, 0);
+            }
+            if let Some(sublist) = self.list.not_empty_sublist(list, node_index) {
+                self.stack.push((sublist, 0, node_position));
+            }
+            if visited {
+                continue;
+            }
+            return Some(node_position);
+        }
+    }
+}
+// endregion
@@ -0,0 +1,436 @@
+//! A layout of [SpacedList](crate::SpacedList) with a fixed capacity, which never allocates.
+//!
+//! Like in [ArenaSpacedList](crate::ArenaSpacedList), a list and all of its (nested) sublists share
+//! their storage and are referenced by their index in `lists`, but the storage is a set of inline
+//! arrays. Each slot of `slots` holds one link length and one sublist id, and a list of depth `d`
+//! takes a block of `2^d` slots: its `2^d - 1` link lengths and its `2^(d - 1)` sublist slots,
+//! which are both laid out from the start of the block. Blocks that were left behind when a list
+//! grew are kept in per-depth free lists, which are threaded through the sublist slot of the last
+//! slot of each free block, as no list of that depth uses it.
+
+use core::default::default;
+use core::fmt;
+use core::fmt::{Display, Formatter};
+use core::num::NonZeroUsize;
+use crate::{link_index, LinkIndicesAbove, Spacing, SpacedListBackend, zero};
+
+/// Index of a list in `ArraySpacedList::lists`. The root list always has the id 0, so the id of a
+/// sublist is never zero.
+type ListId = usize;
+
+// region capacity error
+/// The error returned by [ArraySpacedList::insert] if the node does not fit into the list. The
+/// list is left unchanged.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CapacityError;
+
+impl Display for CapacityError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "the spaced list is out of capacity")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CapacityError {}
+// endregion
+
+// region list header
+#[derive(Copy, Clone)]
+struct ListHeader<S: Spacing> {
+    size: usize,
+    capacity: usize,
+    depth: usize,
+    length: S,
+    /// Offset of the block of `2^depth` slots of this list.
+    block: usize,
+    /// The list this one is a sublist of, and the index of the node in there it comes after. Only
+    /// the iterator needs these, as it cannot keep a stack.
+    parent: ListId,
+    parent_index: usize,
+}
+
+impl<S: Spacing> ListHeader<S> {
+    fn new() -> Self {
+        Self {
+            size: 1,
+            capacity: 1,
+            depth: 0,
+            length: zero(),
+            block: 0,
+            parent: 0,
+            parent_index: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Slot<S: Spacing> {
+    link: S,
+    sublist: Option<NonZeroUsize>,
+}
+// endregion
+
+// region array spaced list
+/// A [SpacedList](crate::SpacedList) that keeps its link lengths and sublists in inline arrays of
+/// `N` slots, so it never allocates, and can be used where allocating is not an option.
+///
+/// Each list and sublist takes a block of fewer than four slots per node, and the blocks it outgrew
+/// only go to other lists of their size, so `N` should be a few times the number of nodes to hold.
+/// [Self::insert] returns a [CapacityError] if there is no room for a node. The queries have the
+/// same semantics as those of [SpacedList](crate::SpacedList).
+pub struct ArraySpacedList<S: Spacing, const N: usize> {
+    lists: [ListHeader<S>; N],
+    list_count: usize,
+    slots: [Slot<S>; N],
+    /// Number of slots at the start of `slots` that have been handed out. The rest have never
+    /// been used.
+    used: usize,
+    /// Offset of the first free block of each depth.
+    free_blocks: [Option<usize>; usize::BITS as usize],
+}
+
+impl<S: Spacing, const N: usize> Default for ArraySpacedList<S, N> {
+    fn default() -> Self {
+        assert!(N > 0, "an ArraySpacedList needs room for at least one list");
+        Self {
+            lists: [ListHeader::new(); N],
+            list_count: 1,
+            slots: [Slot { link: zero(), sublist: None }; N],
+            used: 0,
+            free_blocks: [None; usize::BITS as usize],
+        }
+    }
+}
+
+impl<S: Spacing, const N: usize> ArraySpacedList<S, N> {
+    pub fn new() -> Self {
+        default()
+    }
+
+    fn link(&self, list: ListId, node_index: usize, degree: usize) -> S {
+        self.slots[self.lists[list].block + link_index(node_index, degree)].link
+    }
+
+    /// Returns the sublist at `index` of `list`, or None if there is none (there never is one
+    /// after the last node)
+    fn sublist(&self, list: ListId, index: usize) -> Option<ListId> {
+        let header = &self.lists[list];
+        if index + 1 >= header.size {
+            return None;
+        }
+        self.slots[header.block + index].sublist.map(NonZeroUsize::get)
+    }
+
+    /// Returns the sublist at `index` of `list`, or None if there is no sublist at `index` or that
+    /// sublist is empty
+    fn not_empty_sublist(&self, list: ListId, index: usize) -> Option<ListId> {
+        self.sublist(list, index).filter(|&sublist| self.lists[sublist].size > 1)
+    }
+
+    /// Returns the position of the node at `index` in `list`, relative to its node zero.
+    fn node_position(&self, list: ListId, index: usize) -> S {
+        let mut position = zero();
+        let mut node_index = 0;
+        for degree in (0..self.lists[list].depth).rev() {
+            if node_index + (1 << degree) <= index {
+                position += self.link(list, node_index, degree);
+                node_index += 1 << degree;
+            }
+        }
+        position
+    }
+
+    /// Takes a cleared block for a list of `depth` from the free list or the unused slots, or
+    /// returns None if there is no room for one.
+    fn allocate_block(&mut self, depth: usize) -> Option<usize> {
+        let len = 1 << depth;
+        match self.free_blocks[depth] {
+            Some(offset) => {
+                self.free_blocks[depth] =
+                    self.slots[offset + len - 1].sublist.map(|next| next.get() - 1);
+                self.slots[offset..offset + len].fill(Slot { link: zero(), sublist: None });
+                Some(offset)
+            }
+            None if self.used + len <= N => {
+                self.used += len;
+                Some(self.used - len)
+            }
+            None => None,
+        }
+    }
+
+    fn free_block(&mut self, depth: usize, offset: usize) {
+        if depth == 0 {
+            return;
+        }
+        let len = 1 << depth;
+        self.slots[offset + len - 1].sublist =
+            self.free_blocks[depth].map(|next| NonZeroUsize::new(next + 1).unwrap());
+        self.free_blocks[depth] = Some(offset);
+    }
+
+    /// Moves the block of `list` to a block of the next depth if one more node does not fit into
+    /// it, like `SpacedList::make_space` grows `link_lengths`. Fails without changing anything if
+    /// there is no room for that block.
+    fn make_space(&mut self, list: ListId) -> Result<(), CapacityError> {
+        let ListHeader { size, capacity, depth, length, block, .. } = self.lists[list];
+        if capacity > size {
+            return Ok(());
+        }
+        let new_block = self.allocate_block(depth + 1).ok_or(CapacityError)?;
+        if depth > 0 {
+            // the last slot holds neither a link length nor a sublist
+            self.slots.copy_within(block..block + (1 << depth), new_block);
+            self.slots[new_block + (1 << depth) - 1].sublist = None;
+        }
+        self.slots[new_block + (1 << depth) - 1].link = length;
+        self.free_block(depth, block);
+
+        let header = &mut self.lists[list];
+        header.capacity = (1 << depth) + 1;
+        header.depth = depth + 1;
+        header.block = new_block;
+        Ok(())
+    }
+
+    fn append_node(&mut self, list: ListId, distance: S) -> Result<(), CapacityError> {
+        assert!(distance > zero());
+        self.make_space(list)?;
+        let header = &mut self.lists[list];
+        header.size += 1;
+        header.length += distance;
+        let ListHeader { size, depth, block, .. } = *header;
+        for link_index in LinkIndicesAbove::new(size - 1 - 1).take(depth) {
+            self.slots[block + link_index].link += distance
+        }
+        Ok(())
+    }
+
+    /// Starts a sublist after the node at `index` in `list`, with one node at `position`.
+    fn insert_sublist(&mut self, list: ListId, index: usize, position: S)
+                      -> Result<(), CapacityError> {
+        if self.list_count == N {
+            return Err(CapacityError);
+        }
+        let block = self.allocate_block(1).ok_or(CapacityError)?;
+        let sublist = self.list_count;
+        self.list_count += 1;
+        self.lists[sublist] = ListHeader {
+            size: 2,
+            capacity: 2,
+            depth: 1,
+            length: position,
+            block,
+            parent: list,
+            parent_index: index,
+        };
+        self.slots[block].link = position;
+        self.slots[self.lists[list].block + index].sublist = NonZeroUsize::new(sublist);
+        Ok(())
+    }
+
+    /// Inserts a node at `position`, which must be positive, or returns a [CapacityError] and
+    /// leaves the list unchanged if there is no room for it.
+    pub fn insert(&mut self, mut position: S) -> Result<(), CapacityError> {
+        let mut list = 0;
+        loop {
+            assert!(position > zero());
+            let length = self.lists[list].length;
+            if position >= length {
+                return self.append_node(list, position - length);
+            }
+            // zero() < position < length
+            let (node_position, index) = self.descend(list, position, true);
+            position = position - node_position;
+            list = match self.sublist(list, index) {
+                Some(sublist) => sublist,
+                None => return self.insert_sublist(list, index, position),
+            };
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lists[0].size == 1
+    }
+}
+
+impl<S: Spacing, const N: usize> ArraySpacedList<S, N> {
+    /// Returns the position and index of the last node in `list`, not in sublists, that is before
+    /// `target_position`, or at it if `inclusive` is true. Assumes there is such a node.
+    fn descend(&self, list: ListId, target_position: S, inclusive: bool) -> (S, usize) {
+        let ListHeader { size, depth, block, .. } = self.lists[list];
+        let mut position = S::zero();
+        let mut index = 0usize;
+        for degree in (0..depth).rev() {
+            let possibly_next_index = index + (1 << degree);
+            if possibly_next_index < size {
+                let possibly_next_position =
+                    position + self.slots[block + link_index(index, degree)].link;
+                if possibly_next_position < target_position
+                    || inclusive && possibly_next_position == target_position {
+                    position = possibly_next_position;
+                    index = possibly_next_index;
+                }
+            }
+        }
+        (position, index)
+    }
+
+    fn position_before_in(&self, list: ListId, target_position: S) -> Option<S> {
+        if target_position <= zero() {
+            return None;
+        }
+        let (position, index) = self.descend(list, target_position, false);
+        match self.not_empty_sublist(list, index) {
+            Some(sublist) =>
+                Some(position + self.position_before_in(sublist, target_position - position)?),
+            None => Some(position),
+        }
+    }
+
+    fn position_at_or_before_in(&self, list: ListId, target_position: S) -> Option<S> {
+        if target_position < zero() {
+            return None;
+        }
+        let (position, index) = self.descend(list, target_position, true);
+        match self.not_empty_sublist(list, index) {
+            Some(sublist) if position != target_position =>
+                Some(position
+                    + self.position_at_or_before_in(sublist, target_position - position)?),
+            _ => Some(position),
+        }
+    }
+
+    fn position_at_in(&self, list: ListId, target_position: S) -> Option<S> {
+        if target_position < zero() {
+            return None;
+        }
+        let (position, index) = self.descend(list, target_position, true);
+        if position == target_position {
+            return Some(position);
+        }
+        let sublist = self.not_empty_sublist(list, index)?;
+        Some(position + self.position_at_in(sublist, target_position - position)?)
+    }
+
+    fn position_at_or_after_in(&self, list: ListId, target_position: S) -> Option<S> {
+        if target_position < zero() {
+            return Some(zero());
+        }
+        if target_position > self.lists[list].length {
+            return None;
+        }
+        let (position, index) = self.descend(list, target_position, true);
+        if position == target_position {
+            return Some(position);
+        }
+        let in_sublist = self.not_empty_sublist(list, index)
+            .and_then(|sublist| self.position_at_or_after_in(sublist, target_position - position));
+        Some(position + in_sublist.unwrap_or_else(|| self.link(list, index, 0)))
+    }
+
+    fn position_after_in(&self, list: ListId, target_position: S) -> Option<S> {
+        if target_position < zero() {
+            return Some(zero());
+        }
+        if target_position >= self.lists[list].length {
+            return None;
+        }
+        let (position, index) = self.descend(list, target_position, true);
+        let in_sublist = self.not_empty_sublist(list, index)
+            .and_then(|sublist| self.position_after_in(sublist, target_position - position));
+        Some(position + in_sublist.unwrap_or_else(|| self.link(list, index, 0)))
+    }
+}
+// endregion
+
+// region backend
+impl<S: Spacing, const N: usize> SpacedListBackend<S> for ArraySpacedList<S, N> {
+    type Positions<'a> = ArrayPositions<'a, S, N> where S: 'a;
+
+    /// Panics if the node does not fit, use [ArraySpacedList::insert] to handle that.
+    fn insert(&mut self, position: S) {
+        ArraySpacedList::insert(self, position).unwrap()
+    }
+
+    fn position_before(&self, target_position: S) -> Option<S> {
+        self.position_before_in(0, target_position)
+    }
+
+    fn position_at_or_before(&self, target_position: S) -> Option<S> {
+        self.position_at_or_before_in(0, target_position)
+    }
+
+    fn position_at(&self, target_position: S) -> Option<S> {
+        self.position_at_in(0, target_position)
+    }
+
+    fn position_at_or_after(&self, target_position: S) -> Option<S> {
+        self.position_at_or_after_in(0, target_position)
+    }
+
+    fn position_after(&self, target_position: S) -> Option<S> {
+        self.position_after_in(0, target_position)
+    }
+
+    fn positions(&self) -> ArrayPositions<'_, S, N> {
+        ArrayPositions {
+            list: self,
+            current: 0,
+            index: 0,
+            position: zero(),
+            base: zero(),
+        }
+    }
+}
+
+/// Iterator over the positions of all nodes of an [ArraySpacedList], including sublists. It keeps
+/// no stack, but returns from a sublist to its parent through the parent's header.
+pub struct ArrayPositions<'a, S: Spacing, const N: usize> {
+    list: &'a ArraySpacedList<S, N>,
+    /// The list being iterated, the index and absolute position of the next node to visit in it,
+    /// and the absolute position of its node zero.
+    current: ListId,
+    index: usize,
+    position: S,
+    base: S,
+}
+
+impl<S: Spacing, const N: usize> Iterator for ArrayPositions<'_, S, N> {
+    type Item = S;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let ListHeader { size, parent, parent_index, .. } = self.list.lists[self.current];
+            if self.index >= size {
+                if self.current == 0 {
+                    return None;
+                }
+                // continue after the node this sublist hangs off, which is at the base of it and
+                // never the last node of the parent
+                self.position = self.base + self.list.link(parent, parent_index, 0);
+                self.base = self.base - self.list.node_position(parent, parent_index);
+                self.current = parent;
+                self.index = parent_index + 1;
+                continue;
+            }
+            let node_position = self.position;
+            let node_index = self.index;
+            // node zero of a sublist is the node it hangs off, which has been visited already
+            let visited = node_index == 0 && self.current != 0;
+            self.index += 1;
+            if let Some(sublist) = self.list.not_empty_sublist(self.current, node_index) {
+                self.current = sublist;
+                self.index = 0;
+                self.base = node_position;
+            } else if node_index + 1 < size {
+                self.position += self.list.link(self.current, node_index, 0);
+            }
+            if visited {
+                continue;
+            }
+            return Some(node_position);
+        }
+    }
+}
+// endregion
@@ -0,0 +1,281 @@
+//! Benchmarks, run with `cargo bench`. `cargo test` runs every benchmark once as a smoke test.
+
+extern crate test;
+
+use std::env;
+use test::{black_box, Bencher};
+use crate::{ArenaSpacedList, LineIndex, SpacedList, SpacedListBackend, UnchunkedSpacedList};
+
+/// Number of nodes in the benchmarked lists.
+const NODES: usize = 10_000;
+
+/// A prime larger than `NODES * STEP`, so that `positions` never repeats itself.
+const RANGE: usize = 100_003;
+const STEP: usize = 7_919;
+
+/// Distinct positive positions in a scattered order, so that inserting them produces many small,
+/// nested sublists.
+fn positions() -> impl Iterator<Item=usize> {
+    (1..=NODES).map(|n| n * STEP % RANGE)
+}
+
+// region nested and arena layout
+#[bench]
+fn bench_append_nested(bencher: &mut Bencher) {
+    bencher.iter(|| {
+        let mut list = SpacedList::<usize>::new();
+        for position in 1..=NODES {
+            list.insert(position);
+        }
+        list
+    });
+}
+
+#[bench]
+fn bench_append_arena(bencher: &mut Bencher) {
+    bencher.iter(|| {
+        let mut list = ArenaSpacedList::<usize>::new();
+        for position in 1..=NODES {
+            list.insert(position);
+        }
+        list
+    });
+}
+
+#[bench]
+fn bench_insert_scattered_nested(bencher: &mut Bencher) {
+    bencher.iter(|| {
+        let mut list = SpacedList::<usize>::new();
+        positions().for_each(|position| list.insert(position));
+        list
+    });
+}
+
+#[bench]
+fn bench_insert_scattered_arena(bencher: &mut Bencher) {
+    bencher.iter(|| {
+        let mut list = ArenaSpacedList::<usize>::new();
+        positions().for_each(|position| list.insert(position));
+        list
+    });
+}
+
+#[bench]
+fn bench_query_scattered_nested(bencher: &mut Bencher) {
+    let mut list = SpacedList::<usize>::new();
+    positions().for_each(|position| list.insert(position));
+    bencher.iter(|| {
+        for target in (0..RANGE).step_by(RANGE / NODES) {
+            black_box(list.node_at_or_before(target));
+        }
+    });
+}
+
+#[bench]
+fn bench_query_scattered_arena(bencher: &mut Bencher) {
+    let mut list = ArenaSpacedList::<usize>::new();
+    positions().for_each(|position| list.insert(position));
+    bencher.iter(|| {
+        for target in (0..RANGE).step_by(RANGE / NODES) {
+            black_box(list.node_at_or_before(target));
+        }
+    });
+}
+// endregion
+
+// region chunked and unchunked layout
+// appending, traversing and iterating, the priorities listed in unchunked.md
+
+fn append<B: SpacedListBackend<usize>>(bencher: &mut Bencher) {
+    bencher.iter(|| {
+        let mut list = B::default();
+        for position in 1..=NODES {
+            list.insert(position);
+        }
+        list
+    });
+}
+
+fn traverse<B: SpacedListBackend<usize>>(bencher: &mut Bencher) {
+    let mut list = B::default();
+    for position in 1..=NODES {
+        list.insert(position * 2);
+    }
+    bencher.iter(|| {
+        for target in 0..NODES * 2 {
+            black_box(list.position_at_or_before(target));
+        }
+    });
+}
+
+fn iterate<B: SpacedListBackend<usize>>(bencher: &mut Bencher) {
+    let mut list = B::default();
+    positions().for_each(|position| list.insert(position));
+    bencher.iter(|| list.positions().fold(0, |sum, position| sum ^ position));
+}
+
+#[bench]
+fn bench_append_chunked(bencher: &mut Bencher) {
+    append::<SpacedList<usize>>(bencher)
+}
+
+#[bench]
+fn bench_append_unchunked(bencher: &mut Bencher) {
+    append::<UnchunkedSpacedList<usize>>(bencher)
+}
+
+#[bench]
+fn bench_traverse_chunked(bencher: &mut Bencher) {
+    traverse::<SpacedList<usize>>(bencher)
+}
+
+#[bench]
+fn bench_traverse_unchunked(bencher: &mut Bencher) {
+    traverse::<UnchunkedSpacedList<usize>>(bencher)
+}
+
+#[bench]
+fn bench_iterate_chunked(bencher: &mut Bencher) {
+    iterate::<SpacedList<usize>>(bencher)
+}
+
+#[bench]
+fn bench_iterate_unchunked(bencher: &mut Bencher) {
+    iterate::<UnchunkedSpacedList<usize>>(bencher)
+}
+
+#[bench]
+fn bench_iterate_arena(bencher: &mut Bencher) {
+    iterate::<ArenaSpacedList<usize>>(bencher)
+}
+// endregion
+
+// region large lists
+/// Number of nodes in the large lists, read from `SPACED_LIST_BENCH_NODES`.
+///
+/// `cargo test` runs every benchmark once, so without the variable the lists stay small enough
+/// not to slow it down. Measure with 10^6 to 10^8 nodes, as far as memory allows.
+fn large_nodes() -> usize {
+    env::var("SPACED_LIST_BENCH_NODES").ok().and_then(|nodes| nodes.parse().ok()).unwrap_or(1 << 12)
+}
+
+/// Number of queries per iteration of the large benchmarks.
+const QUERIES: usize = 1 << 12;
+
+/// A large list with irregular distances between 1 and 16, and query targets spread over all of
+/// it in random order, so that neither the branch predictor nor the cache can guess the path.
+fn large_list() -> (SpacedList<usize>, Vec<usize>) {
+    let nodes = large_nodes();
+    let mut random = 0x2545F4914F6CDD1Du64;
+    let mut next_random = move || {
+        random ^= random << 13;
+        random ^= random >> 7;
+        random ^= random << 17;
+        random as usize
+    };
+    let mut list = SpacedList::with_capacity(nodes);
+    for _ in 1..nodes {
+        list.append_node(next_random() % 16 + 1);
+    }
+    let targets = (0..QUERIES).map(|_| next_random() % (list.length + 1)).collect();
+    (list, targets)
+}
+
+#[bench]
+fn bench_traverse_large_shallow(bencher: &mut Bencher) {
+    let (list, targets) = large_list();
+    bencher.iter(|| {
+        for &target in &targets {
+            black_box(list.node_at_or_before_shallow(target));
+        }
+    });
+}
+
+#[bench]
+fn bench_traverse_large(bencher: &mut Bencher) {
+    let (list, targets) = large_list();
+    bencher.iter(|| {
+        for &target in &targets {
+            black_box(list.node_before(target));
+        }
+    });
+}
+// endregion
+
+// region batch queries
+/// A large list and `QUERIES` sorted targets evenly spread over it, like one per pixel column.
+fn large_list_and_sorted_targets() -> (SpacedList<usize>, Vec<usize>) {
+    let (list, _) = large_list();
+    let targets = (0..QUERIES).map(|query| query * list.length / QUERIES).collect();
+    (list, targets)
+}
+
+#[bench]
+fn bench_sorted_single(bencher: &mut Bencher) {
+    let (list, targets) = large_list_and_sorted_targets();
+    bencher.iter(|| {
+        for &target in &targets {
+            black_box(list.node_at_or_before(target));
+        }
+    });
+}
+
+#[bench]
+fn bench_sorted_batch(bencher: &mut Bencher) {
+    let (list, targets) = large_list_and_sorted_targets();
+    bencher.iter(|| black_box(list.batch_at_or_before(&targets)));
+}
+// endregion
+
+// region parallel operations
+/// Number of threads for the parallel benchmarks, taken from `SPACED_LIST_BENCH_THREADS` and
+/// defaulting to the available parallelism.
+fn threads() -> usize {
+    env::var("SPACED_LIST_BENCH_THREADS").ok().and_then(|threads| threads.parse().ok())
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |threads| threads.get()))
+}
+
+#[bench]
+fn bench_count_large(bencher: &mut Bencher) {
+    let (list, _) = large_list();
+    bencher.iter(|| black_box(list.positions().count()));
+}
+
+#[cfg(feature = "std")]
+#[bench]
+fn bench_par_count_large(bencher: &mut Bencher) {
+    let (list, _) = large_list();
+    let threads = threads();
+    bencher.iter(|| black_box(list.par_count(threads)));
+}
+
+#[cfg(feature = "std")]
+#[bench]
+fn bench_par_sorted_batch(bencher: &mut Bencher) {
+    let (list, targets) = large_list_and_sorted_targets();
+    let threads = threads();
+    bencher.iter(|| black_box(list.par_batch_at_or_before(&targets, threads)));
+}
+// endregion
+
+// region line index
+/// A line index of a large text with `large_nodes` lines of irregular lengths between 1 and 80.
+fn large_line_index() -> LineIndex {
+    let text: String = (0..large_nodes()).map(|line| "x".repeat(line * STEP % 80) + "\n").collect();
+    LineIndex::new(&text)
+}
+
+/// Joins a line in the first half of the text with the next one and splits them again, a
+/// different line each time, so that every join removes a line start of the original index.
+#[bench]
+fn bench_line_index_join_lines(bencher: &mut Bencher) {
+    let mut index = large_line_index();
+    let mut line = 0;
+    bencher.iter(|| {
+        line = (line + 1) % (large_nodes() / 2);
+        let line_feed = index.line_col_to_offset(line + 1, 0).unwrap() - 1;
+        index.apply_edit(line_feed..line_feed + 1, "");
+        index.apply_edit(line_feed..line_feed, "\n");
+    });
+}
+// endregion
@@ -0,0 +1,107 @@
+//! Sequences of [Edit]s applied to a [SpacedList], which translate positions held elsewhere, such
+//! as cursors or diagnostics, from before the edits to after them.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::{Aggregate, Edit, SpacedList, Spacing};
+
+// region bias
+/// Which side a position at the start of a shift forward sticks to, as it could either stay where
+/// it is or move with the nodes at and after it.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum Bias {
+    /// Stays before the space the shift adds.
+    Before,
+    /// Moves with the nodes after it, like a node at that position does.
+    #[default]
+    After,
+}
+// endregion
+
+// region change set
+/// The edits applied to a [SpacedList] one after the other, which can map positions from before
+/// all of them to after all of them.
+///
+/// Only shifts change positions: nodes that are inserted or removed leave the others where they
+/// are. Shifts are recorded as starting at the first node they move, like in a
+/// [History](crate::History). A shift forward by `distance` from `position` then moves all
+/// positions at or after it, so the positions in `position..position + distance` are not the
+/// image of any position. A shift back removes the space `position - distance..position`, which
+/// has no nodes in it, so the positions in there all map to `position - distance`, and mapping back
+/// cannot tell them apart any more.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChangeSet<S: Spacing> {
+    edits: Vec<Edit<S>>,
+}
+
+impl<S: Spacing> Default for ChangeSet<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Spacing> ChangeSet<S> {
+    pub fn new() -> Self {
+        Self { edits: vec![] }
+    }
+
+    pub fn edits(&self) -> &[Edit<S>] {
+        &self.edits
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Applies `edit` to `list` and records it, unless it is a shift that does not move any node.
+    pub fn apply<A: Aggregate>(&mut self, list: &mut SpacedList<S, A>, edit: Edit<S>) {
+        let recorded = edit.recorded(list);
+        edit.apply(list);
+        self.edits.extend(recorded)
+    }
+
+    /// Applies all edits of this change set to `list`, which must be in the state they were first
+    /// applied to.
+    pub fn apply_to<A: Aggregate>(&self, list: &mut SpacedList<S, A>) {
+        for &edit in &self.edits {
+            edit.apply(list)
+        }
+    }
+
+    /// Maps `position` from before the edits of this change set to after them. `bias` decides
+    /// where a position that a shift forward starts at ends up.
+    ///
+    /// Takes O(e) for e edits.
+    pub fn map(&self, position: S, bias: Bias) -> S {
+        self.edits.iter().fold(position, |position, &edit| match edit {
+            Edit::Insert(_) | Edit::Remove(_) => position,
+            Edit::ShiftForward { position: start, distance } =>
+                if position > start || position == start && bias == Bias::After {
+                    position + distance
+                } else {
+                    position
+                },
+            Edit::ShiftBackward { position: start, distance } =>
+                if position >= start {
+                    position - distance
+                } else if position > start - distance {
+                    start - distance
+                } else {
+                    position
+                },
+        })
+    }
+
+    /// Returns the change set that applies the edits of this one and then those of `later`.
+    pub fn compose(mut self, later: &Self) -> Self {
+        self.edits.extend_from_slice(&later.edits);
+        self
+    }
+
+    /// Returns the change set that reverts this one, which maps positions from after its edits back
+    /// to before them.
+    pub fn invert(&self) -> Self {
+        Self { edits: self.edits.iter().rev().map(|edit| edit.inverse()).collect() }
+    }
+}
+// endregion
@@ -0,0 +1,189 @@
+//! The shortest sequence of [Edit]s that turns one [SpacedList] into another, where a shift of a
+//! whole suffix of nodes counts as a single edit, instead of removing and inserting each of them.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::{Aggregate, Edit, SpacedList, SpacedListBackend, Spacing};
+
+// region offset
+/// How far the nodes that are left of the first list have moved so far.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+struct Offset<S> {
+    forward: bool,
+    distance: S,
+}
+
+impl<S: Spacing> Offset<S> {
+    /// Returns the offset that moves `from` to `to`.
+    fn between(from: S, to: S) -> Self {
+        if to >= from {
+            Offset { forward: true, distance: to - from }
+        } else {
+            Offset { forward: false, distance: from - to }
+        }
+    }
+
+    /// Returns the offset that moves a node from where this offset moves it to where `other` does.
+    /// That is how much the gap before the node of a shift changes, which fits into `S`.
+    fn to(self, other: Self) -> Self {
+        match (self.forward, other.forward) {
+            (true, true) => Offset::between(self.distance, other.distance),
+            (false, false) => Offset::between(other.distance, self.distance),
+            (forward, _) => Offset { forward: !forward, distance: self.distance + other.distance },
+        }
+    }
+
+    /// Returns where a node at `position` is after moving by this offset, which never moves a node
+    /// to or before zero.
+    fn apply(self, position: S) -> S {
+        if self.forward {
+            position + self.distance
+        } else {
+            position - self.distance
+        }
+    }
+}
+// endregion
+
+// region diff
+/// What the search did to get from the state before to a state.
+#[derive(Copy, Clone)]
+enum Step {
+    Start,
+    /// Removed the next node of the first list.
+    Remove,
+    /// Inserted the next node of the second list.
+    Insert,
+    /// Moved the next node of the first list, and all after it, onto the next node of the second
+    /// list.
+    Shift,
+}
+
+/// A state of the search: the next nodes of both lists that are neither matched nor removed or
+/// inserted, and the offset of the nodes left of the first list.
+struct State<S> {
+    old: usize,
+    new: usize,
+    offset: Offset<S>,
+    step: Step,
+    previous: usize,
+}
+
+/// Returns the shortest sequence of inserts, removals and shifts that turns `old` into `new`. Nodes
+/// that keep their position relative to the nodes before them are matched together, so a shift of
+/// a whole suffix of nodes is one edit.
+///
+/// The removals come first, then the shifts back and then the shifts forward, each from the front
+/// to the back, and the inserts last. This never puts two nodes at the same position, and never
+/// moves a node past the largest position of either list, which may not fit into `S`. The edits can be applied through a
+/// [History](crate::History) or a [ChangeSet](crate::ChangeSet).
+///
+/// Searches by the number of edits like a line diff does, which takes O((n + m) d) for lists of
+/// n and m nodes and d edits, times a logarithmic factor.
+pub fn diff<S: Spacing, A: Aggregate>(old: &SpacedList<S, A>, new: &SpacedList<S, A>)
+                                      -> Vec<Edit<S>> {
+    // without node zero
+    let old: Vec<S> = old.positions().skip(1).collect();
+    let new: Vec<S> = new.positions().skip(1).collect();
+    // matches nodes that are at the same position after moving by the offset, which never takes
+    // an edit that could be saved by not matching them
+    let slide = |mut state: State<S>| {
+        while state.old < old.len() && state.new < new.len()
+            && Offset::between(old[state.old], new[state.new]) == state.offset {
+            state.old += 1;
+            state.new += 1;
+        }
+        state
+    };
+
+    let mut states = vec![slide(State {
+        old: 0,
+        new: 0,
+        offset: Offset::between(S::zero(), S::zero()),
+        step: Step::Start,
+        previous: 0,
+    })];
+    // the fewest edits each pair of next nodes has been reached with, and with which offsets
+    let mut reached: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+    let mut visited: BTreeSet<(usize, usize, Offset<S>)> = BTreeSet::new();
+    let mut frontier = vec![0];
+    for edits in 0.. {
+        let mut next_frontier = vec![];
+        for &index in &frontier {
+            let State { old: old_index, new: new_index, offset, .. } = states[index];
+            if old_index == old.len() && new_index == new.len() {
+                return script(&old, &new, &states, index);
+            }
+            let mut successors = vec![];
+            if old_index < old.len() {
+                successors.push((old_index + 1, new_index, offset, Step::Remove));
+            }
+            if new_index < new.len() {
+                successors.push((old_index, new_index + 1, offset, Step::Insert));
+            }
+            if old_index < old.len() && new_index < new.len() {
+                let offset = Offset::between(old[old_index], new[new_index]);
+                successors.push((old_index + 1, new_index + 1, offset, Step::Shift));
+            }
+            for (old_index, new_index, offset, step) in successors {
+                let state =
+                    slide(State { old: old_index, new: new_index, offset, step, previous: index });
+                // a state reached with fewer edits is at most one shift away from any offset
+                if *reached.entry((state.old, state.new)).or_insert(edits + 1) <= edits
+                    || !visited.insert((state.old, state.new, state.offset)) {
+                    continue;
+                }
+                next_frontier.push(states.len());
+                states.push(state);
+            }
+        }
+        frontier = next_frontier;
+    }
+    unreachable!()
+}
+
+/// Returns the edits of the steps that led to the state at `index`.
+fn script<S: Spacing>(old: &[S], new: &[S], states: &[State<S>], mut index: usize)
+                      -> Vec<Edit<S>> {
+    let mut removals = vec![];
+    let mut shifts = vec![];
+    let mut inserts = vec![];
+    while index != 0 {
+        let state = &states[index];
+        let previous = &states[state.previous];
+        match state.step {
+            Step::Start => break,
+            Step::Remove => removals.push(previous.old),
+            Step::Insert => inserts.push(Edit::Insert(new[previous.new])),
+            Step::Shift => shifts.push((previous.old, previous.offset.to(state.offset))),
+        }
+        index = state.previous;
+    }
+    // where the nodes of the first list are that are not removed
+    let mut positions: Vec<Option<S>> = old.iter().copied().map(Some).collect();
+    for &removed in &removals {
+        positions[removed] = None;
+    }
+    let mut edits: Vec<Edit<S>> =
+        removals.into_iter().rev().map(|removed| Edit::Remove(old[removed])).collect();
+    // each shift only changes the gap before its node, so they can be done in any order, and
+    // doing the shifts back first keeps each node between zero and where it is in either list
+    shifts.reverse();
+    for forward in [false, true] {
+        for &(index, offset) in shifts.iter().filter(|(_, offset)| offset.forward == forward) {
+            let position = positions[index].unwrap();
+            edits.push(if forward {
+                Edit::ShiftForward { position, distance: offset.distance }
+            } else {
+                Edit::ShiftBackward { position, distance: offset.distance }
+            });
+            for position in positions[index..].iter_mut().flatten() {
+                *position = offset.apply(*position);
+            }
+        }
+    }
+    edits.extend(inserts.into_iter().rev());
+    edits
+}
+// endregion
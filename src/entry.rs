@@ -0,0 +1,200 @@
+//! An entry API for the values of a [SpacedList], like the one of [BTreeMap], which finds the node
+//! at a position once and then reads, modifies or inserts it without searching again.
+//!
+//! [BTreeMap]: alloc::collections::BTreeMap
+
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::{Aggregate, Position, SpacedList, Spacing, zero};
+
+// region spaced list entry
+impl<P: Position<S>, A: Aggregate, S: Spacing> SpacedList<P, A, S> {
+    /// Returns the entry for the node at `position`, which must be after node zero, whether there
+    /// is one or not.
+    ///
+    /// Pushes the pending updates along the way down, so the entry reads and writes values
+    /// directly, and only walks back up the sublists it went through to update their links.
+    pub fn entry(&mut self, position: P) -> Entry<'_, P, A, S> {
+        let mut relative_position = self.relative_or_zero(position);
+        assert!(relative_position > zero());
+        let mut path = vec![];
+        let mut list = &mut *self;
+        let vacancy = loop {
+            if relative_position > list.length {
+                break Vacancy::End;
+            }
+            let (node_position, index) = list.descend::<true>(relative_position);
+            if node_position == relative_position {
+                // index is not zero, as relative_position is positive
+                list.push_down_above(index - 1);
+                return Entry::Occupied(OccupiedEntry { list: self, path, index, position });
+            }
+            list.push_down_above(index);
+            match list.get_not_empty_sublist_at_index_mut(index) {
+                Some(sublist) => {
+                    path.push(index);
+                    relative_position = relative_position - node_position;
+                    list = sublist;
+                }
+                None => break Vacancy::Sublist { index, node_position },
+            }
+        };
+        Entry::Vacant(VacantEntry { list: self, path, vacancy, relative_position, position })
+    }
+
+    /// Calls `f` with the list at the end of `path`, a sequence of sublist indices, then adds
+    /// `added` nodes to the links above the sublists along `path` and updates their aggregates.
+    fn at_path<R>(&mut self, path: &[usize], added: usize, f: impl FnOnce(&mut Self) -> R) -> R {
+        let Some((&index, rest)) = path.split_first() else {
+            return f(self);
+        };
+        let sublist = self.sublists[index].as_mut().unwrap();
+        let result = sublist.at_path(rest, added, f);
+        self.add_to_node_counts(index, added);
+        self.update_aggregates_above(index);
+        result
+    }
+
+    /// Returns the list at the end of `path`, a sequence of sublist indices.
+    fn list_at_path(&self, path: &[usize]) -> &Self {
+        path.iter().fold(self, |list, &index| list.sublists[index].as_ref().unwrap())
+    }
+}
+// endregion
+
+// region entry
+/// The node at a position of a [SpacedList], or the place for it, as returned by
+/// [SpacedList::entry].
+pub enum Entry<'a, P: Position<S>, A: Aggregate, S: Spacing = P> {
+    Occupied(OccupiedEntry<'a, P, A, S>),
+    Vacant(VacantEntry<'a, P, A, S>),
+}
+
+impl<'a, P: Position<S>, A: Aggregate, S: Spacing> Entry<'a, P, A, S> {
+    pub fn position(&self) -> P {
+        match self {
+            Entry::Occupied(entry) => entry.position,
+            Entry::Vacant(entry) => entry.position,
+        }
+    }
+
+    /// Inserts a node with `value` if there is none, and returns the value of the node.
+    pub fn or_insert(self, value: A::Value) -> A::Value {
+        self.or_insert_with(|| value)
+    }
+
+    /// Inserts a node with the value returned by `f` if there is none, and returns the value of the
+    /// node.
+    pub fn or_insert_with(self, f: impl FnOnce() -> A::Value) -> A::Value {
+        match self {
+            Entry::Occupied(entry) => entry.get(),
+            Entry::Vacant(entry) => entry.insert(f()),
+        }
+    }
+
+    /// Inserts a node with the default value if there is none, and returns the value of the node.
+    pub fn or_default(self) -> A::Value {
+        self.or_insert_with(A::Value::default)
+    }
+
+    /// Calls `f` with the value of the node if there is one.
+    pub fn and_modify(self, f: impl FnOnce(&mut A::Value)) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                entry.modify(f);
+                Entry::Occupied(entry)
+            }
+            vacant => vacant,
+        }
+    }
+}
+
+/// The node at a position of a [SpacedList].
+pub struct OccupiedEntry<'a, P: Position<S>, A: Aggregate, S: Spacing = P> {
+    pub(crate) list: &'a mut SpacedList<P, A, S>,
+    /// Indices of the sublists that lead to the list the node is in.
+    pub(crate) path: Vec<usize>,
+    /// Index of the node in that list, which is never zero.
+    pub(crate) index: usize,
+    pub(crate) position: P,
+}
+
+impl<'a, P: Position<S>, A: Aggregate, S: Spacing> OccupiedEntry<'a, P, A, S> {
+    pub fn position(&self) -> P {
+        self.position
+    }
+
+    pub fn get(&self) -> A::Value {
+        self.list.list_at_path(&self.path).values[self.index - 1]
+    }
+
+    /// Calls `f` with the value of the node, and updates the aggregates for its new value.
+    pub fn modify(&mut self, f: impl FnOnce(&mut A::Value)) {
+        let index = self.index;
+        self.list.at_path(&self.path, 0, |list| {
+            f(&mut list.values[index - 1]);
+            list.update_aggregates_above(index - 1);
+        });
+        self.list.validate_after_mutation()
+    }
+
+    /// Sets the value of the node and returns the previous one.
+    pub fn insert(&mut self, value: A::Value) -> A::Value {
+        let mut previous = value;
+        self.modify(|current| core::mem::swap(current, &mut previous));
+        previous
+    }
+
+    /// Removes the node and returns its value, see [SpacedList::remove].
+    pub fn remove(self) -> A::Value {
+        let value = self.get();
+        self.list.remove(self.position);
+        value
+    }
+}
+
+/// Where a vacant node goes in the list at the end of the path of a [VacantEntry].
+enum Vacancy<S> {
+    /// After the last node.
+    End,
+    /// Into the sublist after the node at `index`, which is at `node_position`, and which is empty
+    /// or absent.
+    Sublist { index: usize, node_position: S },
+}
+
+/// The place for a node at a position of a [SpacedList] that has none.
+pub struct VacantEntry<'a, P: Position<S>, A: Aggregate, S: Spacing = P> {
+    pub(crate) list: &'a mut SpacedList<P, A, S>,
+    /// Indices of the sublists that lead to the list the node goes into.
+    pub(crate) path: Vec<usize>,
+    vacancy: Vacancy<S>,
+    /// The position of the node in that list.
+    pub(crate) relative_position: S,
+    position: P,
+}
+
+impl<'a, P: Position<S>, A: Aggregate, S: Spacing> VacantEntry<'a, P, A, S> {
+    pub fn position(&self) -> P {
+        self.position
+    }
+
+    /// Inserts a node with `value` and returns `value`.
+    pub fn insert(self, value: A::Value) -> A::Value {
+        let VacantEntry { list, path, vacancy, relative_position, .. } = self;
+        let mut handles = list.take_handles();
+        list.at_path(&path, 1, |list| match vacancy {
+            Vacancy::End => list.append_node_with_value(relative_position - list.length, value),
+            Vacancy::Sublist { index, node_position } => {
+                // the sublist is empty, so this appends to it
+                let sublist = list.get_sublist_at_index(index, &mut handles);
+                sublist.append_node_with_value(relative_position - node_position, value);
+                list.add_to_node_counts(index, 1);
+                list.update_aggregates_above(index)
+            }
+        });
+        list.handles = Some(handles);
+        list.validate_after_mutation();
+        value
+    }
+}
+// endregion
@@ -0,0 +1,244 @@
+//! An undo/redo history on top of a [SpacedList], which records the edits made through it instead
+//! of copies of the list.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::{Aggregate, NodeId, SpacedList, SpacedListBackend, Spacing, zero};
+
+// region edit
+/// A single mutation of a [SpacedList], as recorded by a [History].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Edit<S: Spacing> {
+    Insert(S),
+    Remove(S),
+    ShiftForward { position: S, distance: S },
+    ShiftBackward { position: S, distance: S },
+}
+
+impl<S: Spacing> Edit<S> {
+    /// Returns the edit that reverts this one.
+    pub fn inverse(self) -> Self {
+        match self {
+            Edit::Insert(position) => Edit::Remove(position),
+            Edit::Remove(position) => Edit::Insert(position),
+            Edit::ShiftForward { position, distance } =>
+                Edit::ShiftBackward { position: position + distance, distance },
+            Edit::ShiftBackward { position, distance } =>
+                Edit::ShiftForward { position: position - distance, distance },
+        }
+    }
+
+    /// Returns this edit as it is recorded before it is applied to `list`, or None if it would not
+    /// change anything. Shifts are recorded as starting at the first node they move.
+    ///
+    /// A shift back from `position` can move nodes to before `position - distance`, and the shift
+    /// forward from there that reverts it would then move too many.
+    pub(crate) fn recorded<A: Aggregate>(self, list: &SpacedList<S, A>) -> Option<Self> {
+        let first_moved = |position: S, distance: S| {
+            assert!(position > list.origin());
+            list.position_at_or_after(position).filter(|_| distance > zero())
+        };
+        match self {
+            Edit::ShiftForward { position, distance } => first_moved(position, distance)
+                .map(|position| Edit::ShiftForward { position, distance }),
+            Edit::ShiftBackward { position, distance } => first_moved(position, distance)
+                .map(|position| Edit::ShiftBackward { position, distance }),
+            edit => Some(edit),
+        }
+    }
+
+    pub(crate) fn apply<A: Aggregate>(self, list: &mut SpacedList<S, A>) {
+        match self {
+            Edit::Insert(position) => list.insert(position),
+            Edit::Remove(position) => assert!(list.remove(position)),
+            Edit::ShiftForward { position, distance } => list.shift_forward(position, distance),
+            Edit::ShiftBackward { position, distance } => list.shift_backward(position, distance),
+        }
+    }
+
+    /// Returns what applying this edit to `list` would take from it: the value and handle of the
+    /// node that a removal removes.
+    pub(crate) fn removed<A: Aggregate>(self, list: &SpacedList<S, A>)
+                                        -> Option<Removed<A::Value>> {
+        let Edit::Remove(position) = self else {
+            return None;
+        };
+        Some(Removed { value: list.value_at(position)?, id: list.id_at(position) })
+    }
+
+    /// Reverts this edit on `list`, after which it is as it was before the edit was applied, given
+    /// what applying it took, see [Self::removed].
+    pub(crate) fn revert<A: Aggregate>(self, list: &mut SpacedList<S, A>,
+                                       removed: Option<Removed<A::Value>>) {
+        match (self, removed) {
+            (Edit::Remove(position), Some(Removed { value, id })) => {
+                list.insert_with_value(position, value);
+                if let Some(id) = id {
+                    list.assign_id(position, id);
+                }
+            }
+            (edit, _) => edit.inverse().apply(list),
+        }
+    }
+}
+
+/// The value and handle of a node that an [Edit::Remove] removed, which undoing it brings back.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Removed<V> {
+    value: V,
+    id: Option<NodeId>,
+}
+
+/// An edit as a [History] records it, together with what it removed.
+#[derive(Copy, Clone, Debug)]
+struct Recorded<S: Spacing, V> {
+    edit: Edit<S>,
+    removed: Option<Removed<V>>,
+}
+// endregion
+
+// region history
+/// A [SpacedList] that remembers the edits made through it, so they can be undone and redone.
+///
+/// Edits made between [begin_group](History::begin_group) and the matching
+/// [end_group](History::end_group) are undone and redone as one step. Groups can be nested, in
+/// which case only the outermost one counts.
+pub struct History<S: Spacing, A: Aggregate = ()> {
+    list: SpacedList<S, A>,
+    undo_stack: Vec<Vec<Recorded<S, A::Value>>>,
+    redo_stack: Vec<Vec<Recorded<S, A::Value>>>,
+    group: Vec<Recorded<S, A::Value>>,
+    group_depth: usize,
+}
+
+impl<S: Spacing, A: Aggregate> Default for History<S, A> {
+    fn default() -> Self {
+        Self::new(SpacedList::default())
+    }
+}
+
+impl<S: Spacing, A: Aggregate> History<S, A> {
+    /// Starts an empty history of `list`.
+    pub fn new(list: SpacedList<S, A>) -> Self {
+        Self {
+            list,
+            undo_stack: vec![],
+            redo_stack: vec![],
+            group: vec![],
+            group_depth: 0,
+        }
+    }
+
+    pub fn list(&self) -> &SpacedList<S, A> {
+        &self.list
+    }
+
+    pub fn into_list(self) -> SpacedList<S, A> {
+        self.list
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Applies `edit` and records it, which discards everything that could be redone. Undoing a
+    /// removal brings back the value and handle of the removed node.
+    pub fn apply(&mut self, edit: Edit<S>) {
+        let removed = edit.removed(&self.list);
+        edit.apply(&mut self.list);
+        self.record(Recorded { edit, removed })
+    }
+
+    /// Records `recorded`, which has been applied already.
+    fn record(&mut self, recorded: Recorded<S, A::Value>) {
+        self.redo_stack.clear();
+        self.group.push(recorded);
+        if self.group_depth == 0 {
+            self.end_step();
+        }
+    }
+
+    pub fn insert(&mut self, position: S) {
+        self.apply(Edit::Insert(position))
+    }
+
+    /// Removes the node at `position` and returns whether there was one. Nothing is recorded if
+    /// there was not.
+    pub fn remove(&mut self, position: S) -> bool {
+        let edit = Edit::Remove(position);
+        let removed = edit.removed(&self.list);
+        if !self.list.remove(position) {
+            return false;
+        }
+        self.record(Recorded { edit, removed });
+        true
+    }
+
+    /// Moves all nodes at or after `position` forward by `distance`. Nothing is recorded if that
+    /// does not move any node.
+    pub fn shift_forward(&mut self, position: S, distance: S) {
+        let edit = Edit::ShiftForward { position, distance };
+        if let Some(edit) = edit.recorded(&self.list) {
+            self.apply(edit)
+        }
+    }
+
+    /// Moves all nodes at or after `position` back by `distance`. Nothing is recorded if that does
+    /// not move any node.
+    pub fn shift_backward(&mut self, position: S, distance: S) {
+        let edit = Edit::ShiftBackward { position, distance };
+        if let Some(edit) = edit.recorded(&self.list) {
+            self.apply(edit)
+        }
+    }
+
+    pub fn begin_group(&mut self) {
+        self.group_depth += 1;
+    }
+
+    pub fn end_group(&mut self) {
+        assert!(self.group_depth > 0, "no group to end");
+        self.group_depth -= 1;
+        if self.group_depth == 0 {
+            self.end_step();
+        }
+    }
+
+    /// Makes the edits since the last step one step of their own, unless there are none.
+    fn end_step(&mut self) {
+        if !self.group.is_empty() {
+            self.undo_stack.push(core::mem::take(&mut self.group));
+        }
+    }
+
+    /// Reverts the last step and returns whether there was one.
+    pub fn undo(&mut self) -> bool {
+        assert_eq!(self.group_depth, 0, "cannot undo inside a group");
+        let Some(step) = self.undo_stack.pop() else {
+            return false;
+        };
+        for recorded in step.iter().rev() {
+            recorded.edit.revert(&mut self.list, recorded.removed);
+        }
+        self.redo_stack.push(step);
+        true
+    }
+
+    /// Repeats the last step that was undone and returns whether there was one.
+    pub fn redo(&mut self) -> bool {
+        assert_eq!(self.group_depth, 0, "cannot redo inside a group");
+        let Some(step) = self.redo_stack.pop() else {
+            return false;
+        };
+        for recorded in &step {
+            recorded.edit.apply(&mut self.list);
+        }
+        self.undo_stack.push(step);
+        true
+    }
+}
+// endregion
@@ -1,25 +1,272 @@
-#![feature(trait_alias)]
 #![feature(int_log)]
 #![feature(default_free_fn)]
 #![feature(option_get_or_insert_default)]
 #![allow(unused)]
+#![cfg_attr(test, feature(test))]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-use std::default::default;
-use std::fmt;
-use std::fmt::{Debug, Formatter, Write};
-use std::iter::empty;
-use std::num::NonZeroU64;
-use std::ops::{Add, AddAssign, Index, IndexMut, Sub};
-use std::ptr::NonNull;
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::default::default;
+use core::fmt;
+use core::fmt::{Debug, Formatter, Write};
+use core::iter::empty;
+use core::num::NonZeroU64;
+use core::ops::{Add, AddAssign, Index, IndexMut, Range, Sub};
+use core::time::Duration;
 use indenter::{indented, Indented};
-use num_traits::{Zero, zero};
 
-pub trait Spacing = Add<Output=Self> + AddAssign + Sub<Output=Self> + Zero + Ord + Copy;
+mod aggregate;
+mod arena;
+mod array;
+mod change_set;
+mod diff;
+mod entry;
+mod history;
+mod line_index;
+mod metric;
+mod node_id;
+mod overflow;
+mod persistent;
+mod tombstone;
+mod unchunked;
+
+pub use aggregate::{Aggregate, Count, Max, Min, RangeUpdate, Sum, Update};
+pub use arena::ArenaSpacedList;
+pub use array::{ArraySpacedList, CapacityError};
+pub use change_set::{Bias, ChangeSet};
+pub use diff::diff;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use history::{Edit, History};
+pub use line_index::LineIndex;
+pub use metric::{Component, Metric, Metrics, Whole};
+pub use node_id::NodeId;
+pub use overflow::{CheckedAggregate, Overflow};
+pub use persistent::PersistentSpacedList;
+pub use unchunked::UnchunkedSpacedList;
+use node_id::{Handles, ListKey, ROOT};
+
+// region spacing
+/// The distances between the nodes of a spaced list, which are also the positions of the nodes, as
+/// their distances from node zero, unless the list has positions of another [Position] type.
+///
+/// Implemented for all primitive integers and [Duration]. A unit type of your own only needs to
+/// add, subtract and compare, and have a zero, for example:
+///
+/// ```
+/// # use core::ops::{Add, AddAssign, Sub};
+/// # use spaced_list_3::{SpacedList, SpacedListBackend, Spacing};
+/// #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+/// struct Pixels(u32);
+///
+/// impl Add for Pixels {
+///     type Output = Self;
+///
+///     fn add(self, other: Self) -> Self {
+///         Pixels(self.0 + other.0)
+///     }
+/// }
+///
+/// impl AddAssign for Pixels {
+///     fn add_assign(&mut self, other: Self) {
+///         self.0 += other.0
+///     }
+/// }
+///
+/// impl Sub for Pixels {
+///     type Output = Self;
+///
+///     fn sub(self, other: Self) -> Self {
+///         Pixels(self.0 - other.0)
+///     }
+/// }
+///
+/// impl Spacing for Pixels {
+///     fn zero() -> Self {
+///         Pixels(0)
+///     }
+/// }
+///
+/// let mut list = SpacedList::<Pixels>::default();
+/// list.insert(Pixels(12));
+/// assert_eq!(list.position_at_or_after(Pixels(1)), Some(Pixels(12)));
+/// ```
+pub trait Spacing: Add<Output=Self> + AddAssign + Sub<Output=Self> + Ord + Copy {
+    /// Returns the distance from a position to itself, which is the position of node zero.
+    fn zero() -> Self;
+
+    /// Returns whether this is the distance from a position to itself. The default compares it to
+    /// [Self::zero], which a type can replace with a cheaper check.
+    fn is_zero(&self) -> bool {
+        *self == Self::zero()
+    }
+}
+
+macro_rules! integer_spacing {
+    ($($integer: ty),+) => {
+        $(impl Spacing for $integer {
+            fn zero() -> Self {
+                0
+            }
+        })+
+    };
+}
+
+integer_spacing!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+impl Spacing for Duration {
+    fn zero() -> Self {
+        Duration::ZERO
+    }
+}
+
+/// The positions of the nodes of a spaced list with distances of type `S` between them, such as
+/// `Instant`s with [Duration]s between them. A list with positions of type `S` itself measures them
+/// from node zero at zero, any other list from the origin it is made with, see
+/// [SpacedList::with_origin].
+///
+/// Implemented for every type that a distance can be added to, and that gives the distance between
+/// two positions when subtracted.
+pub trait Position<S: Spacing>: Add<S, Output=Self> + Sub<Output=S> + Ord + Copy {}
+
+impl<P: Add<S, Output=P> + Sub<Output=S> + Ord + Copy, S: Spacing> Position<S> for P {}
+
+/// A delta that moves positions of type `S` forward if it is positive and back if it is negative,
+/// as passed to [SpacedList::shift].
+///
+/// Implemented for every primitive integer and [Duration] as a delta of itself, and for every
+/// signed integer as a delta of the unsigned integer of the same width, so that lists of unsigned
+/// positions can be shifted back by negative deltas.
+pub trait Delta<S: Spacing>: Copy {
+    /// Returns the distance this delta moves by, and whether it moves back, or None if it moves
+    /// back further than any distance of type `S` goes, such as `i8::MIN` does for `i8`.
+    fn distance(self) -> Option<(S, bool)>;
+}
+
+macro_rules! unsigned_delta {
+    ($($integer: ty),+) => {
+        $(impl Delta<$integer> for $integer {
+            fn distance(self) -> Option<($integer, bool)> {
+                Some((self, false))
+            }
+        })+
+    };
+}
+
+unsigned_delta!(u8, u16, u32, u64, u128, usize);
+
+macro_rules! signed_delta {
+    ($($integer: ty: $unsigned: ty),+) => {
+        $(impl Delta<$integer> for $integer {
+            fn distance(self) -> Option<($integer, bool)> {
+                Some((self.checked_abs()?, self < 0))
+            }
+        }
+
+        impl Delta<$unsigned> for $integer {
+            fn distance(self) -> Option<($unsigned, bool)> {
+                Some((self.unsigned_abs(), self < 0))
+            }
+        })+
+    };
+}
+
+signed_delta!(i8: u8, i16: u16, i32: u32, i64: u64, i128: u128, isize: usize);
+
+impl Delta<Duration> for Duration {
+    fn distance(self) -> Option<(Duration, bool)> {
+        Some((self, false))
+    }
+}
+
+/// Returns the zero of `S`, wherever its type can be inferred.
+fn zero<S: Spacing>() -> S {
+    S::zero()
+}
+// endregion
 
 // region helper functions
+/// Index of the link of degree `degree` that starts at or before the node at `node_index`, in the
+/// in-order layout (`0102010`) of [ArenaSpacedList], [ArraySpacedList] and the leaves of
+/// [PersistentSpacedList].
 const fn link_index(node_index: usize, degree: usize) -> usize {
     (((node_index >> degree << 1) + 1) << degree) - 1
 }
+
+/// Index of the link of degree `degree` that starts at or before the node at `node_index`, in the
+/// breadth-first (Eytzinger) layout of [SpacedList] with links of degrees `0..depth`: the link of
+/// the highest degree comes first, then the two links of the degree below it, then the four below
+/// those, and so on. The two links below the one at `i` are at `2 * i + 1` and `2 * i + 2`, and
+/// the one above it at `(i - 1) / 2`.
+const fn eytzinger_link_index(node_index: usize, degree: usize, depth: usize) -> usize {
+    (1 << (depth - 1 - degree)) - 1 + (node_index >> degree)
+}
+
+/// Returns the indices of the two links below the link at `link_index` in the Eytzinger layout,
+/// which it is the sum of.
+const fn child_link_indices(link_index: usize) -> [usize; 2] {
+    [2 * link_index + 1, 2 * link_index + 2]
+}
+
+/// Number of degrees that a descent prefetches ahead of the link it reads.
+const PREFETCH_DEGREES: usize = 3;
+
+/// Returns the indices of the links that a descent reading the link at `link_index` (not the top
+/// one) in the Eytzinger layout reads [PREFETCH_DEGREES] degrees further down, whichever way it
+/// goes: the descent only ever reads the first of two links below the same one, and moves on to
+/// the first link below either of them, so it stays below the link above `link_index`, whose
+/// descendants of each degree are consecutive.
+const fn prefetched_link_indices(link_index: usize) -> Range<usize> {
+    let above = (link_index - 1) / 2;
+    let start = ((above + 1) << (PREFETCH_DEGREES + 1)) - 1;
+    start..start + (1 << (PREFETCH_DEGREES + 1))
+}
+
+/// Hints to the CPU that `slice[index]` will be read soon. `index` may be out of bounds, in which
+/// case this does nothing useful, but nothing harmful either.
+#[inline(always)]
+fn prefetch<T>(slice: &[T], index: usize) {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        // prefetching never faults, so the pointer does not need to be in bounds
+        _mm_prefetch::<_MM_HINT_T0>(slice.as_ptr().wrapping_add(index) as *const i8);
+    }
+}
+
+/// Lays out `links` in the Eytzinger layout anew as the links of one degree more, with `top` as the
+/// new top link, the links so far as the first half below it, and `filler` for the second half.
+fn grow_links<T: Copy>(links: &mut Vec<T>, top: T, filler: T) {
+    let old = core::mem::take(links);
+    links.reserve_exact(2 * old.len() + 1);
+    links.push(top);
+    let mut start = 0;
+    let mut width = 1;
+    while start < old.len() {
+        links.extend_from_slice(&old[start..start + width]);
+        links.extend(core::iter::repeat_n(filler, width));
+        start += width;
+        width *= 2;
+    }
+}
+
+/// Undoes [grow_links]: lays out `links` in the Eytzinger layout anew as the links of one degree
+/// less, keeping the first half below the top link.
+fn shrink_links<T: Copy>(links: &mut Vec<T>) {
+    let mut shrunk = Vec::with_capacity(links.len() / 2);
+    let mut start = 1;
+    let mut width = 1;
+    while start < links.len() {
+        shrunk.extend_from_slice(&links[start..start + width]);
+        start += 2 * width;
+        width *= 2;
+    }
+    *links = shrunk;
+}
 // endregion
 
 // region link indices above iterator
@@ -50,53 +297,208 @@ impl Iterator for LinkIndicesAbove {
 // endregion
 
 // region traversal result
-#[derive(Eq, PartialEq)]
-struct TraversalResult<'a, S: Spacing> {
-    list: &'a SpacedList<S>,
+#[derive(PartialEq)]
+struct TraversalResult<'a, P: Position<S>, A: Aggregate = (), S: Spacing = P> {
+    list: &'a SpacedList<P, A, S>,
     position: S,
     index: usize,
 }
 
-impl<S: Spacing> Debug for TraversalResult<'_, S>
+impl<P: Position<S>, A: Aggregate + Eq, S: Spacing> Eq for TraversalResult<'_, P, A, S>
+    where A::Value: Eq {}
+
+impl<P: Position<S>, A: Aggregate, S: Spacing> Debug for TraversalResult<'_, P, A, S>
     where S: Debug {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        return f.debug_struct("TraversalResult")
+        f.debug_struct("TraversalResult")
             .field("position", &self.position)
             .field("index", &self.index)
-            .finish();
+            .finish()
     }
 }
 // endregion
 
 // region spaced list
-/// A list that stores non-zero distance between its nodes, but does not store values.
+/// A list that stores non-zero distances of type `S` between its nodes, which are at positions of
+/// type `P`, and optionally a value per node, with the [Aggregate] `A` of those values per link.
 ///
 /// IMPORTANT: New and empty instances of this list contain one node, fixed at position zero, and
-/// thereby have a size of 1, even though they are empty.
-#[derive(Eq, PartialEq)]
-pub struct SpacedList<S: Spacing> {
+/// thereby have a size of 1, even though they are empty. Node zero is at zero, or at the origin the
+/// list is made with, see [Self::with_origin].
+///
+/// The list owns all its data and has no interior mutability, so it is [Send] and [Sync] whenever
+/// `P` and `S` are. Shared references can therefore be read from several threads at once, as the
+/// `par_` methods do (with the `std` feature).
+pub struct SpacedList<P: Position<S>, A: Aggregate = (), S: Spacing = P> {
     size: usize,
     capacity: usize,
     length: S,
+    /// Length of each link, in the breadth-first layout of [eytzinger_link_index], so that a
+    /// descent reads the links of the higher degrees from a few cache lines at the front, and the
+    /// links it may read next lie close to each other further back.
     link_lengths: Vec<S>,
-    sublists: Vec<Option<SpacedList<S>>>,
+    /// Number of nodes after node zero, including those in sublists.
+    node_count: usize,
+    /// Number of nodes that each link spans, including the one it ends at and those in sublists,
+    /// laid out like `link_lengths`.
+    link_node_counts: Vec<usize>,
+    /// Value of the node each zero-degree link ends at. Node zero has no value.
+    values: Vec<A::Value>,
+    /// Aggregate of the values of the nodes that each link spans, counted like `link_node_counts`.
+    link_aggregates: Vec<A>,
+    /// Update of the nodes that each link of a degree above zero spans, which is included in its
+    /// aggregate, but not yet pushed down to the links below it. Zero-degree links apply updates
+    /// to their value and sublist right away.
+    link_tags: Vec<A::Update>,
+    /// Handle of the node each zero-degree link ends at, if it has been given one.
+    ids: Vec<Option<NodeId>>,
+    /// The handles of this list and its sublists and where they are, which only the root list
+    /// keeps, once it needs them.
+    handles: Option<Box<Handles>>,
+    /// Identifies this list among the sublists of its root list in their [Handles], or [ROOT].
+    key: ListKey,
+    sublists: Vec<Option<Box<SpacedList<P, A, S>>>>,
+    /// Position of node zero, which the positions of all nodes are relative to. Sublists keep the
+    /// one of their root list, but do not use it, as their nodes are relative to the node they
+    /// hang off.
+    origin: P,
+}
+
+/// Compares the nodes, values, links and handles, but not where the lists are in memory.
+impl<P: Position<S>, A: Aggregate, S: Spacing> PartialEq for SpacedList<P, A, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size
+            && self.origin == other.origin
+            && self.capacity == other.capacity
+            && self.length == other.length
+            && self.link_lengths == other.link_lengths
+            && self.node_count == other.node_count
+            && self.link_node_counts == other.link_node_counts
+            && self.values == other.values
+            && self.link_aggregates == other.link_aggregates
+            && self.link_tags == other.link_tags
+            && self.ids == other.ids
+            && self.next_id() == other.next_id()
+            && self.sublists == other.sublists
+    }
 }
 
-impl<S: Spacing> Default for SpacedList<S> {
+impl<P: Position<S>, A: Aggregate + Eq, S: Spacing> Eq for SpacedList<P, A, S> where A::Value: Eq {}
+
+impl<S: Spacing, A: Aggregate> Default for SpacedList<S, A> {
     fn default() -> Self {
+        Self::with_origin(zero())
+    }
+}
+
+impl<S: Spacing, A: Aggregate> SpacedList<S, A> {
+    fn new() -> Self {
+        default()
+    }
+
+    /// Returns an empty list with enough link lengths for `capacity` nodes (including the node at
+    /// zero) and room for as many sublists.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut list = Self::new();
+        list.reserve(capacity.saturating_sub(1));
+        list
+    }
+}
+
+impl<P: Position<S>, A: Aggregate, S: Spacing> SpacedList<P, A, S> {
+    /// Returns an empty list with node zero at `origin`, which the positions of the nodes it gets
+    /// are measured from, for positions that are not distances themselves, such as `Instant`s.
+    pub fn with_origin(origin: P) -> Self {
         Self {
             size: 1,
             capacity: 1,
             length: zero(),
             link_lengths: vec![],
+            node_count: 0,
+            link_node_counts: vec![],
+            values: vec![],
+            link_aggregates: vec![],
+            link_tags: vec![],
+            ids: vec![],
+            handles: None,
+            key: ROOT,
             sublists: vec![],
+            origin,
         }
     }
-}
 
-impl<S: Spacing> SpacedList<S> {
-    fn new() -> Self {
-        default()
+    /// Returns the position of node zero.
+    pub fn origin(&self) -> P {
+        self.origin
+    }
+
+    /// Returns `position` relative to node zero, or None if it is before node zero.
+    fn relative(&self, position: P) -> Option<S> {
+        (position >= self.origin).then(|| position - self.origin)
+    }
+
+    /// Returns `position` relative to node zero, or zero if it is before node zero, where there are
+    /// no nodes either.
+    fn relative_or_zero(&self, position: P) -> S {
+        self.relative(position).unwrap_or(zero())
+    }
+
+    /// Returns the position of what is `relative_position` after node zero.
+    fn absolute(&self, relative_position: S) -> P {
+        self.origin + relative_position
+    }
+
+    /// Makes sure that `additional` more nodes can be appended without growing `link_lengths` or
+    /// `sublists` again.
+    pub fn reserve(&mut self, additional: usize) {
+        let capacity = Self::capacity_for(self.size + additional);
+        while self.capacity < capacity {
+            self.grow();
+        }
+        self.values.reserve(additional);
+        self.ids.reserve(additional);
+        self.sublists.reserve(additional);
+    }
+
+    /// Gives back all memory this list and its sublists don't need for their current nodes:
+    /// `link_lengths` is reduced to the smallest capacity that fits the size of the list, and
+    /// empty sublists are dropped.
+    pub fn shrink_to_fit(&mut self) {
+        let mut handles = self.take_handles();
+        self.shrink_to_fit_with_handles(&mut handles);
+        self.handles = Some(handles);
+    }
+
+    /// Like [Self::shrink_to_fit], and makes `handles` of the root list forget the dropped
+    /// sublists.
+    fn shrink_to_fit_with_handles(&mut self, handles: &mut Handles) {
+        let capacity = Self::capacity_for(self.size);
+        while self.capacity > capacity {
+            self.shrink();
+        }
+        self.link_lengths.shrink_to_fit();
+        self.link_node_counts.shrink_to_fit();
+        self.values.shrink_to_fit();
+        self.link_aggregates.shrink_to_fit();
+        self.link_tags.shrink_to_fit();
+        self.ids.shrink_to_fit();
+        for index in 0..self.sublists.len() {
+            if let Some(mut sublist) = self.take_sublist(index, handles) {
+                sublist.shrink_to_fit_with_handles(handles);
+                self.sublists[index] = Some(sublist);
+            }
+        }
+        self.sublists.shrink_to_fit();
+    }
+
+    /// Returns the smallest element of the capacity series (see [Self::grow]) that is at least
+    /// `size`.
+    fn capacity_for(size: usize) -> usize {
+        if size <= 1 {
+            1
+        } else {
+            (size - 1).next_power_of_two() + 1
+        }
     }
 
     fn make_space(&mut self) {
@@ -104,24 +506,64 @@ impl<S: Spacing> SpacedList<S> {
             return;
         }
         if self.capacity < self.size {
-            // Link lengths series:
-            // 0 -> 1 -> 3 -> 7 -> 15 -> 31 -> 63 -> 127 -> 255 -> 511 ->
-            // Zero-level links series:
-            // 0 -> 1 -> 2 -> 4 ->  8 -> 16 -> 32 ->  64 -> 128 -> 256 ->
-            // Capacity series:
-            // 1 -> 2 -> 3 -> 5 ->  9 -> 17 -> 33 ->  65 -> 129 -> 257 ->
-            // the next element of the capacity series is always the last element of the link
-            // lengths series + 2
-            self.capacity = self.link_lengths.len() + 2;
-            self.link_lengths.push(self.length);
-            self.link_lengths.extend(vec![zero(); self.link_lengths.len() - 1].iter());
+            self.grow();
+        }
+    }
+
+    /// Moves this list to the next element of the capacity series, adding one degree of links.
+    fn grow(&mut self) {
+        // Link lengths series:
+        // 0 -> 1 -> 3 -> 7 -> 15 -> 31 -> 63 -> 127 -> 255 -> 511 ->
+        // Zero-level links series:
+        // 0 -> 1 -> 2 -> 4 ->  8 -> 16 -> 32 ->  64 -> 128 -> 256 ->
+        // Capacity series:
+        // 1 -> 2 -> 3 -> 5 ->  9 -> 17 -> 33 ->  65 -> 129 -> 257 ->
+        // the next element of the capacity series is always the last element of the link
+        // lengths series + 2
+        self.capacity = self.link_lengths.len() + 2;
+        // the links so far become the first half below a new top link
+        let total_aggregate = self.total_aggregate();
+        grow_links(&mut self.link_aggregates, total_aggregate, A::identity());
+        grow_links(&mut self.link_tags, A::Update::identity(), A::Update::identity());
+        grow_links(&mut self.link_lengths, self.length, zero());
+        grow_links(&mut self.link_node_counts, self.node_count, 0);
+    }
+
+    /// Moves this list to the previous element of the capacity series, removing the links of the
+    /// highest degree. The first half below the top link already describes the first half of the
+    /// nodes, so this is only valid if the size of this list fits into that half.
+    fn shrink(&mut self) {
+        let len = self.link_lengths.len() / 2;
+        let capacity = if len == 0 { 1 } else { len.div_ceil(2) + 1 };
+        assert!(self.size <= capacity);
+        if self.depth() > 1 {
+            // the update of the top link also covers the links that are kept
+            self.push_down(0, self.depth() - 1);
         }
+        shrink_links(&mut self.link_lengths);
+        shrink_links(&mut self.link_node_counts);
+        shrink_links(&mut self.link_aggregates);
+        shrink_links(&mut self.link_tags);
+        self.capacity = capacity;
     }
 
     fn depth(&self) -> usize {
         (self.link_lengths.len() + 1).trailing_zeros() as usize
     }
 
+    /// Returns the index of the link of degree `degree` after node `node_index` in `link_lengths`
+    /// and the vectors laid out like it, see [eytzinger_link_index].
+    fn link_index(&self, node_index: usize, degree: usize) -> usize {
+        eytzinger_link_index(node_index, degree, self.depth())
+    }
+
+    /// Returns the indices of the links above the zero-degree link after node `node_index`, from
+    /// that link up to the top link.
+    fn link_indices_above(&self, node_index: usize) -> impl Iterator<Item=usize> {
+        let first = (self.depth() > 0).then(|| self.link_index(node_index, 0));
+        core::iter::successors(first, |&link_index| (link_index > 0).then(|| (link_index - 1) / 2))
+    }
+
     /// TODO this documentation is just for thoughts rn, nothing to go onto docs.rs or anything
     ///
     /// A list of size 0 is completely empty. No nodes, no length, no link lengths, no sublists.
@@ -138,34 +580,183 @@ impl<S: Spacing> SpacedList<S> {
     ///
     /// A list of any higher size has enough nodes to link every node to the next one, and binary
     /// shortcut (higher-degree) links on several degrees up. It has a length equal to the greatest
-    /// shortcut link (the one right in the middle of `link_lengths`, at least using the current
-    /// system of link length storage, state 035dba816c16b4005a65c3e0132fdaa59cf6bbc7), and a number
+    /// shortcut link (the first one in `link_lengths`, which is laid out breadth-first), and a number
     /// of link lengths that is enough to store the distances between nodes, allowing for a total
     /// size up to the next power of 2 + 1. There can be a sublist after every node except for the
     /// last one.
     ///
     ///
     fn append_node(&mut self, distance: S) {
+        self.append_node_with_value(distance, default())
+    }
+
+    fn append_node_with_value(&mut self, distance: S, value: A::Value) {
         assert!(distance > zero());
         // self.size is at least 1 (no empty lists exist), so
         self.size += 1;
         // self.size is greater than one by now
         self.make_space();
+        // updates pending above the new node are not meant for it
+        self.push_down_above(self.size - 1 - 1);
         self.length += distance;
-        for link_index in LinkIndicesAbove::new(self.size - 1 - 1).take(self.depth()) {
-            self.link_lengths[link_index] += distance
+        for link_index in self.link_indices_above(self.size - 1 - 1) {
+            self.link_lengths[link_index] += distance;
+            // the new node is the last one in all of these links
+            self.link_aggregates[link_index] = self.link_aggregates[link_index].combine(A::of(value))
+        }
+        self.add_to_node_counts(self.size - 1 - 1, 1);
+        self.values.push(value);
+        self.ids.push(None);
+        self.sublists.push(None);
+        self.validate_after_mutation()
+    }
+
+    /// Returns the aggregate of the values of all nodes, including sublists.
+    fn total_aggregate(&self) -> A {
+        match self.depth() {
+            0 => A::identity(),
+            depth => self.link_aggregates[self.link_index(0, depth - 1)],
+        }
+    }
+
+    /// Returns the aggregate of the zero-degree link after node `index`, computed from the value
+    /// of the node it ends at and the sublist before that node.
+    fn zero_degree_aggregate(&self, index: usize) -> A {
+        if index + 1 >= self.size {
+            return A::identity();
+        }
+        let value = A::of(self.values[index]);
+        match &self.sublists[index] {
+            Some(sublist) => sublist.total_aggregate().combine(value),
+            None => value,
+        }
+    }
+
+    /// Computes the aggregates of the links above the zero-degree link after node `index` anew from
+    /// the links below them, after the value of a node or the sublist in it has changed. Their
+    /// updates must have been pushed down before.
+    fn update_aggregates_above(&mut self, index: usize) {
+        for (degree, link_index) in self.link_indices_above(index).enumerate() {
+            self.link_aggregates[link_index] = if degree == 0 {
+                self.zero_degree_aggregate(index)
+            } else {
+                let [before, after] = child_link_indices(link_index);
+                self.link_aggregates[before].combine(self.link_aggregates[after])
+            }
+        }
+    }
+
+    /// Applies `update` to the link of degree `degree` after node `index`: to its aggregate right
+    /// away, and to the links below it through its tag, or to its value and sublist if it is a
+    /// zero-degree link.
+    fn apply_to_link(&mut self, index: usize, degree: usize, update: A::Update) {
+        let link_index = self.link_index(index, degree);
+        self.link_aggregates[link_index] = update
+            .apply_to_aggregate(self.link_aggregates[link_index], self.link_node_counts[link_index]);
+        if degree > 0 {
+            self.link_tags[link_index] = self.link_tags[link_index].compose(update);
+            return;
         }
-        self.sublists.push(None)
+        if let Some(value) = self.values.get_mut(index) {
+            *value = update.apply(*value)
+        }
+        if let Some(Some(sublist)) = self.sublists.get_mut(index) {
+            sublist.apply_to_all(update)
+        }
+    }
+
+    /// Applies `update` to all nodes of this list, through the tag of its top link.
+    fn apply_to_all(&mut self, update: A::Update) {
+        if let depth @ 1.. = self.depth() {
+            self.apply_to_link(0, depth - 1, update)
+        }
+    }
+
+    /// Moves the tag of the link of degree `degree` (above zero) after node `index` down to the two
+    /// links below it.
+    fn push_down(&mut self, index: usize, degree: usize) {
+        let link_index = self.link_index(index, degree);
+        let update = core::mem::replace(&mut self.link_tags[link_index], A::Update::identity());
+        if update != A::Update::identity() {
+            self.apply_to_link(index, degree - 1, update);
+            self.apply_to_link(index + (1 << (degree - 1)), degree - 1, update);
+        }
+    }
+
+    /// Pushes the tags of the links above the zero-degree link after node `index` down, so that its
+    /// value and sublist are up-to-date and those links can be changed or computed anew.
+    fn push_down_above(&mut self, index: usize) {
+        for degree in (1..self.depth()).rev() {
+            self.push_down(index >> degree << degree, degree)
+        }
+    }
+
+    /// Pushes all tags down to the zero-degree links, so that all values and sublists of this list
+    /// are up-to-date. Does not push down the tags within sublists.
+    fn push_down_all(&mut self) {
+        self.push_down_from(0)
+    }
+
+    /// Pushes the tags of all links that cover a zero-degree link at or after the one after node
+    /// `index` down, like [Self::push_down_above] for each of these links.
+    fn push_down_from(&mut self, index: usize) {
+        for degree in (1..self.depth()).rev() {
+            for index in (index >> degree << degree..self.capacity - 1).step_by(1 << degree) {
+                self.push_down(index, degree)
+            }
+        }
+    }
+
+    /// Returns the update pending above the zero-degree link after node `index`, which is yet to be
+    /// applied to its value and sublist.
+    fn pending_update(&self, index: usize) -> A::Update {
+        (1..self.depth()).rev().fold(A::Update::identity(), |pending, degree| {
+            self.link_tags[self.link_index(index, degree)].compose(pending)
+        })
+    }
+
+    /// Adds `count` nodes to the links above the zero-degree link after node `index`.
+    fn add_to_node_counts(&mut self, index: usize, count: usize) {
+        self.node_count += count;
+        for link_index in self.link_indices_above(index) {
+            self.link_node_counts[link_index] += count
+        }
+    }
+
+    /// Removes `count` nodes from the links above the zero-degree link after node `index`.
+    fn subtract_from_node_counts(&mut self, index: usize, count: usize) {
+        self.node_count -= count;
+        for link_index in self.link_indices_above(index) {
+            self.link_node_counts[link_index] -= count
+        }
+    }
+
+    /// Returns a mutable reference to the sublist at `index`, creating an empty one if absent, which
+    /// is mapped in `handles` of the root list.
+    fn get_sublist_at_index(&mut self, index: usize, handles: &mut Handles)
+                            -> &mut SpacedList<P, A, S> {
+        let key = self.key;
+        let origin = self.origin;
+        self.sublists[index].get_or_insert_with(|| Box::new(Self {
+            key: handles.new_key((key, index)),
+            ..Self::with_origin(origin)
+        }))
     }
 
-    /// Returns a mutable reference to the sublist at `index`, creating an empty one if absent
-    fn get_sublist_at_index(&mut self, index: usize) -> &mut SpacedList<S> {
-        self.sublists[index].get_or_insert_default()
+    /// Takes the sublist at `index` out of this list, or returns None if there is none or it is
+    /// empty, in which case it is dropped and forgotten by `handles` of the root list.
+    fn take_sublist(&mut self, index: usize, handles: &mut Handles) -> Option<Box<Self>> {
+        let sublist = self.sublists.get_mut(index)?.take()?;
+        if sublist.is_empty() {
+            handles.forget_sublist(sublist.key);
+            return None;
+        }
+        Some(sublist)
     }
 
     /// Returns a reference to the sublist at `index`, or None if there is no sublist at
     /// `index` or that sublist is empty
-    fn get_not_empty_sublist_at_index(&self, index: usize) -> Option<&SpacedList<S>> {
+    fn get_not_empty_sublist_at_index(&self, index: usize) -> Option<&SpacedList<P, A, S>> {
         let sublist = self.sublists.get(index)?.as_ref()?;
         if sublist.is_empty() {
             None
@@ -176,7 +767,8 @@ impl<S: Spacing> SpacedList<S> {
 
     /// Returns a mutable reference to the sublist at `index`, or None if there is no sublist at
     /// `index` or that sublist is empty
-    fn get_not_empty_sublist_at_index_mut(&mut self, index: usize) -> Option<&mut SpacedList<S>> {
+    fn get_not_empty_sublist_at_index_mut(&mut self, index: usize)
+                                          -> Option<&mut SpacedList<P, A, S>> {
         let sublist = self.sublists.get_mut(index)?.as_mut()?;
         if sublist.is_empty() {
             None
@@ -185,19 +777,37 @@ impl<S: Spacing> SpacedList<S> {
         }
     }
 
-    fn insert(&mut self, position: S) {
+    /// Inserts a node with the default value at `position`, which must be after node zero.
+    pub fn insert(&mut self, position: P) {
+        self.insert_with_value(position, default())
+    }
+
+    /// Inserts a node with `value` at `position`, which must be after node zero.
+    pub fn insert_with_value(&mut self, position: P, value: A::Value) {
+        let mut handles = self.take_handles();
+        self.insert_with_handles(self.relative_or_zero(position), value, &mut handles);
+        self.handles = Some(handles);
+        self.validate_after_mutation()
+    }
+
+    /// Inserts a node with `value` at `position` relative to node zero, and maps the sublists it
+    /// creates in `handles` of the root list.
+    fn insert_with_handles(&mut self, position: S, value: A::Value, handles: &mut Handles) {
         assert!(position > zero());
 
         if position >= self.length {
-            self.append_node(position - self.length)
+            self.append_node_with_value(position - self.length, value)
         } else {
             // zero() < position < self.length
             let TraversalResult { list, position: node_position, index } =
                 self.node_at_or_before_shallow(position).unwrap();
-            let sublist = self.get_sublist_at_index(index);
+            self.push_down_above(index);
+            let sublist = self.get_sublist_at_index(index, handles);
             let position_in_sublist = position - node_position;
             assert!(position_in_sublist > zero());
-            sublist.insert(position_in_sublist)
+            sublist.insert_with_handles(position_in_sublist, value, handles);
+            self.add_to_node_counts(index, 1);
+            self.update_aggregates_above(index)
         }
     }
 
@@ -206,26 +816,57 @@ impl<S: Spacing> SpacedList<S> {
     }
 }
 
-impl<S: Spacing> SpacedList<S> {
+impl<P: Position<S>, A: Aggregate, S: Spacing> SpacedList<P, A, S> {
+    /// Descends through the links of this list, not into sublists, to the last node before
+    /// `target_position`, or at it if `INCLUSIVE` is true, and returns its position and index.
+    /// Assumes there is such a node.
+    ///
+    /// Each degree reads exactly one link and selects the next index arithmetically instead of
+    /// branching on the comparison, which the branch predictor cannot guess for random targets.
+    /// Thanks to the Eytzinger layout, the links it can read a few degrees further down are
+    /// consecutive, and prefetched in the meantime.
+    fn descend<const INCLUSIVE: bool>(&self, target_position: S) -> (S, usize) {
+        self.descend_by::<Whole, INCLUSIVE>(target_position)
+    }
+
+    /// Like [Self::descend], but compares `target_position` with one metric of the positions.
+    fn descend_by<M: Metric<S>, const INCLUSIVE: bool>(&self, target_position: M::Value)
+                                                       -> (S, usize) {
+        let past = |position: S| if INCLUSIVE {
+            M::measure(position) <= target_position
+        } else {
+            M::measure(position) < target_position
+        };
+        // the top link spans all nodes, and there is no link after it to go on with
+        if self.is_empty() || past(self.length) {
+            return (self.length, self.size - 1);
+        }
+        let mut position = S::zero();
+        let mut index = 0usize;
+        // the first half of the top link
+        let mut link_index = 1;
+        for degree in (0..self.depth() - 1).rev() {
+            let prefetched = prefetched_link_indices(link_index);
+            prefetch(&self.link_lengths, prefetched.start);
+            prefetch(&self.link_lengths, prefetched.end - 1);
+            let possibly_next_position = position + self.link_lengths[link_index];
+            let advance = past(possibly_next_position);
+            index += (advance as usize) << degree;
+            position = if advance { possibly_next_position } else { position };
+            link_index = child_link_indices(link_index + advance as usize)[0];
+        }
+        // the links after the last node are zero, so the descent may have passed it without moving
+        (position, index.min(self.size - 1))
+    }
+
     /// Returns the last node before (the greatest less than) `target_position` in this list, not in
     /// sublists, or None if `target_position` is zero or negative.
-    fn node_before_shallow(&self, target_position: S) -> Option<TraversalResult<S>> {
+    fn node_before_shallow(&self, target_position: S) -> Option<TraversalResult<'_, P, A, S>> {
         if target_position <= zero() {
             return None;
         }
 
-        let mut position = S::zero();
-        let mut index = 0usize;
-        for degree in (0..self.depth()).rev() {
-            let possibly_next_index = index + (1 << degree);
-            if possibly_next_index < self.size {
-                let possibly_next_position = position + self[(index, degree)];
-                if possibly_next_position < target_position {
-                    position = possibly_next_position;
-                    index = possibly_next_index;
-                }
-            }
-        }
+        let (position, index) = self.descend::<false>(target_position);
 
         Some(TraversalResult {
             list: self,
@@ -236,23 +877,13 @@ impl<S: Spacing> SpacedList<S> {
 
     /// Returns the last node at or before (the greatest less than or equal to) `target_position` in
     /// this list, not in sublists, or None if `target_position` is negative.
-    fn node_at_or_before_shallow(&self, target_position: S) -> Option<TraversalResult<S>> {
+    fn node_at_or_before_shallow(&self, target_position: S)
+                                 -> Option<TraversalResult<'_, P, A, S>> {
         if target_position < zero() {
             return None;
         }
 
-        let mut position = S::zero();
-        let mut index = 0usize;
-        for degree in (0..self.depth()).rev() {
-            let possibly_next_index = index + (1 << degree);
-            if possibly_next_index < self.size {
-                let possibly_next_position = position + self[(index, degree)];
-                if possibly_next_position <= target_position {
-                    position = possibly_next_position;
-                    index = possibly_next_index;
-                }
-            }
-        }
+        let (position, index) = self.descend::<true>(target_position);
 
         Some(TraversalResult {
             list: self,
@@ -263,23 +894,12 @@ impl<S: Spacing> SpacedList<S> {
 
     /// Returns the node at `target_position` in this list, not in sublists, or None if this list
     /// does not contain a node at `target_position`.
-    fn node_at_shallow(&self, target_position: S) -> Option<TraversalResult<S>> {
+    fn node_at_shallow(&self, target_position: S) -> Option<TraversalResult<'_, P, A, S>> {
         if target_position < zero() {
             return None;
         }
 
-        let mut position = S::zero();
-        let mut index = 0usize;
-        for degree in (0..self.depth()).rev() {
-            let possibly_next_index = index + (1 << degree);
-            if possibly_next_index < self.size {
-                let possibly_next_position = position + self[(index, degree)];
-                if possibly_next_position <= target_position {
-                    position = possibly_next_position;
-                    index = possibly_next_index;
-                }
-            }
-        }
+        let (position, index) = self.descend::<true>(target_position);
 
         if position == target_position {
             Some(TraversalResult {
@@ -294,7 +914,7 @@ impl<S: Spacing> SpacedList<S> {
 
     /// Returns the first node at or after (the least greater than or equal to) `target_position` in
     /// this list, not in sublists, or None if `target_position > self.length`.
-    fn node_at_or_after_shallow(&self, target_position: S) -> Option<TraversalResult<S>> {
+    fn node_at_or_after_shallow(&self, target_position: S) -> Option<TraversalResult<'_, P, A, S>> {
         if target_position < zero() {
             return Some(TraversalResult {
                 list: self,
@@ -307,18 +927,7 @@ impl<S: Spacing> SpacedList<S> {
             return None;
         }
 
-        let mut position = S::zero();
-        let mut index = 0usize;
-        for degree in (0..self.depth()).rev() {
-            let possibly_next_index = index + (1 << degree);
-            if possibly_next_index < self.size {
-                let possibly_next_position = position + self[(index, degree)];
-                if possibly_next_position <= target_position {
-                    position = possibly_next_position;
-                    index = possibly_next_index;
-                }
-            }
-        }
+        let (position, index) = self.descend::<true>(target_position);
 
         if position == target_position {
             Some(TraversalResult {
@@ -339,7 +948,7 @@ impl<S: Spacing> SpacedList<S> {
 
     /// Returns the first node after (the least greater than) `target_position` in this list, not in
     /// sublists, or None if `target_position > self.length`.
-    fn node_after_shallow(&self, target_position: S) -> Option<TraversalResult<S>> {
+    fn node_after_shallow(&self, target_position: S) -> Option<TraversalResult<'_, P, A, S>> {
         if target_position < zero() {
             return Some(TraversalResult {
                 list: self,
@@ -352,52 +961,58 @@ impl<S: Spacing> SpacedList<S> {
             return None;
         }
 
-        let mut position = self.length;
-        // let mut index = 1 << ((self.size - 1).log2() + 1);
-        // let mut index = 1 << (self.size.log2() + 1);
-        // let mut index = 1 << self.size.log2();
-        let mut index = self.capacity - 1;
-        for degree in (0..self.depth()).rev() {
-            let possibly_next_index = index - (1 << degree);
-            // let link_index = link_index(possibly_next_index, degree);
-            // if link_index >= self.link_lengths.len() {
-            //     continue
-            // }
-            let possibly_next_position = position - self[(possibly_next_index, degree)];
-            if possibly_next_position > target_position {
-                position = possibly_next_position;
-                index = possibly_next_index;
-            }
-        }
-
+        // target_position < self.length
+        // therefore, we can safely assume there is a node after position and index
+        let (position, index) = self.descend::<true>(target_position);
         Some(TraversalResult {
             list: self,
-            position,
-            index,
+            position: position + self[(index, 0)],
+            index: index + 1,
         })
     }
 }
 
-impl<S: Spacing> SpacedList<S> {
+impl<P: Position<S>, A: Aggregate, S: Spacing> SpacedList<P, A, S> {
     /// Returns the last node before (the greatest less than) `target_position` in this list,
     /// including sublists, or None if `target_position` is zero or negative.
-    fn node_before(&self, target_position: S) -> Option<Vec<TraversalResult<S>>> {
+    fn node_before(&self, target_position: S) -> Option<Vec<TraversalResult<'_, P, A, S>>> {
+        self.node_before_by::<Whole>(target_position)
+    }
+
+    /// Returns the last node at or before (the greatest less than or equal to) `target_position` in
+    /// this list, including sublists, or None if `target_position` is negative.
+    fn node_at_or_before(&self, target_position: S) -> Option<Vec<TraversalResult<'_, P, A, S>>> {
+        self.node_at_or_before_by::<Whole>(target_position)
+    }
+
+    /// Returns the node at `target_position` in this list, including sublists, or None if this list
+    /// does not contain a node at `target_position`.
+    fn node_at(&self, target_position: S) -> Option<Vec<TraversalResult<'_, P, A, S>>> {
+        self.node_at_by::<Whole>(target_position)
+    }
+
+    /// Returns the first node at or after (the least greater than or equal to) `target_position` in
+    /// this list, including sublists, or None if `target_position > self.length`.
+    fn node_at_or_after(&self, target_position: S) -> Option<Vec<TraversalResult<'_, P, A, S>>> {
+        self.node_at_or_after_by::<Whole>(target_position)
+    }
+
+    /// Returns the first node after (the least greater than) `target_position` in this list,
+    /// including sublists, or None if `target_position >= self.length`.
+    fn node_after(&self, target_position: S) -> Option<Vec<TraversalResult<'_, P, A, S>>> {
+        self.node_after_by::<Whole>(target_position)
+    }
+}
+
+impl<P: Position<S>, A: Aggregate, S: Spacing> SpacedList<P, A, S> {
+    /// Like [Self::node_before], but compares `target_position` with the metric `M` of the positions.
+    fn node_before_by<M: Metric<S>>(&self, target_position: M::Value)
+                                    -> Option<Vec<TraversalResult<'_, P, A, S>>> {
         if target_position <= zero() {
             return None;
         }
 
-        let mut position = S::zero();
-        let mut index = 0usize;
-        for degree in (0..self.depth()).rev() {
-            let possibly_next_index = index + (1 << degree);
-            if possibly_next_index < self.size {
-                let possibly_next_position = position + self[(index, degree)];
-                if possibly_next_position < target_position {
-                    position = possibly_next_position;
-                    index = possibly_next_index;
-                }
-            }
-        }
+        let (position, index) = self.descend_by::<M, false>(target_position);
 
         let mut result = vec![TraversalResult {
             list: self,
@@ -406,90 +1021,56 @@ impl<S: Spacing> SpacedList<S> {
         }];
         let sublist = self.get_not_empty_sublist_at_index(index);
         if let Some(sublist) = sublist {
-            let sublist_result = sublist.node_before(target_position - position)?;
+            let sublist_result = sublist.node_before_by::<M>(target_position - M::measure(position))?;
             // TODO possibly implement this check for the methods below too? dunno rn
             if sublist_result.len() > 1 || sublist_result[0].index != 0 {
-                result.extend(sublist_result.into_iter())
+                result.extend(sublist_result)
             }
         }
         Some(result)
     }
 
-    /// Returns the last node at or before (the greatest less than or equal to) `target_position` in
-    /// this list, not in sublists, or None if `target_position` is negative.
-    fn node_at_or_before(&self, target_position: S) -> Option<Vec<TraversalResult<S>>> {
+    /// Like [Self::node_at_or_before], but compares `target_position` with the metric `M` of the positions.
+    fn node_at_or_before_by<M: Metric<S>>(&self, target_position: M::Value)
+                                          -> Option<Vec<TraversalResult<'_, P, A, S>>> {
         if target_position < zero() {
             return None;
         }
 
-        let mut position = S::zero();
-        let mut index = 0usize;
-        for degree in (0..self.depth()).rev() {
-            let possibly_next_index = index + (1 << degree);
-            if possibly_next_index < self.size {
-                let possibly_next_position = position + self[(index, degree)];
-                if possibly_next_position <= target_position {
-                    position = possibly_next_position;
-                    index = possibly_next_index;
-                }
-            }
-        }
+        let (position, index) = self.descend_by::<M, true>(target_position);
 
         let mut result = vec![TraversalResult {
             list: self,
             position,
             index,
         }];
-        let sublist = self.get_not_empty_sublist_at_index(index);
-        match sublist {
-            Some(sublist) if position != target_position => {
-                result.extend(sublist.node_at_or_before(target_position - position)?.into_iter())
+        // the sublist can start with nodes at the same measure as the node it hangs off, which are
+        // after that node
+        if let Some(sublist) = self.get_not_empty_sublist_at_index(index) {
+            let sublist_result =
+                sublist.node_at_or_before_by::<M>(target_position - M::measure(position))?;
+            if M::measure(position) != target_position
+                || sublist_result.iter().any(|result| result.index != 0) {
+                result.extend(sublist_result)
             }
-            _ => ()
         }
         Some(result)
     }
 
-    /// Returns the node at `target_position` in this list, not in sublists, or None if this list
-    /// does not contain a node at `target_position`.
-    fn node_at(&self, target_position: S) -> Option<Vec<TraversalResult<S>>> {
-        if target_position < zero() {
-            return None;
-        }
-
-        let mut position = S::zero();
-        let mut index = 0usize;
-        for degree in (0..self.depth()).rev() {
-            let possibly_next_index = index + (1 << degree);
-            if possibly_next_index < self.size {
-                let possibly_next_position = position + self[(index, degree)];
-                if possibly_next_position <= target_position {
-                    position = possibly_next_position;
-                    index = possibly_next_index;
-                }
-            }
-        }
-
-        let mut result = vec![TraversalResult {
-            list: self,
-            position,
-            index,
-        }];
-        if position != target_position {
-            let sublist = self.get_not_empty_sublist_at_index(index);
-            if let Some(sublist) = sublist {
-                result.extend(sublist.node_at(target_position - position)?.into_iter())
-            } else {
-                return None;
-            }
-        }
-        Some(result)
+    /// Like [Self::node_at], but compares `target_position` with the metric `M` of the positions.
+    /// Of several nodes at `target_position`, returns the first one.
+    fn node_at_by<M: Metric<S>>(&self, target_position: M::Value)
+                                -> Option<Vec<TraversalResult<'_, P, A, S>>> {
+        let result = self.node_at_or_after_by::<M>(target_position)?;
+        let position = result.iter().fold(zero(), |position: S, result| position + result.position);
+        (M::measure(position) == target_position).then_some(result)
     }
 
-    /// Returns the first node at or after (the least greater than or equal to) `target_position` in
-    /// this list, not in sublists, or None if `target_position > self.length`.
-    fn node_at_or_after(&self, target_position: S) -> Option<Vec<TraversalResult<S>>> {
-        if target_position < zero() {
+    /// Like [Self::node_at_or_after], but compares `target_position` with the metric `M` of the positions.
+    fn node_at_or_after_by<M: Metric<S>>(&self, target_position: M::Value)
+                                         -> Option<Vec<TraversalResult<'_, P, A, S>>> {
+        // node zero is the first node at zero, even if the nodes after it are at zero too
+        if target_position <= zero() {
             return Some(vec![TraversalResult {
                 list: self,
                 position: zero(),
@@ -497,48 +1078,39 @@ impl<S: Spacing> SpacedList<S> {
             }]);
         }
 
-        if target_position > self.length {
+        if target_position > M::measure(self.length) {
             return None;
         }
 
-        let mut position = S::zero();
-        let mut index = 0usize;
-        for degree in (0..self.depth()).rev() {
-            let possibly_next_index = index + (1 << degree);
-            if possibly_next_index < self.size {
-                let possibly_next_position = position + self[(index, degree)];
-                if possibly_next_position <= target_position {
-                    position = possibly_next_position;
-                    index = possibly_next_index;
-                }
-            }
-        }
+        // the last node before target_position, as the first one at it can be in its sublist
+        let (position, index) = self.descend_by::<M, false>(target_position);
 
         let mut result = vec![TraversalResult {
             list: self,
             position,
             index,
         }];
-        if position != target_position {
-            // target_position < self.length
-            // therefore, we can safely assume there is a node after position and index
-            let sublist = self.get_not_empty_sublist_at_index(index);
-            if let Some(sublist) = sublist {
-                result.extend(sublist.node_at_or_after(target_position - position)?.into_iter())
-            } else {
-                return Some(vec![TraversalResult {
-                    list: self,
-                    position: position + self[(index, 0)],
-                    index: index + 1,
-                }]);
+        // 0 < target_position <= self.length
+        // therefore, we can safely assume there is a node after position and index
+        let sublist = self.get_not_empty_sublist_at_index(index);
+        if let Some(sublist) = sublist {
+            // the sublist may end before target_position, in which case the next node in this
+            // list is the result
+            if let Some(sublist_result) = sublist.node_at_or_after_by::<M>(target_position - M::measure(position)) {
+                result.extend(sublist_result);
+                return Some(result);
             }
         }
-        Some(result)
+        Some(vec![TraversalResult {
+            list: self,
+            position: position + self[(index, 0)],
+            index: index + 1,
+        }])
     }
 
-    /// Returns the first node after (the least greater than) `target_position` in this list, not in
-    /// sublists, or None if `target_position > self.length`.
-    fn node_after(&self, target_position: S) -> Option<Vec<TraversalResult<S>>> {
+    /// Like [Self::node_after], but compares `target_position` with the metric `M` of the positions.
+    fn node_after_by<M: Metric<S>>(&self, target_position: M::Value)
+                                   -> Option<Vec<TraversalResult<'_, P, A, S>>> {
         if target_position < zero() {
             return Some(vec![TraversalResult {
                 list: self,
@@ -547,22 +1119,11 @@ impl<S: Spacing> SpacedList<S> {
             }]);
         }
 
-        if target_position >= self.length {
+        if target_position >= M::measure(self.length) {
             return None;
         }
 
-        let mut position = S::zero();
-        let mut index = 0usize;
-        for degree in (0..self.depth()).rev() {
-            let possibly_next_index = index + (1 << degree);
-            if possibly_next_index < self.size {
-                let possibly_next_position = position + self[(index, degree)];
-                if possibly_next_position <= target_position {
-                    position = possibly_next_position;
-                    index = possibly_next_index;
-                }
-            }
-        }
+        let (position, index) = self.descend_by::<M, true>(target_position);
 
         let mut result = vec![TraversalResult {
             list: self,
@@ -573,10 +1134,10 @@ impl<S: Spacing> SpacedList<S> {
         // therefore, we can safely assume there is a node after position and index
         let sublist = self.get_not_empty_sublist_at_index(index);
         if let Some(sublist) = sublist {
-            let sublist_result = sublist.node_after(target_position - position);
+            let sublist_result = sublist.node_after_by::<M>(target_position - M::measure(position));
             // TODO possibly implement this check for the methods above too? dunno rn
             if let Some(sublist_result) = sublist_result {
-                result.extend(sublist_result.into_iter());
+                result.extend(sublist_result);
                 return Some(result);
             }
         }
@@ -589,18 +1150,1474 @@ impl<S: Spacing> SpacedList<S> {
 }
 // endregion
 
+// region spaced list multi-metric queries
+/// Only positions that are spacings themselves can be measured by a [Metric], so these queries
+/// need a list of positions of its spacing.
+impl<S: Spacing, A: Aggregate> SpacedList<S, A> {
+    /// Returns the position, in all metrics, of the last node before `target_position` in the
+    /// metric `M`, including sublists.
+    pub fn position_before_by<M: Metric<S>>(&self, target_position: M::Value) -> Option<S> {
+        self.node_before_by::<M>(target_position - M::measure(self.origin))
+            .map(|path| self.absolute(path_position(path)))
+    }
+
+    /// Returns the position, in all metrics, of the last node at or before `target_position` in
+    /// the metric `M`, including sublists.
+    pub fn position_at_or_before_by<M: Metric<S>>(&self, target_position: M::Value) -> Option<S> {
+        self.node_at_or_before_by::<M>(target_position - M::measure(self.origin))
+            .map(|path| self.absolute(path_position(path)))
+    }
+
+    /// Returns the position, in all metrics, of the node at `target_position` in the metric `M`,
+    /// including sublists.
+    pub fn position_at_by<M: Metric<S>>(&self, target_position: M::Value) -> Option<S> {
+        self.node_at_by::<M>(target_position - M::measure(self.origin))
+            .map(|path| self.absolute(path_position(path)))
+    }
+
+    /// Returns the position, in all metrics, of the first node at or after `target_position` in
+    /// the metric `M`, including sublists.
+    pub fn position_at_or_after_by<M: Metric<S>>(&self, target_position: M::Value) -> Option<S> {
+        self.node_at_or_after_by::<M>(target_position - M::measure(self.origin))
+            .map(|path| self.absolute(path_position(path)))
+    }
+
+    /// Returns the position, in all metrics, of the first node after `target_position` in the
+    /// metric `M`, including sublists.
+    pub fn position_after_by<M: Metric<S>>(&self, target_position: M::Value) -> Option<S> {
+        self.node_after_by::<M>(target_position - M::measure(self.origin))
+            .map(|path| self.absolute(path_position(path)))
+    }
+}
+// endregion
+
+// region spaced list removal and shifting
+/// A node after node zero, with everything a list keeps about it, as returned by
+/// [SpacedList::entries].
+struct NodeEntry<S, V> {
+    position: S,
+    value: V,
+    id: Option<NodeId>,
+}
+
+impl<P: Position<S>, A: Aggregate, S: Spacing> SpacedList<P, A, S> {
+    /// Removes the node at `position` and returns whether there was one. The nodes in the sublist
+    /// after it keep their positions and move into the sublist of the node before it. The node at
+    /// zero cannot be removed.
+    ///
+    /// The nodes after it in its list move one index down, so this takes O(n - i) for the node at
+    /// index i of a list of n nodes, plus the nodes of the sublist after it. Removing nodes from
+    /// the end is cheap, removing them from the front is as slow as building the list anew.
+    pub fn remove(&mut self, position: P) -> bool {
+        let mut handles = self.take_handles();
+        let removed = self.remove_with_handles(self.relative_or_zero(position), &mut handles);
+        self.handles = Some(handles);
+        self.validate_after_mutation();
+        removed
+    }
+
+    /// Removes the node at `position` relative to node zero and returns whether there was one, and
+    /// keeps `handles` of the root list up-to-date.
+    fn remove_with_handles(&mut self, position: S, handles: &mut Handles) -> bool {
+        if position <= zero() || position > self.length {
+            return false;
+        }
+        let (node_position, index) = self.descend::<true>(position);
+        if node_position == position {
+            self.remove_node(index, handles);
+            return true;
+        }
+        self.push_down_above(index);
+        let Some(sublist) = self.get_not_empty_sublist_at_index_mut(index) else {
+            return false;
+        };
+        if !sublist.remove_with_handles(position - node_position, handles) {
+            return false;
+        }
+        self.subtract_from_node_counts(index, 1);
+        self.update_aggregates_above(index);
+        true
+    }
+
+    /// Removes the node at `index`, which is not zero, by merging the gap before it with the one
+    /// after it and building the links from there on anew, which takes O(n - index) plus the
+    /// nodes of the sublist after it, which move into the sublist before it.
+    fn remove_node(&mut self, index: usize, handles: &mut Handles) {
+        assert!(index > 0 && index < self.size);
+        // the gap that ends at the removed node, which takes over the gap after it
+        let gap = index - 1;
+        self.push_down_from(gap);
+        let removed_length = self[(gap, 0)];
+        let last = index + 1 == self.size;
+        let before = self.take_sublist(gap, handles);
+        let after = self.take_sublist(index, handles);
+        let mut gaps: Vec<S> = (index..self.size - 1).map(|index| self[(index, 0)]).collect();
+        if let Some(merged) = gaps.first_mut() {
+            *merged += removed_length
+        }
+        if let Some(id) = self.ids.remove(gap) {
+            handles.forget_id(id);
+        }
+        self.values.remove(gap);
+        self.sublists.remove(gap);
+        self.size -= 1;
+        // if the removed node was the last one, the nodes before it end up after the last node
+        let mut appended = None;
+        if last {
+            appended = before
+        } else {
+            self.sublists[gap] = match (before, after) {
+                (None, None) => None,
+                (Some(before), None) => Some(before),
+                (None, Some(mut after)) => {
+                    after.shift_all_forward(removed_length);
+                    Some(after)
+                }
+                (Some(mut before), Some(after)) => {
+                    let offset = removed_length - before.length;
+                    before.append_nodes_of(*after, offset, handles);
+                    Some(before)
+                }
+            }
+        }
+        // the zero-degree link that ended at the last node is empty now
+        self.build_links_from(gap, &gaps, self.size);
+        self.relink(gap, handles);
+        if let Some(appended) = appended {
+            self.append_nodes_of(*appended, zero(), handles)
+        }
+    }
+
+    /// Appends the nodes of `other`, a sublist that is dropped afterwards, after its node zero, with
+    /// their values, handles and sublists, where node zero of `other` is `offset` after the last
+    /// node of this list. Builds the links over them at once instead of appending them one by one,
+    /// so that it only computes the aggregates those links end up with.
+    fn append_nodes_of(&mut self, mut other: Self, offset: S, handles: &mut Handles) {
+        other.push_down_all();
+        handles.forget_sublist(other.key);
+        // the gap after the last node of this list
+        let first = self.size - 1;
+        self.reserve(other.size - 1);
+        self.push_down_above(first);
+        let mut gaps = Vec::with_capacity(other.size - 1);
+        for index in 0..other.size - 1 {
+            let mut sublist = other.take_sublist(index, handles);
+            let mut gap = other[(index, 0)];
+            if index == 0 {
+                gap += offset;
+                if let Some(sublist) = &mut sublist {
+                    sublist.shift_all_forward(offset)
+                }
+            }
+            gaps.push(gap);
+            let gap_index = first + index;
+            if let Some(id) = other.ids[index] {
+                handles.move_id(id, (self.key, gap_index));
+            }
+            if let Some(sublist) = &sublist {
+                handles.move_sublist(sublist.key, (self.key, gap_index));
+            }
+            self.values.push(other.values[index]);
+            self.ids.push(other.ids[index]);
+            self.sublists.push(sublist);
+        }
+        self.size += gaps.len();
+        self.build_links_from(first, &gaps, self.size - 1);
+    }
+
+    /// Moves all nodes after node zero forward by `distance`, including the ones in sublists.
+    fn shift_all_forward(&mut self, distance: S) {
+        if self.is_empty() {
+            return;
+        }
+        self.length += distance;
+        for link_index in self.link_indices_above(0) {
+            self.link_lengths[link_index] += distance
+        }
+        if let Some(sublist) = self.get_not_empty_sublist_at_index_mut(0) {
+            sublist.shift_all_forward(distance)
+        }
+    }
+
+    /// Returns all nodes after node zero, including sublists, which pushes all tags down to get the
+    /// values.
+    fn entries(&mut self) -> Vec<NodeEntry<S, A::Value>> {
+        self.push_down_all();
+        let mut entries = vec![];
+        let mut position = zero();
+        for index in 0..self.size - 1 {
+            if let Some(sublist) = &mut self.sublists[index] {
+                entries.extend(sublist.entries().into_iter()
+                    .map(|entry| NodeEntry { position: position + entry.position, ..entry }));
+            }
+            position += self[(index, 0)];
+            entries.push(NodeEntry {
+                position,
+                value: self.values[index],
+                id: self.ids[index],
+            });
+        }
+        entries
+    }
+
+    /// Moves all nodes at or after `position` forward by `distance`. The node at zero cannot be
+    /// moved.
+    pub fn shift_forward(&mut self, position: P, distance: S) {
+        let relative_position = self.relative_or_zero(position);
+        assert!(relative_position > zero());
+        assert!(distance >= zero(), "cannot shift forward by a negative distance");
+        self.shift_links(relative_position, distance, true);
+        self.validate_after_mutation()
+    }
+
+    /// Moves all nodes at or after `position` back by `distance`. Panics if that would move a node
+    /// to or before the last node before `position`.
+    pub fn shift_backward(&mut self, position: P, distance: S) {
+        let relative_position = self.relative_or_zero(position);
+        assert!(relative_position > zero());
+        assert!(distance >= zero(), "cannot shift back by a negative distance");
+        if let Some(next) = self.position_at_or_after(position) {
+            let previous = self.position_before(position).unwrap();
+            assert!(distance < next - previous, "shifting back would move a node past another");
+        }
+        self.shift_links(relative_position, distance, false);
+        self.validate_after_mutation()
+    }
+
+    /// Moves all nodes at or after `position` by `delta`, like [Self::shift_forward] if it is
+    /// positive and like [Self::shift_backward] if it is negative. The delta can be of another type
+    /// than the positions, such as `i32` for `u32` positions, see [Delta].
+    pub fn shift<D: Delta<S>>(&mut self, position: P, delta: D) {
+        match delta.distance() {
+            Some((distance, false)) => self.shift_forward(position, distance),
+            Some((distance, true)) => self.shift_backward(position, distance),
+            None => {
+                // no gap is that long, so any node it moves moves past the one before it
+                assert!(self.relative_or_zero(position) > zero());
+                assert!(self.position_at_or_after(position).is_none(),
+                        "shifting back would move a node past another")
+            }
+        }
+    }
+
+    fn shift_links(&mut self, position: S, distance: S, forward: bool) {
+        let (node_position, index) = self.descend::<false>(position);
+        if index + 1 < self.size {
+            let shift = |length: &mut S| if forward {
+                *length += distance
+            } else {
+                *length = *length - distance
+            };
+            shift(&mut self.length);
+            for link_index in self.link_indices_above(index) {
+                shift(&mut self.link_lengths[link_index])
+            }
+        }
+        if let Some(sublist) = self.get_not_empty_sublist_at_index_mut(index) {
+            sublist.shift_links(position - node_position, distance, forward)
+        }
+    }
+}
+// endregion
+
+// region spaced list ranks
+impl<P: Position<S>, A: Aggregate, S: Spacing> SpacedList<P, A, S> {
+    /// Returns the number of nodes after node zero, including those in sublists.
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    /// Returns the position of the last node at or before `target_position`, including sublists,
+    /// and its rank, the number of nodes before it.
+    pub fn rank_at_or_before(&self, target_position: P) -> Option<(P, usize)> {
+        let (position, rank) = self.rank_at_or_before_relative(self.relative(target_position)?)?;
+        Some((self.absolute(position), rank))
+    }
+
+    /// Like [Self::rank_at_or_before], with positions relative to node zero.
+    fn rank_at_or_before_relative(&self, target_position: S) -> Option<(S, usize)> {
+        if target_position < zero() {
+            return None;
+        }
+        let mut position = zero();
+        let mut index = 0;
+        let mut rank = 0;
+        for degree in (0..self.depth()).rev() {
+            let possibly_next_index = index + (1 << degree);
+            if possibly_next_index < self.size {
+                let possibly_next_position = position + self[(index, degree)];
+                if possibly_next_position <= target_position {
+                    position = possibly_next_position;
+                    rank += self.link_node_counts[self.link_index(index, degree)];
+                    index = possibly_next_index;
+                }
+            }
+        }
+        match self.get_not_empty_sublist_at_index(index) {
+            Some(sublist) if position != target_position => {
+                let (position_in_sublist, rank_in_sublist) =
+                    sublist.rank_at_or_before_relative(target_position - position)?;
+                Some((position + position_in_sublist, rank + rank_in_sublist))
+            }
+            _ => Some((position, rank)),
+        }
+    }
+
+    /// Returns the position of the node with `rank` nodes before it, including sublists.
+    pub fn position_of_rank(&self, rank: usize) -> Option<P> {
+        self.relative_position_of_rank(rank).map(|position| self.absolute(position))
+    }
+
+    /// Like [Self::position_of_rank], but returns the position relative to node zero.
+    fn relative_position_of_rank(&self, rank: usize) -> Option<S> {
+        if rank > self.node_count {
+            return None;
+        }
+        let mut position = zero();
+        let mut index = 0;
+        let mut node_rank = 0;
+        for degree in (0..self.depth()).rev() {
+            let possibly_next_index = index + (1 << degree);
+            if possibly_next_index < self.size {
+                let possibly_next_rank = node_rank + self.link_node_counts[self.link_index(index, degree)];
+                if possibly_next_rank <= rank {
+                    position += self[(index, degree)];
+                    node_rank = possibly_next_rank;
+                    index = possibly_next_index;
+                }
+            }
+        }
+        if node_rank == rank {
+            return Some(position);
+        }
+        // the node is in the sublist after the node found, which spans the ranks up to the next one
+        let sublist = self.get_not_empty_sublist_at_index(index)?;
+        Some(position + sublist.relative_position_of_rank(rank - node_rank)?)
+    }
+}
+// endregion
+
+// region spaced list aggregates
+impl<P: Position<S>, A: Aggregate, S: Spacing> SpacedList<P, A, S> {
+    /// Returns the value of the node at `target_position`, or None if there is no node there or it
+    /// is node zero, which has no value.
+    pub fn value_at(&self, target_position: P) -> Option<A::Value> {
+        self.value_at_with_pending(self.relative_or_zero(target_position), A::Update::identity())
+    }
+
+    /// Returns the value of the node at `target_position` relative to node zero, with the `pending`
+    /// update from the links above this list applied.
+    fn value_at_with_pending(&self, target_position: S, pending: A::Update) -> Option<A::Value> {
+        if target_position <= zero() || target_position > self.length {
+            return None;
+        }
+        let (position, index) = self.descend::<true>(target_position);
+        if position == target_position {
+            // index is not zero, as target_position is positive
+            let pending = self.pending_update(index - 1).compose(pending);
+            return Some(pending.apply(self.values[index - 1]));
+        }
+        let pending = self.pending_update(index).compose(pending);
+        self.get_not_empty_sublist_at_index(index)?
+            .value_at_with_pending(target_position - position, pending)
+    }
+
+    /// Sets the value of the node at `target_position` and returns whether there is a node there
+    /// that can have a value.
+    pub fn set_value(&mut self, target_position: P, value: A::Value) -> bool {
+        self.set_value_relative(self.relative_or_zero(target_position), value)
+    }
+
+    /// Like [Self::set_value], with `target_position` relative to node zero.
+    fn set_value_relative(&mut self, target_position: S, value: A::Value) -> bool {
+        if target_position <= zero() || target_position > self.length {
+            return false;
+        }
+        let (position, index) = self.descend::<true>(target_position);
+        if position == target_position {
+            // index is not zero, as target_position is positive
+            self.push_down_above(index - 1);
+            self.values[index - 1] = value;
+            self.update_aggregates_above(index - 1);
+            return true;
+        }
+        self.push_down_above(index);
+        let set = match self.get_not_empty_sublist_at_index_mut(index) {
+            Some(sublist) => sublist.set_value_relative(target_position - position, value),
+            None => false,
+        };
+        if set {
+            self.update_aggregates_above(index)
+        }
+        set
+    }
+
+    /// Returns the aggregate of the values of the nodes in `range`, including sublists.
+    pub fn aggregate(&self, range: Range<P>) -> A {
+        self.aggregate_with_pending(self.relative_range(range), A::Update::identity())
+    }
+
+    /// Returns `range` relative to node zero, without the part before node zero, where there are no
+    /// nodes.
+    fn relative_range(&self, range: Range<P>) -> Range<S> {
+        self.relative_or_zero(range.start)..self.relative_or_zero(range.end)
+    }
+
+    /// Returns the aggregate of the values of the nodes in `range` relative to node zero, with the
+    /// `pending` update from the links above this list applied.
+    fn aggregate_with_pending(&self, range: Range<S>, pending: A::Update) -> A {
+        if range.start >= range.end {
+            return A::identity();
+        }
+        match self.depth() {
+            0 => A::identity(),
+            depth => self.aggregate_link(0, depth - 1, zero(), &range, pending),
+        }
+    }
+
+    /// Returns the aggregate of the values of the nodes in `range` that the link of degree
+    /// `degree` after the node at `index`, which is at `position`, spans, with the `pending` update
+    /// from the links above it applied.
+    fn aggregate_link(&self, index: usize, degree: usize, position: S, range: &Range<S>,
+                      pending: A::Update) -> A {
+        if index + 1 >= self.size {
+            return A::identity();
+        }
+        let link_index = self.link_index(index, degree);
+        let end_position = position + self.link_lengths[link_index];
+        // the link spans the nodes after position, up to and including end_position
+        if end_position < range.start || range.end <= position {
+            return A::identity();
+        }
+        if range.start <= position && end_position < range.end {
+            return pending
+                .apply_to_aggregate(self.link_aggregates[link_index], self.link_node_counts[link_index]);
+        }
+        if degree > 0 {
+            let pending = self.link_tags[link_index].compose(pending);
+            let middle = index + (1 << (degree - 1));
+            let middle_position = position + self[(index, degree - 1)];
+            return self.aggregate_link(index, degree - 1, position, range, pending)
+                .combine(self.aggregate_link(middle, degree - 1, middle_position, range, pending));
+        }
+        let in_sublist = match self.get_not_empty_sublist_at_index(index) {
+            Some(sublist) => {
+                let start = if range.start > position { range.start - position } else { zero() };
+                sublist.aggregate_with_pending(start..range.end - position, pending)
+            }
+            None => A::identity(),
+        };
+        if range.contains(&end_position) {
+            in_sublist.combine(A::of(pending.apply(self.values[index])))
+        } else {
+            in_sublist
+        }
+    }
+
+    /// Applies `update` to the values of all nodes in `range`, including sublists.
+    ///
+    /// Takes O(log n) per level of sublists: the update is applied to the aggregates of the links
+    /// that span the range right away, and only passed on to the links below them, and finally to
+    /// the values, when a query or mutation descends into them.
+    pub fn update(&mut self, range: Range<P>, update: A::Update) {
+        self.update_relative(self.relative_range(range), update)
+    }
+
+    /// Like [Self::update], with `range` relative to node zero.
+    fn update_relative(&mut self, range: Range<S>, update: A::Update) {
+        if range.start < range.end {
+            if let depth @ 1.. = self.depth() {
+                self.update_link(0, depth - 1, zero(), &range, update)
+            }
+        }
+        self.validate_after_mutation()
+    }
+
+    /// Applies `update` to the values of the nodes in `range` that the link of degree `degree`
+    /// after the node at `index`, which is at `position`, spans.
+    fn update_link(&mut self, index: usize, degree: usize, position: S, range: &Range<S>,
+                   update: A::Update) {
+        if index + 1 >= self.size {
+            return;
+        }
+        let link_index = self.link_index(index, degree);
+        let end_position = position + self.link_lengths[link_index];
+        if end_position < range.start || range.end <= position {
+            return;
+        }
+        if range.start <= position && end_position < range.end {
+            self.apply_to_link(index, degree, update);
+            return;
+        }
+        if degree > 0 {
+            self.push_down(index, degree);
+            let half = 1 << (degree - 1);
+            let middle_position = position + self[(index, degree - 1)];
+            self.update_link(index, degree - 1, position, range, update);
+            self.update_link(index + half, degree - 1, middle_position, range, update);
+            let [before, after] = child_link_indices(link_index);
+            self.link_aggregates[link_index] =
+                self.link_aggregates[before].combine(self.link_aggregates[after]);
+            return;
+        }
+        if let Some(sublist) = self.get_not_empty_sublist_at_index_mut(index) {
+            let start = if range.start > position { range.start - position } else { zero() };
+            sublist.update_relative(start..range.end - position, update)
+        }
+        if range.contains(&end_position) {
+            self.values[index] = update.apply(self.values[index])
+        }
+        self.link_aggregates[link_index] = self.zero_degree_aggregate(index)
+    }
+}
+// endregion
+
+// region spaced list import and export
+impl<P: Position<S>, A: Aggregate, S: Spacing> SpacedList<P, A, S> {
+    /// Returns the positions of all nodes after node zero, including sublists, in order.
+    pub fn to_positions(&self) -> Vec<P> {
+        self.positions().skip(1).collect()
+    }
+
+    /// Returns the distances between all consecutive nodes, including sublists, in order, starting
+    /// with the one from node zero to the node after it.
+    pub fn to_gaps(&self) -> Vec<S> {
+        let mut previous = self.origin;
+        self.positions().skip(1)
+            .map(|position| {
+                let gap = position - previous;
+                previous = position;
+                gap
+            })
+            .collect()
+    }
+
+    /// Like [Self::from_gaps], but with the values and handles of the nodes, and node zero at
+    /// `origin`.
+    fn from_nodes(origin: P, gaps: &[S], values: Vec<A::Value>, ids: Vec<Option<NodeId>>) -> Self {
+        let mut list = Self::with_origin(origin);
+        list.reserve(gaps.len());
+        list.size = gaps.len() + 1;
+        list.sublists = gaps.iter().map(|_| None).collect();
+        list.values = values;
+        list.ids = ids;
+        list.build_links_from(0, gaps, gaps.len());
+        list.validate_after_mutation();
+        list
+    }
+}
+
+impl<S: Spacing, A: Aggregate> SpacedList<S, A> {
+    /// Returns a list with nodes at `positions` after node zero, which must be positive and
+    /// strictly increasing, see [Self::from_gaps].
+    pub fn from_positions(positions: &[S]) -> Self {
+        let mut previous = zero();
+        let gaps: Vec<S> = positions.iter()
+            .map(|&position| {
+                assert!(position > previous, "positions must be positive and strictly increasing");
+                let gap = position - previous;
+                previous = position;
+                gap
+            })
+            .collect();
+        Self::from_gaps(&gaps)
+    }
+
+    /// Returns a list with a node after each of `gaps` in turn, which must be positive, and default
+    /// values.
+    ///
+    /// Fills the links of each degree from the ones below them, which takes O(n) and leaves no
+    /// sublists, instead of appending the nodes one by one.
+    pub fn from_gaps(gaps: &[S]) -> Self {
+        Self::from_nodes(zero(), gaps, vec![default(); gaps.len()], vec![None; gaps.len()])
+    }
+}
+
+impl<P: Position<S>, A: Aggregate, S: Spacing> SpacedList<P, A, S> {
+    /// Builds the links over the zero-degree links after nodes `first..end` anew, from `gaps`, the
+    /// lengths of the zero-degree links after node `first` on, and from the values and sublists of
+    /// their nodes, which takes O(end - first). The links after those that are left over are
+    /// empty, and the ones from `end` on must be empty already.
+    ///
+    /// The tags of the links above the one after node `first` must have been pushed down before.
+    /// The tags of the links after it are dropped, as they can only be left over from nodes that
+    /// are gone.
+    fn build_links_from(&mut self, first: usize, gaps: &[S], end: usize) {
+        assert_eq!(first + gaps.len() + 1, self.size);
+        assert!(first + gaps.len() <= end && end < self.capacity);
+        for index in first..end {
+            let link_index = self.link_index(index, 0);
+            let gap = gaps.get(index - first).copied();
+            assert!(gap.is_none_or(|gap| gap > zero()), "gaps must be positive");
+            self.link_lengths[link_index] = gap.unwrap_or(zero());
+            self.link_node_counts[link_index] = match gap {
+                Some(_) =>
+                    1 + self.sublists[index].as_ref().map_or(0, |sublist| sublist.node_count),
+                None => 0,
+            };
+            self.link_aggregates[link_index] = self.zero_degree_aggregate(index);
+        }
+        for degree in 1..self.depth() {
+            for index in (first >> degree << degree..end).step_by(1 << degree) {
+                let link_index = self.link_index(index, degree);
+                let [before, after] = child_link_indices(link_index);
+                if index > first {
+                    self.link_tags[link_index] = A::Update::identity();
+                }
+                self.link_lengths[link_index] =
+                    self.link_lengths[before] + self.link_lengths[after];
+                self.link_node_counts[link_index] =
+                    self.link_node_counts[before] + self.link_node_counts[after];
+                self.link_aggregates[link_index] =
+                    self.link_aggregates[before].combine(self.link_aggregates[after]);
+            }
+        }
+        (self.length, self.node_count) = match self.depth() {
+            0 => (zero(), 0),
+            depth => (self[(0, depth - 1)], self.link_node_counts[self.link_index(0, depth - 1)]),
+        };
+    }
+}
+// endregion
+
+// region spaced list batch queries
+/// Returns `rest`, with `first` in front of it.
+fn prepend<'a, P: Position<S>, A: Aggregate, S: Spacing>(first: TraversalResult<'a, P, A, S>,
+                                                         rest: Vec<TraversalResult<'a, P, A, S>>)
+                                                         -> Vec<TraversalResult<'a, P, A, S>> {
+    let mut result = Vec::with_capacity(rest.len() + 1);
+    result.push(first);
+    result.extend(rest);
+    result
+}
+
+/// Batch versions of the queries above. Each takes many target positions, which must be sorted in
+/// ascending order, and returns the same as calling the single query for every one of them.
+///
+/// Instead of descending from the top of this list once per target, a batch splits the targets at
+/// every link it passes, so that every link is read at most once per batch, and descends into each
+/// sublist at most once, with all targets that end up in that sublist.
+impl<P: Position<S>, A: Aggregate, S: Spacing> SpacedList<P, A, S> {
+    /// Returns where a batch query over all of this list starts, see [Self::batch_descend].
+    fn batch_start(&self) -> (S, usize, usize) {
+        (zero(), 0, self.depth())
+    }
+
+    /// Splits `targets` into runs that share the same last node before them, or at them if
+    /// `INCLUSIVE` is true, in this list, not in sublists, and returns the position and index of
+    /// that node along with the number of targets in the run. Assumes every target has such a node.
+    ///
+    /// Descends from `from`, the position and index of a node and a degree, such that all targets
+    /// lie within the link of that degree from that node. [Self::batch_start] covers all of this
+    /// list.
+    fn batch_descend<const INCLUSIVE: bool>(&self, targets: &[S],
+                                            (position, index, degree): (S, usize, usize))
+                                            -> Vec<(S, usize, usize)> {
+        let mut runs = vec![];
+        self.batch_descend_from::<INCLUSIVE>(targets, position, index, degree, &mut runs);
+        runs
+    }
+
+    /// Continues [Self::batch_descend] for the targets that lie behind the node at `index` and
+    /// before the end of its link of degree `degree`.
+    fn batch_descend_from<const INCLUSIVE: bool>(&self, targets: &[S], position: S, index: usize,
+                                                 degree: usize, runs: &mut Vec<(S, usize, usize)>) {
+        if targets.is_empty() {
+            return;
+        }
+        if degree == 0 {
+            runs.push((position, index, targets.len()));
+            return;
+        }
+        let degree = degree - 1;
+        let next_index = index + (1 << degree);
+        if next_index >= self.size {
+            self.batch_descend_from::<INCLUSIVE>(targets, position, index, degree, runs);
+            return;
+        }
+        let next_position = position + self[(index, degree)];
+        let split = targets.partition_point(|&target|
+            if INCLUSIVE { target < next_position } else { target <= next_position });
+        self.batch_descend_from::<INCLUSIVE>(&targets[..split], position, index, degree, runs);
+        self.batch_descend_from::<INCLUSIVE>(&targets[split..], next_position, next_index, degree,
+                                             runs);
+    }
+
+    /// Returns the targets relative to `position`.
+    fn relative_targets(targets: &[S], position: S) -> Vec<S> {
+        targets.iter().map(|&target| target - position).collect()
+    }
+
+    /// Batch version of [Self::node_before].
+    fn batch_node_before(&self, targets: &[S], from: (S, usize, usize))
+                         -> Vec<Option<Vec<TraversalResult<'_, P, A, S>>>> {
+        debug_assert!(targets.is_sorted());
+        let start = targets.partition_point(|&target| target <= zero());
+        let mut results: Vec<_> = targets[..start].iter().map(|_| None).collect();
+        let mut remaining = &targets[start..];
+        for (position, index, len) in self.batch_descend::<false>(remaining, from) {
+            let (run, rest) = remaining.split_at(len);
+            remaining = rest;
+            let node = || TraversalResult { list: self, position, index };
+            match self.get_not_empty_sublist_at_index(index) {
+                Some(sublist) => results.extend(
+                    sublist.batch_node_before(&Self::relative_targets(run, position),
+                                              sublist.batch_start())
+                        .into_iter()
+                        .map(|sublist_result| match sublist_result {
+                            Some(sublist_result)
+                            if sublist_result.len() > 1 || sublist_result[0].index != 0 =>
+                                Some(prepend(node(), sublist_result)),
+                            _ => Some(vec![node()]),
+                        })),
+                None => results.extend(run.iter().map(|_| Some(vec![node()]))),
+            }
+        }
+        results
+    }
+
+    /// Batch version of [Self::node_at_or_before].
+    fn batch_node_at_or_before(&self, targets: &[S], from: (S, usize, usize))
+                               -> Vec<Option<Vec<TraversalResult<'_, P, A, S>>>> {
+        debug_assert!(targets.is_sorted());
+        let start = targets.partition_point(|&target| target < zero());
+        let mut results: Vec<_> = targets[..start].iter().map(|_| None).collect();
+        let mut remaining = &targets[start..];
+        for (position, index, len) in self.batch_descend::<true>(remaining, from) {
+            let (run, rest) = remaining.split_at(len);
+            remaining = rest;
+            let node = || TraversalResult { list: self, position, index };
+            // the targets at the node itself come first in the run
+            let at_node = run.partition_point(|&target| target == position);
+            results.extend(run[..at_node].iter().map(|_| Some(vec![node()])));
+            let run = &run[at_node..];
+            match self.get_not_empty_sublist_at_index(index) {
+                Some(sublist) => results.extend(
+                    sublist.batch_node_at_or_before(&Self::relative_targets(run, position),
+                                                    sublist.batch_start())
+                        .into_iter()
+                        .map(|sublist_result| Some(prepend(node(), sublist_result?)))),
+                None => results.extend(run.iter().map(|_| Some(vec![node()]))),
+            }
+        }
+        results
+    }
+
+    /// Batch version of [Self::node_at].
+    fn batch_node_at(&self, targets: &[S], from: (S, usize, usize))
+                     -> Vec<Option<Vec<TraversalResult<'_, P, A, S>>>> {
+        debug_assert!(targets.is_sorted());
+        let start = targets.partition_point(|&target| target < zero());
+        let mut results: Vec<_> = targets[..start].iter().map(|_| None).collect();
+        let mut remaining = &targets[start..];
+        for (position, index, len) in self.batch_descend::<true>(remaining, from) {
+            let (run, rest) = remaining.split_at(len);
+            remaining = rest;
+            let node = || TraversalResult { list: self, position, index };
+            let at_node = run.partition_point(|&target| target == position);
+            results.extend(run[..at_node].iter().map(|_| Some(vec![node()])));
+            let run = &run[at_node..];
+            match self.get_not_empty_sublist_at_index(index) {
+                Some(sublist) => results.extend(
+                    sublist.batch_node_at(&Self::relative_targets(run, position),
+                                          sublist.batch_start())
+                        .into_iter()
+                        .map(|sublist_result| Some(prepend(node(), sublist_result?)))),
+                None => results.extend(run.iter().map(|_| None)),
+            }
+        }
+        results
+    }
+
+    /// Batch version of [Self::node_at_or_after].
+    fn batch_node_at_or_after(&self, targets: &[S], from: (S, usize, usize))
+                              -> Vec<Option<Vec<TraversalResult<'_, P, A, S>>>> {
+        debug_assert!(targets.is_sorted());
+        let start = targets.partition_point(|&target| target < zero());
+        let end = targets.partition_point(|&target| target <= self.length);
+        let first = || Some(vec![TraversalResult { list: self, position: zero(), index: 0 }]);
+        let mut results: Vec<_> = targets[..start].iter().map(|_| first()).collect();
+        let mut remaining = &targets[start..end];
+        for (position, index, len) in self.batch_descend::<true>(remaining, from) {
+            let (run, rest) = remaining.split_at(len);
+            remaining = rest;
+            let node = || TraversalResult { list: self, position, index };
+            let at_node = run.partition_point(|&target| target == position);
+            results.extend(run[..at_node].iter().map(|_| Some(vec![node()])));
+            let run = &run[at_node..];
+            // the sublist may end before a target, in which case the next node in this list is
+            // the result
+            let next_node = || Some(vec![TraversalResult {
+                list: self,
+                position: position + self[(index, 0)],
+                index: index + 1,
+            }]);
+            match self.get_not_empty_sublist_at_index(index) {
+                Some(sublist) => results.extend(
+                    sublist.batch_node_at_or_after(&Self::relative_targets(run, position),
+                                                   sublist.batch_start())
+                        .into_iter()
+                        .map(|sublist_result| match sublist_result {
+                            Some(sublist_result) => Some(prepend(node(), sublist_result)),
+                            None => next_node(),
+                        })),
+                None => results.extend(run.iter().map(|_| next_node())),
+            }
+        }
+        results.extend(targets[end..].iter().map(|_| None));
+        results
+    }
+
+    /// Batch version of [Self::node_after].
+    fn batch_node_after(&self, targets: &[S], from: (S, usize, usize))
+                        -> Vec<Option<Vec<TraversalResult<'_, P, A, S>>>> {
+        debug_assert!(targets.is_sorted());
+        let start = targets.partition_point(|&target| target < zero());
+        let end = targets.partition_point(|&target| target < self.length);
+        let first = || Some(vec![TraversalResult { list: self, position: zero(), index: 0 }]);
+        let mut results: Vec<_> = targets[..start].iter().map(|_| first()).collect();
+        let mut remaining = &targets[start..end];
+        for (position, index, len) in self.batch_descend::<true>(remaining, from) {
+            let (run, rest) = remaining.split_at(len);
+            remaining = rest;
+            let node = || TraversalResult { list: self, position, index };
+            let next_node = || Some(vec![TraversalResult {
+                list: self,
+                position: position + self[(index, 0)],
+                index: index + 1,
+            }]);
+            match self.get_not_empty_sublist_at_index(index) {
+                Some(sublist) => results.extend(
+                    sublist.batch_node_after(&Self::relative_targets(run, position),
+                                             sublist.batch_start())
+                        .into_iter()
+                        .map(|sublist_result| match sublist_result {
+                            Some(sublist_result) => Some(prepend(node(), sublist_result)),
+                            None => next_node(),
+                        })),
+                None => results.extend(run.iter().map(|_| next_node())),
+            }
+        }
+        results.extend(targets[end..].iter().map(|_| None));
+        results
+    }
+
+    /// Runs the batch query `nodes` for the sorted `targets` relative to node zero, answers the
+    /// ones before node zero with `before`, and returns the positions of the nodes it finds.
+    fn batch<'a>(&'a self, targets: &[P], before: Option<P>,
+                 nodes: impl FnOnce(&[S]) -> Vec<Option<Vec<TraversalResult<'a, P, A, S>>>>)
+                 -> Vec<Option<P>> {
+        let start = targets.partition_point(|&target| target < self.origin);
+        let relative_targets: Vec<S> =
+            targets[start..].iter().map(|&target| target - self.origin).collect();
+        let mut results = vec![before; start];
+        results.extend(nodes(&relative_targets).into_iter()
+            .map(|path| path.map(|path| self.absolute(path_position(path)))));
+        results
+    }
+
+    /// Returns the position of the last node before each of the sorted `targets`.
+    pub fn batch_before(&self, targets: &[P]) -> Vec<Option<P>> {
+        self.batch(targets, None, |targets| self.batch_node_before(targets, self.batch_start()))
+    }
+
+    /// Returns the position of the last node at or before each of the sorted `targets`.
+    pub fn batch_at_or_before(&self, targets: &[P]) -> Vec<Option<P>> {
+        self.batch(targets, None,
+                   |targets| self.batch_node_at_or_before(targets, self.batch_start()))
+    }
+
+    /// Returns each of the sorted `targets` that there is a node at.
+    pub fn batch_at(&self, targets: &[P]) -> Vec<Option<P>> {
+        self.batch(targets, None, |targets| self.batch_node_at(targets, self.batch_start()))
+    }
+
+    /// Returns the position of the first node at or after each of the sorted `targets`.
+    pub fn batch_at_or_after(&self, targets: &[P]) -> Vec<Option<P>> {
+        self.batch(targets, Some(self.origin),
+                   |targets| self.batch_node_at_or_after(targets, self.batch_start()))
+    }
+
+    /// Returns the position of the first node after each of the sorted `targets`.
+    pub fn batch_after(&self, targets: &[P]) -> Vec<Option<P>> {
+        self.batch(targets, Some(self.origin),
+                   |targets| self.batch_node_after(targets, self.batch_start()))
+    }
+}
+// endregion
+
+// region spaced list backend
+/// The operations that every layout of a spaced list supports, so that code can be written once
+/// and run on whichever layout suits a workload best. All positions are absolute, i.e. include the
+/// positions of the nodes that sublists hang off.
+pub trait SpacedListBackend<S: Spacing>: Default {
+    type Positions<'a>: Iterator<Item=S> where Self: 'a;
+
+    fn insert(&mut self, position: S);
+
+    /// Returns the position of the last node before `target_position`.
+    fn position_before(&self, target_position: S) -> Option<S>;
+
+    /// Returns the position of the last node at or before `target_position`.
+    fn position_at_or_before(&self, target_position: S) -> Option<S>;
+
+    /// Returns `target_position` if there is a node there.
+    fn position_at(&self, target_position: S) -> Option<S>;
+
+    /// Returns the position of the first node at or after `target_position`.
+    fn position_at_or_after(&self, target_position: S) -> Option<S>;
+
+    /// Returns the position of the first node after `target_position`.
+    fn position_after(&self, target_position: S) -> Option<S>;
+
+    /// Returns the positions of all nodes in ascending order, starting with the node at zero.
+    fn positions(&self) -> Self::Positions<'_>;
+}
+
+/// Returns the position of the node at the end of `path` relative to node zero of the list it
+/// starts in.
+fn path_position<P: Position<S>, A: Aggregate, S: Spacing>(path: Vec<TraversalResult<P, A, S>>)
+                                                           -> S {
+    path.iter().fold(zero(), |position, result| position + result.position)
+}
+
+/// Queries that find a node before node zero find none, and the ones that find a node after it
+/// find node zero.
+impl<P: Position<S>, A: Aggregate, S: Spacing> SpacedList<P, A, S> {
+    /// Runs the query `node` for `target_position` relative to node zero, or returns `before` if it
+    /// is before node zero, and returns the position of the node it finds.
+    fn find<'a>(&'a self, target_position: P, before: Option<P>,
+                node: impl FnOnce(S) -> Option<Vec<TraversalResult<'a, P, A, S>>>) -> Option<P> {
+        match self.relative(target_position) {
+            Some(target_position) =>
+                node(target_position).map(|path| self.absolute(path_position(path))),
+            None => before,
+        }
+    }
+
+    /// Returns the position of the last node before `target_position`.
+    pub fn position_before(&self, target_position: P) -> Option<P> {
+        self.find(target_position, None, |target_position| self.node_before(target_position))
+    }
+
+    /// Returns the position of the last node at or before `target_position`.
+    pub fn position_at_or_before(&self, target_position: P) -> Option<P> {
+        self.find(target_position, None,
+                  |target_position| self.node_at_or_before(target_position))
+    }
+
+    /// Returns `target_position` if there is a node there.
+    pub fn position_at(&self, target_position: P) -> Option<P> {
+        self.find(target_position, None, |target_position| self.node_at(target_position))
+    }
+
+    /// Returns the position of the first node at or after `target_position`.
+    pub fn position_at_or_after(&self, target_position: P) -> Option<P> {
+        self.find(target_position, Some(self.origin),
+                  |target_position| self.node_at_or_after(target_position))
+    }
+
+    /// Returns the position of the first node after `target_position`.
+    pub fn position_after(&self, target_position: P) -> Option<P> {
+        self.find(target_position, Some(self.origin),
+                  |target_position| self.node_after(target_position))
+    }
+
+    /// Returns the positions of all nodes in ascending order, starting with node zero.
+    pub fn positions(&self) -> Positions<'_, P, A, S> {
+        Positions {
+            stack: vec![(self, 0, zero())],
+            end: self.size,
+            origin: self.origin,
+        }
+    }
+}
+
+impl<S: Spacing, A: Aggregate> SpacedListBackend<S> for SpacedList<S, A> {
+    type Positions<'a> = Positions<'a, S, A> where S: 'a, A: 'a;
+
+    fn insert(&mut self, position: S) {
+        SpacedList::insert(self, position)
+    }
+
+    fn position_before(&self, target_position: S) -> Option<S> {
+        SpacedList::position_before(self, target_position)
+    }
+
+    fn position_at_or_before(&self, target_position: S) -> Option<S> {
+        SpacedList::position_at_or_before(self, target_position)
+    }
+
+    fn position_at(&self, target_position: S) -> Option<S> {
+        SpacedList::position_at(self, target_position)
+    }
+
+    fn position_at_or_after(&self, target_position: S) -> Option<S> {
+        SpacedList::position_at_or_after(self, target_position)
+    }
+
+    fn position_after(&self, target_position: S) -> Option<S> {
+        SpacedList::position_after(self, target_position)
+    }
+
+    fn positions(&self) -> Positions<'_, S, A> {
+        SpacedList::positions(self)
+    }
+}
+
+/// Iterator over the positions of all nodes of a [SpacedList], including sublists.
+pub struct Positions<'a, P: Position<S>, A: Aggregate = (), S: Spacing = P> {
+    /// The lists being iterated, innermost last, each with the index and the position relative to
+    /// node zero of the outermost list of the next node to visit in it.
+    stack: Vec<(&'a SpacedList<P, A, S>, usize, S)>,
+    /// The index in the outermost list before which to stop.
+    end: usize,
+    /// The position of node zero of the outermost list.
+    origin: P,
+}
+
+impl<P: Position<S>, A: Aggregate, S: Spacing> Iterator for Positions<'_, P, A, S> {
+    type Item = P;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let nesting = self.stack.len();
+            let end = self.end;
+            let (list, index, position) = self.stack.last_mut()?;
+            if *index >= if nesting == 1 { end } else { list.size } {
+                self.stack.pop();
+                continue;
+            }
+            let list = *list;
+            let node_index = *index;
+            let node_position = *position;
+            // node zero of a sublist is the node it hangs off, which has been visited already
+            let visited = node_index == 0 && nesting > 1;
+            *index += 1;
+            if node_index + 1 < list.size {
+                *position += list[(node_index, 0)];
+            }
+            if let Some(sublist) = list.get_not_empty_sublist_at_index(node_index) {
+                self.stack.push((sublist, 0, node_position));
+            }
+            if visited {
+                continue;
+            }
+            return Some(self.origin + node_position);
+        }
+    }
+}
+// endregion
+
+// region spaced list parallel operations
+/// One of the batch queries, such as [SpacedList::batch_node_before].
+#[cfg(feature = "std")]
+type BatchQuery<'a, P, A, S> = fn(&'a SpacedList<P, A, S>, &[S], (S, usize, usize))
+                                  -> Vec<Option<Vec<TraversalResult<'a, P, A, S>>>>;
+
+impl<P: Position<S> + Send + Sync, A: Aggregate + Send + Sync, S: Spacing + Send + Sync>
+    SpacedList<P, A, S>
+    where A::Value: Send + Sync, A::Update: Send + Sync {
+    /// Returns the index and position of the first node of each of at most `parts` parts of this
+    /// list, and the degree of the links they are split at. Parts start at the ends of links of the
+    /// lowest degree that needs no more than `parts` of them, so each part is the span of one such
+    /// link, with the sublists within it.
+    fn split_points(&self, parts: usize) -> (Vec<(usize, S)>, usize) {
+        assert!(parts > 0);
+        let mut degree = 0;
+        while self.size.div_ceil(1 << degree) > parts {
+            degree += 1;
+        }
+        let mut points = vec![(0, zero())];
+        let mut position = zero();
+        let mut index = 0;
+        while index + (1 << degree) < self.size {
+            position += self[(index, degree)];
+            index += 1 << degree;
+            points.push((index, position));
+        }
+        (points, degree)
+    }
+
+    /// Splits the iteration over the positions of all nodes, including sublists, into at most
+    /// `parts` iterators over consecutive runs of positions, which can be run on separate threads.
+    pub fn split_positions(&self, parts: usize) -> Vec<Positions<'_, P, A, S>> {
+        let (points, _) = self.split_points(parts);
+        let ends = points.iter().skip(1).map(|&(index, _)| index).chain([self.size]);
+        points.iter().zip(ends).map(|(&(index, position), end)| Positions {
+            stack: vec![(self, index, position)],
+            end,
+            origin: self.origin,
+        }).collect()
+    }
+}
+
+/// The methods that run on threads, which need the `std` feature.
+#[cfg(feature = "std")]
+impl<P: Position<S> + Send + Sync, A: Aggregate + Send + Sync, S: Spacing + Send + Sync>
+    SpacedList<P, A, S>
+    where A::Value: Send + Sync, A::Update: Send + Sync {
+    /// Calls `f` with each part of [Self::split_positions] on a thread of its own, and returns the
+    /// results in order.
+    pub fn par_map_positions<R: Send>(&self, threads: usize,
+                                      f: impl Fn(Positions<'_, P, A, S>) -> R + Sync) -> Vec<R> {
+        let parts = self.split_positions(threads);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = parts.into_iter()
+                .map(|part| scope.spawn(|| f(part)))
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        })
+    }
+
+    /// Collects the positions of all nodes, including sublists, on up to `threads` threads.
+    pub fn par_positions(&self, threads: usize) -> Vec<P> {
+        self.par_map_positions(threads, |part| part.collect::<Vec<_>>()).concat()
+    }
+
+    /// Calls `f` with the position of every node, including sublists, on up to `threads` threads,
+    /// in no particular order.
+    pub fn par_for_each_position(&self, threads: usize, f: impl Fn(P) + Sync) {
+        self.par_map_positions(threads, |part| part.for_each(&f));
+    }
+
+    /// Counts the nodes, including sublists, on up to `threads` threads.
+    pub fn par_count(&self, threads: usize) -> usize {
+        self.par_map_positions(threads, |part| part.count()).into_iter().sum()
+    }
+
+    /// Runs `batch` for runs of the sorted `targets` on up to `threads` threads. The targets are
+    /// split at the same high-degree links as [Self::split_positions], and each run descends from
+    /// the link it lies in rather than from the top of the list. A target at a split point belongs
+    /// to the run before it unless `INCLUSIVE` is true, as for [Self::batch_descend].
+    fn par_batch<'a, const INCLUSIVE: bool>(&'a self, targets: &[S], threads: usize,
+                                            batch: BatchQuery<'a, P, A, S>)
+                                            -> Vec<Option<Vec<TraversalResult<'a, P, A, S>>>> {
+        debug_assert!(targets.is_sorted());
+        let (points, degree) = self.split_points(threads);
+        let mut runs = vec![];
+        let mut remaining = targets;
+        for (&(index, position), &(next_index, next_position)) in points.iter().zip(&points[1..]) {
+            let split = remaining.partition_point(|&target|
+                if INCLUSIVE { target < next_position } else { target <= next_position });
+            let (run, rest) = remaining.split_at(split);
+            runs.push((run, (position, index, degree)));
+            remaining = rest;
+        }
+        let &(index, position) = points.last().unwrap();
+        runs.push((remaining, (position, index, degree)));
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = runs.into_iter()
+                .map(|(run, from)| scope.spawn(move || batch(self, run, from)))
+                .collect();
+            handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+        })
+    }
+
+    /// Parallel version of [Self::batch_before].
+    pub fn par_batch_before(&self, targets: &[P], threads: usize) -> Vec<Option<P>> {
+        self.batch(targets, None,
+                   |targets| self.par_batch::<false>(targets, threads, Self::batch_node_before))
+    }
+
+    /// Parallel version of [Self::batch_at_or_before].
+    pub fn par_batch_at_or_before(&self, targets: &[P], threads: usize) -> Vec<Option<P>> {
+        self.batch(targets, None, |targets| {
+            self.par_batch::<true>(targets, threads, Self::batch_node_at_or_before)
+        })
+    }
+
+    /// Parallel version of [Self::batch_at].
+    pub fn par_batch_at(&self, targets: &[P], threads: usize) -> Vec<Option<P>> {
+        self.batch(targets, None,
+                   |targets| self.par_batch::<true>(targets, threads, Self::batch_node_at))
+    }
+
+    /// Parallel version of [Self::batch_at_or_after].
+    pub fn par_batch_at_or_after(&self, targets: &[P], threads: usize) -> Vec<Option<P>> {
+        self.batch(targets, Some(self.origin),
+                   |targets| self.par_batch::<true>(targets, threads, Self::batch_node_at_or_after))
+    }
+
+    /// Parallel version of [Self::batch_after].
+    pub fn par_batch_after(&self, targets: &[P], threads: usize) -> Vec<Option<P>> {
+        self.batch(targets, Some(self.origin),
+                   |targets| self.par_batch::<true>(targets, threads, Self::batch_node_after))
+    }
+}
+// endregion
+
 // region spaced list indexing
-impl<S: Spacing> Index<(usize, usize)> for SpacedList<S> {
+impl<P: Position<S>, A: Aggregate, S: Spacing> Index<(usize, usize)> for SpacedList<P, A, S> {
     type Output = S;
 
     fn index(&self, (node_index, degree): (usize, usize)) -> &Self::Output {
-        &self.link_lengths[link_index(node_index, degree)]
+        &self.link_lengths[self.link_index(node_index, degree)]
     }
 }
 
-impl<S: Spacing> IndexMut<(usize, usize)> for SpacedList<S> {
+impl<P: Position<S>, A: Aggregate, S: Spacing> IndexMut<(usize, usize)> for SpacedList<P, A, S> {
     fn index_mut(&mut self, (node_index, degree): (usize, usize)) -> &mut Self::Output {
-        &mut self.link_lengths[link_index(node_index, degree)]
+        let link_index = self.link_index(node_index, degree);
+        &mut self.link_lengths[link_index]
+    }
+}
+// endregion
+
+// region spaced list validation
+/// A structural invariant of a [SpacedList] that does not hold, as returned by
+/// [SpacedList::validate].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InvariantViolation {
+    /// The list has no nodes, not even the one at position zero.
+    ZeroSize,
+    /// The list has more nodes than it has space for.
+    SizeExceedsCapacity { size: usize, capacity: usize },
+    /// `link_lengths` does not have the length implied by the capacity of the list.
+    LinkLengthsLength { expected: usize, actual: usize },
+    /// `sublists` does not have exactly one entry per link between two nodes.
+    SublistsLength { expected: usize, actual: usize },
+    /// The zero-degree link after the node at `index` is not positive, even though there is a
+    /// node after it.
+    NonPositiveLink { index: usize },
+    /// The zero-degree link after the node at `index` is not zero, even though there is no node
+    /// after it.
+    LinkAfterLastNode { index: usize },
+    /// The link of degree `degree` after the node at `index` is not the sum of the two links of
+    /// degree `degree - 1` below it.
+    LinkSum { index: usize, degree: usize },
+    /// `length` is not equal to the length of the link of the highest degree.
+    Length,
+    /// The sublist after the node at `index` reaches the next node or beyond.
+    SublistTooLong { index: usize },
+    /// The sublist after the node at `index` is not valid itself.
+    InSublist { index: usize, violation: Box<InvariantViolation> },
+    /// The link of degree `degree` after the node at `index` does not count the nodes it spans.
+    LinkNodeCount { index: usize, degree: usize },
+    /// `node_count` is not equal to the node count of the link of the highest degree.
+    NodeCount,
+    /// `values` does not have exactly one entry per link between two nodes.
+    ValuesLength { expected: usize, actual: usize },
+    /// The link of degree `degree` after the node at `index` does not aggregate the values of the
+    /// nodes it spans.
+    LinkAggregate { index: usize, degree: usize },
+    /// `ids` does not have exactly one entry per link between two nodes.
+    IdsLength { expected: usize, actual: usize },
+    /// The sublist after the node at `index` is not mapped to this list and that node by the root
+    /// list.
+    ParentLink { index: usize },
+    /// The handle `id` is not where it is mapped to, or a handle in this list is not mapped to
+    /// where it is.
+    IdLocation { id: NodeId },
+    /// The root list maps another number of sublists than it has.
+    SublistLocations { expected: usize, actual: usize },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroSize =>
+                write!(f, "list has a size of zero"),
+            Self::SizeExceedsCapacity { size, capacity } =>
+                write!(f, "size {} exceeds capacity {}", size, capacity),
+            Self::LinkLengthsLength { expected, actual } =>
+                write!(f, "expected {} link lengths, found {}", expected, actual),
+            Self::SublistsLength { expected, actual } =>
+                write!(f, "expected {} sublist slots, found {}", expected, actual),
+            Self::NonPositiveLink { index } =>
+                write!(f, "link after node {} is not positive", index),
+            Self::LinkAfterLastNode { index } =>
+                write!(f, "link after node {} is behind the last node, but not zero", index),
+            Self::LinkSum { index, degree } =>
+                write!(f, "link of degree {} after node {} is not the sum of the links below it",
+                       degree, index),
+            Self::Length =>
+                write!(f, "length does not match the link of the highest degree"),
+            Self::SublistTooLong { index } =>
+                write!(f, "sublist after node {} does not fit before the next node", index),
+            Self::InSublist { index, violation } =>
+                write!(f, "in sublist after node {}: {}", index, violation),
+            Self::LinkNodeCount { index, degree } =>
+                write!(f, "link of degree {} after node {} does not count the nodes it spans",
+                       degree, index),
+            Self::NodeCount =>
+                write!(f, "node count does not match the link of the highest degree"),
+            Self::ValuesLength { expected, actual } =>
+                write!(f, "expected {} values, found {}", expected, actual),
+            Self::LinkAggregate { index, degree } =>
+                write!(f, "link of degree {} after node {} does not aggregate the nodes it spans",
+                       degree, index),
+            Self::IdsLength { expected, actual } =>
+                write!(f, "expected {} node ids, found {}", expected, actual),
+            Self::ParentLink { index } =>
+                write!(f, "sublist after node {} is not mapped to where it is", index),
+            Self::IdLocation { id } =>
+                write!(f, "node id {:?} is not mapped to where it is", id),
+            Self::SublistLocations { expected, actual } =>
+                write!(f, "expected {} mapped sublists, found {}", expected, actual),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvariantViolation {}
+
+impl<P: Position<S>, A: Aggregate, S: Spacing> SpacedList<P, A, S> {
+    /// Checks that the link lengths of this list are consistent with each other and with its
+    /// length, that every sublist is valid and fits strictly inside the link it belongs to, and
+    /// that every handle is mapped to where its node is.
+    pub fn validate(&self) -> Result<(), InvariantViolation> {
+        self.validate_links()?;
+        self.validate_handles()
+    }
+
+    /// Like [Self::validate], but does not check the handle locations, which the root list keeps
+    /// apart while it is being mutated.
+    fn validate_links(&self) -> Result<(), InvariantViolation> {
+        use InvariantViolation::*;
+
+        if self.size == 0 {
+            return Err(ZeroSize);
+        }
+        if self.size > self.capacity {
+            return Err(SizeExceedsCapacity { size: self.size, capacity: self.capacity });
+        }
+        // see the capacity series in make_space
+        let expected = if self.capacity == 1 { 0 } else { 2 * self.capacity - 3 };
+        if self.link_lengths.len() != expected {
+            return Err(LinkLengthsLength { expected, actual: self.link_lengths.len() });
+        }
+        if self.sublists.len() != self.size - 1 {
+            return Err(SublistsLength { expected: self.size - 1, actual: self.sublists.len() });
+        }
+
+        for index in 0..self.capacity - 1 {
+            let link_length = self[(index, 0)];
+            if index < self.size - 1 {
+                if link_length <= zero() {
+                    return Err(NonPositiveLink { index });
+                }
+            } else if link_length != zero() {
+                return Err(LinkAfterLastNode { index });
+            }
+        }
+
+        for degree in 1..self.depth() {
+            for index in (0..self.capacity - 1).step_by(1 << degree) {
+                let sum = self[(index, degree - 1)] + self[(index + (1 << (degree - 1)), degree - 1)];
+                if self[(index, degree)] != sum {
+                    return Err(LinkSum { index, degree });
+                }
+            }
+        }
+
+        let depth = self.depth();
+        let top_link_length = if depth == 0 { zero() } else { self[(0, depth - 1)] };
+        if self.length != top_link_length {
+            return Err(Length);
+        }
+
+        for (index, sublist) in self.sublists.iter().enumerate() {
+            if let Some(sublist) = sublist {
+                if sublist.length >= self[(index, 0)] {
+                    return Err(SublistTooLong { index });
+                }
+                sublist.validate_links()
+                    .map_err(|violation| InSublist { index, violation: Box::new(violation) })?;
+            }
+        }
+
+        if self.link_node_counts.len() != self.link_lengths.len() {
+            return Err(LinkLengthsLength {
+                expected: self.link_lengths.len(),
+                actual: self.link_node_counts.len(),
+            });
+        }
+        for index in 0..self.capacity - 1 {
+            let expected = if index < self.size - 1 {
+                1 + self.sublists[index].as_ref().map_or(0, |sublist| sublist.node_count)
+            } else {
+                0
+            };
+            if self.link_node_counts[self.link_index(index, 0)] != expected {
+                return Err(LinkNodeCount { index, degree: 0 });
+            }
+        }
+        for degree in 1..depth {
+            for index in (0..self.capacity - 1).step_by(1 << degree) {
+                let sum = self.link_node_counts[self.link_index(index, degree - 1)]
+                    + self.link_node_counts[self.link_index(index + (1 << (degree - 1)), degree - 1)];
+                if self.link_node_counts[self.link_index(index, degree)] != sum {
+                    return Err(LinkNodeCount { index, degree });
+                }
+            }
+        }
+        let top_link_node_count =
+            if depth == 0 { 0 } else { self.link_node_counts[self.link_index(0, depth - 1)] };
+        if self.node_count != top_link_node_count {
+            return Err(NodeCount);
+        }
+
+        if self.values.len() != self.size - 1 {
+            return Err(ValuesLength { expected: self.size - 1, actual: self.values.len() });
+        }
+        if self.link_aggregates.len() != self.link_lengths.len() {
+            return Err(LinkLengthsLength {
+                expected: self.link_lengths.len(),
+                actual: self.link_aggregates.len(),
+            });
+        }
+        for index in 0..self.capacity - 1 {
+            if self.link_aggregates[self.link_index(index, 0)] != self.zero_degree_aggregate(index) {
+                return Err(LinkAggregate { index, degree: 0 });
+            }
+        }
+        if self.link_tags.len() != self.link_lengths.len() {
+            return Err(LinkLengthsLength {
+                expected: self.link_lengths.len(),
+                actual: self.link_tags.len(),
+            });
+        }
+        for degree in 1..depth {
+            for index in (0..self.capacity - 1).step_by(1 << degree) {
+                let link_index = self.link_index(index, degree);
+                let [before, after] = child_link_indices(link_index);
+                let combined = self.link_aggregates[before].combine(self.link_aggregates[after]);
+                // the tag of the link is included in its aggregate, but not in those below it
+                let expected = self.link_tags[link_index]
+                    .apply_to_aggregate(combined, self.link_node_counts[link_index]);
+                if self.link_aggregates[link_index] != expected {
+                    return Err(LinkAggregate { index, degree });
+                }
+            }
+        }
+
+        if self.ids.len() != self.size - 1 {
+            return Err(IdsLength { expected: self.size - 1, actual: self.ids.len() });
+        }
+        Ok(())
+    }
+
+    /// Panics if this list is not valid, but only in debug builds with the `validate-mutations`
+    /// feature enabled. Called at the end of every mutating method. The handle locations are only
+    /// checked once they are back in the root list, as they are taken out of it while it is being
+    /// mutated.
+    fn validate_after_mutation(&self) {
+        #[cfg(all(feature = "validate-mutations", debug_assertions))]
+        {
+            let result = self.validate_links().and_then(|()| match self.handles {
+                Some(_) => self.validate_handles(),
+                None => Ok(()),
+            });
+            if let Err(violation) = result {
+                panic!("invariant violated after mutation: {}", violation);
+            }
+        }
+    }
+
+    /// Returns the number of the next handle that the root list hands out.
+    fn next_id(&self) -> u64 {
+        self.handles.as_ref().map_or(Handles::default().next_id(), |handles| handles.next_id())
     }
 }
 // endregion
@@ -612,7 +2629,7 @@ fn id_letter(id: usize) -> char {
     ID_LETTERS.chars().cycle().nth(id).unwrap()
 }
 
-impl<S: Spacing> Debug for SpacedList<S>
+impl<P: Position<S>, A: Aggregate, S: Spacing> Debug for SpacedList<P, A, S>
     where S: TryInto<usize> + Debug,
           <S as TryInto<usize>>::Error: Debug {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -662,3 +2679,6 @@ impl<S: Spacing> Debug for SpacedList<S>
 
 #[cfg(test)]
 mod tests;
+
+#[cfg(test)]
+mod benches;
@@ -0,0 +1,110 @@
+//! Line starts of a text, for converting between byte offsets and lines and columns.
+
+use core::ops::Range;
+use crate::{SpacedList, SpacedListBackend};
+
+/// Returns the offsets of the lines started by the line feeds in `text`, if it starts at `offset`.
+fn line_starts_in(text: &str, offset: usize) -> impl Iterator<Item=usize> + '_ {
+    text.bytes().enumerate()
+        .filter(|&(_, byte)| byte == b'\n')
+        .map(move |(index, _)| offset + index + 1)
+}
+
+/// The line starts of a text, as a [SpacedList] with one node per line start, which maps byte
+/// offsets to zero-based lines and columns (in bytes) and back.
+///
+/// [Self::apply_edit] keeps the index up to date with edits to the text. It moves the lines after
+/// the edit instead of rebuilding the index.
+pub struct LineIndex {
+    line_starts: SpacedList<usize>,
+    text_len: usize,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let mut line_starts = SpacedList::with_capacity(line_starts_in(text, 0).count() + 1);
+        for line_start in line_starts_in(text, 0) {
+            line_starts.insert(line_start)
+        }
+        Self { line_starts, text_len: text.len() }
+    }
+
+    /// Returns the length of the text in bytes.
+    pub fn text_len(&self) -> usize {
+        self.text_len
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.node_count() + 1
+    }
+
+    /// Returns the line and column of `offset`, or None if it is beyond the end of the text.
+    pub fn offset_to_line_col(&self, offset: usize) -> Option<(usize, usize)> {
+        if offset > self.text_len {
+            return None;
+        }
+        let (line_start, line) = self.line_starts.rank_at_or_before(offset)?;
+        Some((line, offset - line_start))
+    }
+
+    /// Returns the offset of `col` in `line`, or None if there is no such line or the line is not
+    /// that long. The line feed ending a line counts as its last column.
+    pub fn line_col_to_offset(&self, line: usize, col: usize) -> Option<usize> {
+        let line_start = self.line_starts.position_of_rank(line)?;
+        let line_end = match self.line_starts.position_of_rank(line + 1) {
+            Some(next_line_start) => next_line_start - 1,
+            None => self.text_len,
+        };
+        (col <= line_end - line_start).then_some(line_start + col)
+    }
+
+    /// Updates the index for replacing the bytes in `range` with `new_text`.
+    ///
+    /// The line starts within the replaced bytes are moved to the ones in `new_text` as far as they
+    /// go, and only the rest are removed or inserted. The line starts after the edit are moved by a
+    /// single shift. Removing a line start takes O(n - i) for the i-th of n line starts, as
+    /// [SpacedList::remove] splices the links after it, and everything else takes O(log n).
+    pub fn apply_edit(&mut self, range: Range<usize>, new_text: &str) {
+        assert!(range.start <= range.end && range.end <= self.text_len);
+        let (_, rank) = self.line_starts.rank_at_or_before(range.start).unwrap();
+        let (_, end_rank) = self.line_starts.rank_at_or_before(range.end).unwrap();
+        let next_line_start = self.line_starts.position_after(range.end);
+        let removed = end_rank - rank;
+        let mut inserted = line_starts_in(new_text, range.start);
+
+        let mut kept = 0;
+        while kept < removed {
+            let Some(line_start) = inserted.next() else {
+                break;
+            };
+            kept += 1;
+            let from = self.line_starts.position_of_rank(rank + kept).unwrap();
+            self.move_line_starts(from, line_start);
+        }
+        for _ in kept..removed {
+            let line_start = self.line_starts.position_of_rank(rank + kept + 1).unwrap();
+            self.line_starts.remove(line_start);
+        }
+        if let Some(next_line_start) = next_line_start {
+            let from = self.line_starts.position_of_rank(rank + kept + 1).unwrap();
+            self.move_line_starts(from, next_line_start + new_text.len() - range.len());
+        }
+        for line_start in inserted {
+            self.line_starts.insert(line_start)
+        }
+        self.text_len = self.text_len + new_text.len() - range.len();
+    }
+
+    /// Moves the line start at `from`, and all after it, so that it ends up at `to`.
+    fn move_line_starts(&mut self, from: usize, to: usize) {
+        if to > from {
+            self.line_starts.shift_forward(from, to - from)
+        } else if to < from {
+            self.line_starts.shift_backward(from, from - to)
+        }
+    }
+
+    pub fn line_starts(&self) -> &SpacedList<usize> {
+        &self.line_starts
+    }
+}
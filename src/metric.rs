@@ -0,0 +1,111 @@
+//! Spacings that measure each distance in several metrics at once, such as the bytes and the chars
+//! of a piece of text, and the [Metric]s to query them by.
+
+use core::ops::{Add, AddAssign, Sub};
+use num_traits::CheckedAdd;
+use crate::Spacing;
+
+/// One way of measuring a spacing `S`, for the `_by` queries of
+/// [SpacedList](crate::SpacedList), which descend by this metric alone.
+///
+/// The measure of a sum must be the sum of the measures, and the positions of the nodes of a list
+/// must be non-decreasing in every metric it is queried by. Several nodes can have the same
+/// measure, like [hidden](crate::SpacedList::hide) nodes and the nodes before them do in visible
+/// coordinates. Of those, [position_before_by](crate::SpacedList::position_before_by) and
+/// [position_at_or_before_by](crate::SpacedList::position_at_or_before_by) find the last one, and
+/// the other `_by` queries the first one. The positions themselves, compared as a whole, must still
+/// be strictly increasing.
+pub trait Metric<S> {
+    type Value: Spacing;
+
+    fn measure(spacing: S) -> Self::Value;
+}
+
+/// The metric that measures a spacing as a whole, which the queries without `_by` use.
+pub struct Whole;
+
+impl<S: Spacing> Metric<S> for Whole {
+    type Value = S;
+
+    fn measure(spacing: S) -> S {
+        spacing
+    }
+}
+
+/// A spacing of several metrics, stored together in one tuple per link. Adds and subtracts
+/// component-wise, and is ordered by its first component, then by the next.
+///
+/// ```
+/// # use spaced_list_3::{Component, Metrics, SpacedList, SpacedListBackend};
+/// type Bytes = Component<0>;
+/// type Chars = Component<1>;
+/// let mut list = SpacedList::<Metrics<(usize, usize)>>::default();
+/// list.insert(Metrics((3, 1)));
+/// assert_eq!(list.position_at_or_before_by::<Chars>(1), Some(Metrics((3, 1))));
+/// assert_eq!(list.position_at_or_before_by::<Bytes>(2), Some(Metrics((0, 0))));
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Metrics<T>(pub T);
+
+/// The metric that measures a [Metrics] by its `N`th component.
+pub struct Component<const N: usize>;
+
+macro_rules! metrics {
+    ($($component: ident $index: tt),+) => {
+        impl<$($component: Spacing),+> Add for Metrics<($($component,)+)> {
+            type Output = Self;
+
+            fn add(self, other: Self) -> Self {
+                Metrics(($(self.0.$index + other.0.$index,)+))
+            }
+        }
+
+        impl<$($component: Spacing),+> AddAssign for Metrics<($($component,)+)> {
+            fn add_assign(&mut self, other: Self) {
+                $(self.0.$index += other.0.$index;)+
+            }
+        }
+
+        impl<$($component: Spacing),+> Sub for Metrics<($($component,)+)> {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                Metrics(($(self.0.$index - other.0.$index,)+))
+            }
+        }
+
+        impl<$($component: Spacing),+> Spacing for Metrics<($($component,)+)> {
+            fn zero() -> Self {
+                Metrics(($($component::zero(),)+))
+            }
+
+            fn is_zero(&self) -> bool {
+                $(self.0.$index.is_zero())&&+
+            }
+        }
+
+        impl<$($component: Spacing + CheckedAdd),+> CheckedAdd for Metrics<($($component,)+)> {
+            fn checked_add(&self, other: &Self) -> Option<Self> {
+                Some(Metrics(($(self.0.$index.checked_add(&other.0.$index)?,)+)))
+            }
+        }
+
+        metrics!(@components ($($component),+) $($component $index),+);
+    };
+    (@components $all: tt $($component: ident $index: tt),+) => {
+        $(metrics!(@component $all $component $index);)+
+    };
+    (@component ($($all: ident),+) $component: ident $index: tt) => {
+        impl<$($all: Spacing),+> Metric<Metrics<($($all,)+)>> for Component<$index> {
+            type Value = $component;
+
+            fn measure(spacing: Metrics<($($all,)+)>) -> $component {
+                spacing.0.$index
+            }
+        }
+    };
+}
+
+metrics!(A 0, B 1);
+metrics!(A 0, B 1, C 2);
+metrics!(A 0, B 1, C 2, D 3);
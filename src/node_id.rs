@@ -0,0 +1,258 @@
+//! Stable handles to the nodes of a [SpacedList], which keep referring to the same node through
+//! all mutations of the list, until that node is removed.
+//!
+//! The root list maps every handle to the key of the sublist its node is in and the index of the
+//! node there, and every sublist key to the key of its parent and the gap of the parent it is in.
+//! The position of a handle is found by following these keys up to the root, which gives the path
+//! of gaps from the root down to the node, and adding the position of each gap on the way down.
+//! Shifts keep this up-to-date for free, and only the nodes and sublists that move to other indices
+//! need to be mapped anew.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use core::num::NonZeroU64;
+use crate::{Aggregate, InvariantViolation, Position, SpacedList, Spacing, zero};
+
+/// A handle to a node of a [SpacedList], as returned by [SpacedList::node_id].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct NodeId(NonZeroU64);
+
+/// Identifies a sublist among the sublists of its root list, which, unlike its address, stays the
+/// same when it moves.
+pub(crate) type ListKey = u64;
+
+/// The key of the root list.
+pub(crate) const ROOT: ListKey = 0;
+
+/// Where a node or a sublist is: the key of the list it is in, and the index of the zero-degree
+/// link that ends at the node, or of the gap the sublist is in.
+type Location = (ListKey, usize);
+
+/// The handles of a root list and its sublists, and where they and the sublists are, which only the
+/// root list keeps.
+pub(crate) struct Handles {
+    id_locations: BTreeMap<NodeId, Location>,
+    sublist_locations: BTreeMap<ListKey, Location>,
+    /// The number of the next handle to hand out.
+    next_id: u64,
+    next_key: ListKey,
+}
+
+impl Default for Handles {
+    fn default() -> Self {
+        Self {
+            id_locations: BTreeMap::new(),
+            sublist_locations: BTreeMap::new(),
+            next_id: 1,
+            next_key: ROOT + 1,
+        }
+    }
+}
+
+impl Handles {
+    /// Returns the number of the next handle to hand out.
+    pub(crate) fn next_id(&self) -> u64 {
+        self.next_id
+    }
+
+    /// Returns a new key for a sublist at `location`.
+    pub(crate) fn new_key(&mut self, location: Location) -> ListKey {
+        let key = self.next_key;
+        self.next_key += 1;
+        self.sublist_locations.insert(key, location);
+        key
+    }
+
+    /// Forgets the handle `id`, whose node has been removed.
+    pub(crate) fn forget_id(&mut self, id: NodeId) {
+        self.id_locations.remove(&id);
+    }
+
+    /// Forgets the sublist with `key`, which has been dropped.
+    pub(crate) fn forget_sublist(&mut self, key: ListKey) {
+        self.sublist_locations.remove(&key);
+    }
+
+    /// Maps the handle `id` to `location`.
+    pub(crate) fn move_id(&mut self, id: NodeId, location: Location) {
+        self.id_locations.insert(id, location);
+    }
+
+    /// Maps the sublist with `key` to `location`.
+    pub(crate) fn move_sublist(&mut self, key: ListKey, location: Location) {
+        self.sublist_locations.insert(key, location);
+    }
+
+    /// Forgets all handles and sublists, but not which numbers and keys have been handed out.
+    pub(crate) fn clear(&mut self) {
+        self.id_locations.clear();
+        self.sublist_locations.clear();
+    }
+}
+
+// region spaced list node ids
+impl<P: Position<S>, A: Aggregate, S: Spacing> SpacedList<P, A, S> {
+    /// Returns the handle of the node at `position`, or None if there is no node there or it is
+    /// node zero. The handle stays valid until the node is removed, and the same node always has
+    /// the same handle.
+    pub fn node_id(&mut self, position: P) -> Option<NodeId> {
+        let mut handles = self.take_handles();
+        let new_id = NodeId(NonZeroU64::new(handles.next_id).unwrap());
+        let id = self.register_id(self.relative_or_zero(position), new_id, &mut handles);
+        if id == Some(new_id) {
+            handles.next_id += 1;
+        }
+        self.handles = Some(handles);
+        id
+    }
+
+    /// Gives the node at `position` the handle `new_id`, unless it already has one, and returns its
+    /// handle. Only for the root list, as it keeps the locations of the handles.
+    pub(crate) fn assign_id(&mut self, position: P, new_id: NodeId) -> Option<NodeId> {
+        let mut handles = self.take_handles();
+        let id = self.register_id(self.relative_or_zero(position), new_id, &mut handles);
+        self.handles = Some(handles);
+        id
+    }
+
+    /// Takes the handles out of this list, which must be the root list, so that they can be kept
+    /// up-to-date while its sublists are mutated. They must be put back afterwards.
+    pub(crate) fn take_handles(&mut self) -> Box<Handles> {
+        debug_assert_eq!(self.key, ROOT, "only the root list has handles");
+        self.handles.take().unwrap_or_default()
+    }
+
+    /// Returns the handle of the node at `target_position`, or None if there is no node there or
+    /// it has none yet, without giving it one.
+    pub(crate) fn id_at(&self, target_position: P) -> Option<NodeId> {
+        self.id_at_relative(self.relative_or_zero(target_position))
+    }
+
+    /// Like [Self::id_at], with `target_position` relative to node zero.
+    fn id_at_relative(&self, target_position: S) -> Option<NodeId> {
+        if target_position <= zero() || target_position > self.length {
+            return None;
+        }
+        let (position, index) = self.descend::<true>(target_position);
+        if position != target_position {
+            let sublist = self.get_not_empty_sublist_at_index(index)?;
+            return sublist.id_at_relative(target_position - position);
+        }
+        // index is not zero, as target_position is positive
+        self.ids[index - 1]
+    }
+
+    /// Gives the node at `target_position` relative to node zero the handle `new_id`, unless it
+    /// already has one, and returns its handle.
+    fn register_id(&mut self, target_position: S, new_id: NodeId,
+                              handles: &mut Handles) -> Option<NodeId> {
+        if target_position <= zero() || target_position > self.length {
+            return None;
+        }
+        let (position, index) = self.descend::<true>(target_position);
+        if position != target_position {
+            let sublist = self.get_not_empty_sublist_at_index_mut(index)?;
+            return sublist.register_id(target_position - position, new_id, handles);
+        }
+        // index is not zero, as target_position is positive
+        let id = *self.ids[index - 1].get_or_insert(new_id);
+        handles.move_id(id, (self.key, index - 1));
+        Some(id)
+    }
+
+    /// Returns the current position of the node with `id`, or None if it has been removed.
+    ///
+    /// Takes O(log n) per level of sublists.
+    pub fn position_of(&self, id: NodeId) -> Option<P> {
+        let handles = self.handles.as_ref()?;
+        let &(mut key, index) = handles.id_locations.get(&id)?;
+        // the gaps that lead from the root list down to the list of the node, innermost first
+        let mut path = vec![];
+        while key != ROOT {
+            let (parent, gap) = handles.sublist_locations[&key];
+            path.push(gap);
+            key = parent;
+        }
+        let mut list = self;
+        let mut position: S = zero();
+        for &gap in path.iter().rev() {
+            position += list.node_position(gap);
+            list = list.sublists[gap].as_ref().unwrap();
+        }
+        Some(self.absolute(position + list.node_position(index + 1)))
+    }
+
+    /// Returns the position of the node at `index` in this list relative to its node zero.
+    fn node_position(&self, index: usize) -> S {
+        let mut position = zero();
+        let mut node_index = 0;
+        for degree in (0..self.depth()).rev() {
+            if node_index + (1 << degree) <= index {
+                position += self[(node_index, degree)];
+                node_index += 1 << degree;
+            }
+        }
+        position
+    }
+
+    /// Maps the handles and the sublists of the nodes of this list from the one after node `first`
+    /// on to where they are anew, after these nodes have moved to other indices.
+    pub(crate) fn relink(&self, first: usize, handles: &mut Handles) {
+        for (index, id) in self.ids.iter().enumerate().skip(first) {
+            if let Some(id) = *id {
+                handles.move_id(id, (self.key, index));
+            }
+        }
+        for (index, sublist) in self.sublists.iter().enumerate().skip(first) {
+            if let Some(sublist) = sublist {
+                handles.move_sublist(sublist.key, (self.key, index));
+            }
+        }
+    }
+
+    /// Checks that this list, which must be the root list, and its sublists are mapped to where
+    /// they are in its handles, and that the handles map nothing else.
+    pub(crate) fn validate_handles(&self) -> Result<(), InvariantViolation> {
+        let empty = Handles::default();
+        let handles = self.handles.as_deref().unwrap_or(&empty);
+        let (mut id_count, mut sublist_count) = (0, 0);
+        self.validate_locations(handles, &mut id_count, &mut sublist_count)?;
+        if let Some((&id, _)) = handles.id_locations.iter().nth(id_count) {
+            return Err(InvariantViolation::IdLocation { id });
+        }
+        if handles.sublist_locations.len() != sublist_count {
+            return Err(InvariantViolation::SublistLocations {
+                expected: sublist_count,
+                actual: handles.sublist_locations.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that every handle and sublist of this list and its sublists is mapped to where it is
+    /// in `handles` of the root list, and counts them into `id_count` and `sublist_count`.
+    fn validate_locations(&self, handles: &Handles, id_count: &mut usize,
+                          sublist_count: &mut usize) -> Result<(), InvariantViolation> {
+        for (index, id) in self.ids.iter().enumerate() {
+            if let Some(id) = *id {
+                if handles.id_locations.get(&id) != Some(&(self.key, index)) {
+                    return Err(InvariantViolation::IdLocation { id });
+                }
+                *id_count += 1;
+            }
+        }
+        for (index, sublist) in self.sublists.iter().enumerate() {
+            if let Some(sublist) = sublist {
+                if sublist.key == ROOT || sublist.handles.is_some()
+                    || handles.sublist_locations.get(&sublist.key) != Some(&(self.key, index)) {
+                    return Err(InvariantViolation::ParentLink { index });
+                }
+                *sublist_count += 1;
+                sublist.validate_locations(handles, id_count, sublist_count)?;
+            }
+        }
+        Ok(())
+    }
+}
+// endregion
@@ -0,0 +1,852 @@
+//! Overflow-checked variants of the operations of [SpacedList] that can make a spacing, a value or
+//! an aggregate overflow.
+//!
+//! No link is longer than the list it is in, and the length of a list is the position of its last
+//! node, so every sum the list keeps fits into `S` as long as the positions of all nodes do.
+//! Inserting at a position, removing and shifting back never move a node past the last position
+//! already in the list, so only shifting forward and building a list from its gaps can overflow
+//! positions. The same goes for the edits of a [History] or [ChangeSet], and undoing or redoing
+//! edits only brings back positions that were already in the list.
+//!
+//! The aggregates of links do not have such a bound, as the sum of some of the values can be
+//! greater than the sum of all of them if there are negative values. The checked variants of the
+//! operations that change values or aggregates go through the links they would change first,
+//! without changing them, and compute what they would compute with the checked operations of
+//! [CheckedAggregate].
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::fmt::{Display, Formatter};
+use core::ops::{Add, Range};
+use num_traits::{CheckedAdd, Zero};
+use crate::{Aggregate, ChangeSet, Count, default, Delta, Edit, Entry, History, Max, Min,
+            OccupiedEntry, RangeUpdate, SpacedList, Spacing, Sum, Update, VacantEntry, zero};
+
+// region overflow error
+/// The error returned by the checked operations if a position would not fit into the spacing
+/// type, or a value or aggregate would not fit into its type. The list is left unchanged.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Overflow;
+
+impl Display for Overflow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "a position, value or aggregate of the spaced list overflowed")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Overflow {}
+// endregion
+
+// region spaced list checked operations
+impl<S: Spacing + CheckedAdd, A: Aggregate> SpacedList<S, A> {
+    /// Like [Self::shift_forward], but returns [Overflow] instead if the last node would move past
+    /// the greatest value of `S`.
+    pub fn checked_shift_forward(&mut self, position: S, distance: S) -> Result<(), Overflow> {
+        self.check_shift_forward(position, distance)?;
+        self.shift_forward(position, distance);
+        Ok(())
+    }
+
+    /// Returns [Overflow] if shifting forward from `position` by `distance` would move the last
+    /// node past the greatest value of `S`.
+    fn check_shift_forward(&self, position: S, distance: S) -> Result<(), Overflow> {
+        let last = self.absolute(self.length);
+        if position <= last {
+            last.checked_add(&distance).ok_or(Overflow)?;
+        }
+        Ok(())
+    }
+
+    /// Like [Self::shift], but returns [Overflow] instead if the last node would move past the
+    /// greatest value of `S`. Shifting back cannot overflow.
+    pub fn checked_shift<D: Delta<S>>(&mut self, position: S, delta: D) -> Result<(), Overflow> {
+        if let Some((distance, false)) = delta.distance() {
+            self.check_shift_forward(position, distance)?;
+        }
+        self.shift(position, delta);
+        Ok(())
+    }
+
+    /// Like [Self::from_gaps], but returns [Overflow] instead if the gaps add up to more than the
+    /// greatest value of `S`.
+    pub fn checked_from_gaps(gaps: &[S]) -> Result<Self, Overflow> {
+        gaps.iter().try_fold(zero::<S>(), |length, gap| length.checked_add(gap)).ok_or(Overflow)?;
+        Ok(Self::from_gaps(gaps))
+    }
+
+    /// Like [Self::from_positions]. Unlike gaps, positions that fit into `S` cannot add up to more
+    /// than that, as no link is longer than the last position, so this never returns [Overflow].
+    /// It is there so that building a list from either goes through a checked operation.
+    pub fn checked_from_positions(positions: &[S]) -> Result<Self, Overflow> {
+        Ok(Self::from_positions(positions))
+    }
+}
+
+impl<S: Spacing + CheckedAdd> Edit<S> {
+    /// Returns [Overflow] if applying this edit to `list` would move a node past the greatest
+    /// value of `S`, which only a shift forward can, or make an aggregate overflow, which only an
+    /// insertion or a removal can.
+    fn check_overflow<A: CheckedAggregate>(self, list: &SpacedList<S, A>) -> Result<(), Overflow> {
+        match self {
+            Edit::Insert(position) => list.check_change(&Change::Insert(position, default())),
+            Edit::Remove(position) => list.check_removal(position),
+            Edit::ShiftForward { position, distance } =>
+                list.check_shift_forward(position, distance),
+            Edit::ShiftBackward { .. } => Ok(()),
+        }
+    }
+}
+
+impl<S: Spacing + CheckedAdd, A: CheckedAggregate> History<S, A> {
+    /// Like [Self::apply], but returns [Overflow] and records nothing instead if a node would move
+    /// past the greatest value of `S` or an aggregate would overflow.
+    pub fn checked_apply(&mut self, edit: Edit<S>) -> Result<(), Overflow> {
+        edit.check_overflow(self.list())?;
+        self.apply(edit);
+        Ok(())
+    }
+
+    /// Like [Self::shift_forward], but returns [Overflow] and records nothing instead if the last
+    /// node would move past the greatest value of `S`.
+    pub fn checked_shift_forward(&mut self, position: S, distance: S) -> Result<(), Overflow> {
+        self.list().check_shift_forward(position, distance)?;
+        self.shift_forward(position, distance);
+        Ok(())
+    }
+}
+
+impl<S: Spacing, A: CheckedAggregate> History<S, A> {
+    /// Like [Self::insert], but returns [Overflow] and records nothing instead if an aggregate
+    /// would overflow.
+    pub fn checked_insert(&mut self, position: S) -> Result<(), Overflow> {
+        self.list().check_change(&Change::Insert(position, default()))?;
+        self.insert(position);
+        Ok(())
+    }
+
+    /// Like [Self::remove], but returns [Overflow] and records nothing instead if an aggregate
+    /// would overflow.
+    pub fn checked_remove(&mut self, position: S) -> Result<bool, Overflow> {
+        self.list().check_removal(position)?;
+        Ok(self.remove(position))
+    }
+
+    /// Like [Self::shift_backward]. Shifting back neither moves a node past the last position nor
+    /// changes an aggregate, so this never returns [Overflow]. It is there so that every edit of a
+    /// history can be made through a checked operation.
+    pub fn checked_shift_backward(&mut self, position: S, distance: S) -> Result<(), Overflow> {
+        self.shift_backward(position, distance);
+        Ok(())
+    }
+}
+
+impl<S: Spacing + CheckedAdd> ChangeSet<S> {
+    /// Like [Self::apply], but returns [Overflow] and records nothing instead if a node would move
+    /// past the greatest value of `S` or an aggregate would overflow.
+    pub fn checked_apply<A: CheckedAggregate>(&mut self, list: &mut SpacedList<S, A>, edit: Edit<S>)
+                                       -> Result<(), Overflow> {
+        edit.check_overflow(list)?;
+        self.apply(list, edit);
+        Ok(())
+    }
+
+    /// Like [Self::apply_to], but returns [Overflow] instead if an edit would move a node past the
+    /// greatest value of `S` or make an aggregate overflow. The edits applied before that one are
+    /// reverted, and the nodes they removed are inserted again with their values and handles.
+    pub fn checked_apply_to<A: CheckedAggregate>(&self, list: &mut SpacedList<S, A>)
+                                          -> Result<(), Overflow> {
+        // what each edit applied so far removed
+        let mut removed = vec![];
+        for (applied, &edit) in self.edits().iter().enumerate() {
+            if let Err(overflow) = edit.check_overflow(list) {
+                for (&edit, removed) in self.edits()[..applied].iter().zip(removed).rev() {
+                    edit.revert(list, removed);
+                }
+                return Err(overflow);
+            }
+            removed.push(edit.removed(list));
+            edit.apply(list);
+        }
+        Ok(())
+    }
+}
+// endregion
+
+// region checked aggregates
+/// An [Aggregate] whose values, aggregates and updates can overflow, with checked variants of the
+/// operations on them that return None instead.
+pub trait CheckedAggregate: Aggregate {
+    /// Like [Aggregate::combine].
+    fn checked_combine(self, other: Self) -> Option<Self>;
+
+    /// Like [Update::apply].
+    fn checked_apply(update: Self::Update, value: Self::Value) -> Option<Self::Value>;
+
+    /// Like [Update::apply_to_aggregate].
+    fn checked_apply_to_aggregate(update: Self::Update, aggregate: Self, count: usize)
+                                  -> Option<Self>;
+
+    /// Like [Update::compose].
+    fn checked_compose(update: Self::Update, later: Self::Update) -> Option<Self::Update>;
+}
+
+impl CheckedAggregate for () {
+    fn checked_combine(self, _: Self) -> Option<Self> {
+        Some(())
+    }
+
+    fn checked_apply(_: (), _: ()) -> Option<()> {
+        Some(())
+    }
+
+    fn checked_apply_to_aggregate(_: (), _: Self, _: usize) -> Option<Self> {
+        Some(())
+    }
+
+    fn checked_compose(_: (), _: ()) -> Option<()> {
+        Some(())
+    }
+}
+
+impl CheckedAggregate for Count {
+    fn checked_combine(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Count)
+    }
+
+    fn checked_apply(_: (), _: ()) -> Option<()> {
+        Some(())
+    }
+
+    fn checked_apply_to_aggregate(_: (), aggregate: Self, _: usize) -> Option<Self> {
+        Some(aggregate)
+    }
+
+    fn checked_compose(_: (), _: ()) -> Option<()> {
+        Some(())
+    }
+}
+
+impl<T> CheckedAggregate for Sum<T>
+    where T: Copy + PartialEq + Default + Add<Output=T> + Zero + CheckedAdd {
+    fn checked_combine(self, other: Self) -> Option<Self> {
+        self.0.checked_add(&other.0).map(Sum)
+    }
+
+    fn checked_apply(update: RangeUpdate<T>, value: T) -> Option<T> {
+        update.checked_apply(value)
+    }
+
+    fn checked_apply_to_aggregate(update: RangeUpdate<T>, Sum(sum): Self, count: usize)
+                                  -> Option<Self> {
+        match update {
+            RangeUpdate::Add(addend) => sum.checked_add(&checked_times(addend, count)?).map(Sum),
+            RangeUpdate::Set(value) => checked_times(value, count).map(Sum),
+        }
+    }
+
+    fn checked_compose(update: RangeUpdate<T>, later: RangeUpdate<T>) -> Option<RangeUpdate<T>> {
+        update.checked_compose(later)
+    }
+}
+
+impl<T> CheckedAggregate for Min<T>
+    where T: Copy + Ord + Default + Add<Output=T> + Zero + CheckedAdd {
+    fn checked_combine(self, other: Self) -> Option<Self> {
+        Some(self.combine(other))
+    }
+
+    fn checked_apply(update: RangeUpdate<T>, value: T) -> Option<T> {
+        update.checked_apply(value)
+    }
+
+    fn checked_apply_to_aggregate(update: RangeUpdate<T>, Min(min): Self, _: usize)
+                                  -> Option<Self> {
+        match min {
+            Some(min) => update.checked_apply(min).map(|min| Min(Some(min))),
+            None => Some(Min(None)),
+        }
+    }
+
+    fn checked_compose(update: RangeUpdate<T>, later: RangeUpdate<T>) -> Option<RangeUpdate<T>> {
+        update.checked_compose(later)
+    }
+}
+
+impl<T> CheckedAggregate for Max<T>
+    where T: Copy + Ord + Default + Add<Output=T> + Zero + CheckedAdd {
+    fn checked_combine(self, other: Self) -> Option<Self> {
+        Some(self.combine(other))
+    }
+
+    fn checked_apply(update: RangeUpdate<T>, value: T) -> Option<T> {
+        update.checked_apply(value)
+    }
+
+    fn checked_apply_to_aggregate(update: RangeUpdate<T>, Max(max): Self, _: usize)
+                                  -> Option<Self> {
+        match max {
+            Some(max) => update.checked_apply(max).map(|max| Max(Some(max))),
+            None => Some(Max(None)),
+        }
+    }
+
+    fn checked_compose(update: RangeUpdate<T>, later: RangeUpdate<T>) -> Option<RangeUpdate<T>> {
+        update.checked_compose(later)
+    }
+}
+
+impl<T: Copy + CheckedAdd> RangeUpdate<T> {
+    fn checked_apply(self, value: T) -> Option<T> {
+        match self {
+            RangeUpdate::Add(addend) => value.checked_add(&addend),
+            RangeUpdate::Set(new_value) => Some(new_value),
+        }
+    }
+
+    fn checked_compose(self, later: Self) -> Option<Self> {
+        match (self, later) {
+            (_, RangeUpdate::Set(value)) => Some(RangeUpdate::Set(value)),
+            (RangeUpdate::Add(first), RangeUpdate::Add(second)) =>
+                first.checked_add(&second).map(RangeUpdate::Add),
+            (RangeUpdate::Set(first), RangeUpdate::Add(second)) =>
+                first.checked_add(&second).map(RangeUpdate::Set),
+        }
+    }
+}
+
+/// Like `times` of the aggregates, `value` added up `count` times, or None if that overflows. No
+/// intermediate sum is greater in magnitude than the result, so it only overflows if the result
+/// does.
+fn checked_times<T: Copy + Zero + CheckedAdd>(mut value: T, mut count: usize) -> Option<T> {
+    let mut result = T::zero();
+    loop {
+        if count & 1 == 1 {
+            result = result.checked_add(&value)?;
+        }
+        count >>= 1;
+        if count == 0 {
+            return Some(result);
+        }
+        value = value.checked_add(&value)?;
+    }
+}
+// endregion
+
+// region spaced list checked aggregates
+/// A change of values that a checked operation goes through the links for before making it.
+enum Change<S, A: Aggregate> {
+    Update(Range<S>, A::Update),
+    SetValue(S, A::Value),
+    Insert(S, A::Value),
+    /// Appends a node with the value after the last node of the list, whose zero-degree link is
+    /// the one after node `usize`.
+    Append(usize, A::Value),
+}
+
+impl<S: Spacing + CheckedAdd, A: CheckedAggregate> SpacedList<S, A> {
+    /// Appends a node with `value` `distance` after the last node, like inserting it there, but
+    /// returns [Overflow] instead if its position would be past the greatest value of `S` or an
+    /// aggregate would overflow. `distance` must be positive.
+    pub fn checked_append_node(&mut self, distance: S, value: A::Value) -> Result<(), Overflow> {
+        assert!(distance > zero());
+        let position = self.absolute(self.length).checked_add(&distance).ok_or(Overflow)?;
+        self.checked_insert_with_value(position, value)
+    }
+}
+
+impl<S: Spacing, A: CheckedAggregate> SpacedList<S, A> {
+    /// Like [Self::insert_with_value], but returns [Overflow] instead if an aggregate would
+    /// overflow.
+    pub fn checked_insert_with_value(&mut self, position: S, value: A::Value)
+                                     -> Result<(), Overflow> {
+        self.check_change(&Change::Insert(position, value))?;
+        self.insert_with_value(position, value);
+        Ok(())
+    }
+
+    /// Like [Self::set_value], but returns [Overflow] instead if an aggregate would overflow.
+    pub fn checked_set_value(&mut self, target_position: S, value: A::Value)
+                             -> Result<bool, Overflow> {
+        self.check_change(&Change::SetValue(target_position, value))?;
+        Ok(self.set_value(target_position, value))
+    }
+
+    /// Like [Self::update], but returns [Overflow] instead if a value, an aggregate or the update
+    /// pending on a link would overflow.
+    pub fn checked_update(&mut self, range: Range<S>, update: A::Update) -> Result<(), Overflow> {
+        if range.start < range.end {
+            self.check_change(&Change::Update(range.clone(), update))?;
+        }
+        self.update(range, update);
+        Ok(())
+    }
+
+    /// Like [Self::entry], but returns [Overflow] instead if pushing the pending updates down on
+    /// the way to the node would overflow.
+    pub fn checked_entry(&mut self, position: S) -> Result<Entry<'_, S, A>, Overflow> {
+        let mut list = &*self;
+        let mut relative_position = self.relative_or_zero(position);
+        assert!(relative_position > zero());
+        let mut pending = A::Update::identity();
+        while relative_position <= list.length {
+            let (node_position, index) = list.descend::<true>(relative_position);
+            if node_position == relative_position {
+                list.checked_path(index - 1, pending).ok_or(Overflow)?;
+                break;
+            }
+            (_, pending) = list.checked_path(index, pending).ok_or(Overflow)?;
+            let Some(sublist) = list.get_not_empty_sublist_at_index(index) else {
+                break;
+            };
+            relative_position = relative_position - node_position;
+            list = sublist;
+        }
+        Ok(self.entry(position))
+    }
+
+    /// Returns [Overflow] if `change`, at positions of this list rather than relative to its node
+    /// zero, would make an aggregate overflow.
+    fn check_change(&self, change: &Change<S, A>) -> Result<(), Overflow> {
+        let change = match *change {
+            Change::Update(ref range, update) =>
+                Change::Update(self.relative_range(range.clone()), update),
+            Change::SetValue(position, value) =>
+                Change::SetValue(self.relative_or_zero(position), value),
+            Change::Insert(position, value) =>
+                Change::Insert(self.relative_or_zero(position), value),
+            Change::Append(index, value) => Change::Append(index, value),
+        };
+        self.checked_list(&change, A::Update::identity()).map(drop).ok_or(Overflow)
+    }
+
+    /// Returns the aggregate of all nodes of this list after `change` and the `pending` update from
+    /// the links above it, or None if computing it like the change does would overflow.
+    fn checked_list(&self, change: &Change<S, A>, pending: A::Update) -> Option<A> {
+        let change = match *change {
+            Change::Insert(position, value) if position >= self.length =>
+                &Change::Append(self.size - 1, value),
+            _ => change,
+        };
+        let depth = self.depth();
+        match *change {
+            _ if depth == 0 => Some(match *change {
+                Change::Append(_, value) => A::of(value),
+                _ => A::identity(),
+            }),
+            // the list grows, and the links it has now end up before the new node
+            Change::Append(index, value) if index + 1 >= self.capacity =>
+                self.checked_apply_to_link(0, depth - 1, &[pending])?.checked_combine(A::of(value)),
+            _ => self.checked_link(0, depth - 1, zero(), change, pending),
+        }
+    }
+
+    /// Returns the aggregate of the link of degree `degree` after the node at `index`, which is at
+    /// `position`, after `change` and the `pending` update from the links above it, like
+    /// [Self::update_link] and the other changes compute it, or None if that would overflow.
+    fn checked_link(&self, index: usize, degree: usize, position: S, change: &Change<S, A>,
+                    pending: A::Update) -> Option<A> {
+        let current = self.checked_apply_to_link(index, degree, &[pending])?;
+        let link_index = self.link_index(index, degree);
+        let end_position = position + self.link_lengths[link_index];
+        let touched = match *change {
+            Change::Update(ref range, _) => range.start <= end_position && position < range.end,
+            Change::SetValue(target, _) | Change::Insert(target, _) =>
+                position < target && target <= end_position,
+            Change::Append(appended, _) => index <= appended && appended < index + (1 << degree),
+        };
+        if !touched || index + 1 >= self.size && !matches!(change, Change::Append(..)) {
+            return Some(current);
+        }
+        if let Change::Update(ref range, update) = *change {
+            if range.start <= position && end_position < range.end {
+                return self.checked_apply_to_link(index, degree, &[pending, update]);
+            }
+        }
+        if degree > 0 {
+            let pending = A::checked_compose(self.link_tags[link_index], pending)?;
+            let middle = index + (1 << (degree - 1));
+            let middle_position = position + self[(index, degree - 1)];
+            let first = self.checked_link(index, degree - 1, position, change, pending)?;
+            let second = self.checked_link(middle, degree - 1, middle_position, change, pending)?;
+            return first.checked_combine(second);
+        }
+        let in_gap = match *change {
+            Change::Update(ref range, update) => {
+                let start = if range.start > position { range.start - position } else { zero() };
+                Some(Change::Update(start..range.end - position, update))
+            }
+            Change::SetValue(target, value) => Some(Change::SetValue(target - position, value)),
+            Change::Insert(target, value) => Some(Change::Insert(target - position, value)),
+            Change::Append(..) => None,
+        };
+        let in_sublist = match (&self.sublists.get(index), in_gap) {
+            (Some(Some(sublist)), Some(in_gap)) => sublist.checked_list(&in_gap, pending)?,
+            // the sublist is created for the new node
+            (_, Some(Change::Insert(_, value))) => A::of(value),
+            _ => A::identity(),
+        };
+        let value = match *change {
+            Change::Append(_, value) => return in_sublist.checked_combine(A::of(value)),
+            Change::SetValue(target, value) if target == end_position => value,
+            _ => A::checked_apply(pending, self.values[index])?,
+        };
+        let value = match *change {
+            Change::Update(ref range, update) if range.contains(&end_position) =>
+                A::checked_apply(update, value)?,
+            _ => value,
+        };
+        in_sublist.checked_combine(A::of(value))
+    }
+
+    /// Returns the aggregates of the links next to the links above the zero-degree link after node
+    /// `index`, from degree zero up, and the update pending on that link, after pushing the tags
+    /// of the links above it and the `pending` update from the links above this list down like
+    /// [Self::push_down_above] does, or None if that would overflow.
+    fn checked_path(&self, index: usize, mut pending: A::Update) -> Option<(Vec<A>, A::Update)> {
+        let depth = self.depth();
+        let mut siblings = vec![A::identity(); depth.saturating_sub(1)];
+        for degree in (1..depth).rev() {
+            let link_start = index >> degree << degree;
+            self.checked_apply_to_link(link_start, degree, &[pending])?;
+            let tag = self.link_tags[self.link_index(link_start, degree)];
+            pending = A::checked_compose(tag, pending)?;
+            let sibling = (index >> (degree - 1) ^ 1) << (degree - 1);
+            siblings[degree - 1] = self.checked_apply_to_link(sibling, degree - 1, &[pending])?;
+        }
+        self.checked_apply_to_link(index, 0, &[pending])?;
+        Some((siblings, pending))
+    }
+
+    /// Returns the aggregate of all nodes of this list after the aggregate of the zero-degree link
+    /// after node `index` changes to `aggregate`, with the links next to the ones above it as in
+    /// `siblings`, see [Self::checked_path], like [Self::update_aggregates_above] computes it, or
+    /// None if that would overflow.
+    fn checked_up(index: usize, aggregate: A, siblings: &[A]) -> Option<A> {
+        siblings.iter().enumerate().try_fold(aggregate, |aggregate, (degree, &sibling)| {
+            if index >> degree & 1 == 0 {
+                aggregate.checked_combine(sibling)
+            } else {
+                sibling.checked_combine(aggregate)
+            }
+        })
+    }
+
+    /// Returns the aggregate of the link of degree `degree` after the node at `index` after
+    /// applying `updates` one after the other, like [Self::apply_to_link] does, or None if that
+    /// would overflow.
+    fn checked_apply_to_link(&self, index: usize, degree: usize, updates: &[A::Update])
+                             -> Option<A> {
+        let link_index = self.link_index(index, degree);
+        let count = self.link_node_counts[link_index];
+        let mut aggregate = self.link_aggregates[link_index];
+        let mut tag = self.link_tags[link_index];
+        let mut value = self.values.get(index).copied();
+        for &update in updates {
+            aggregate = A::checked_apply_to_aggregate(update, aggregate, count)?;
+            if degree > 0 {
+                tag = A::checked_compose(tag, update)?;
+            } else if let Some(old_value) = value {
+                value = Some(A::checked_apply(update, old_value)?);
+            }
+        }
+        if degree == 0 {
+            if let Some(Some(sublist)) = self.sublists.get(index) {
+                if let depth @ 1.. = sublist.depth() {
+                    sublist.checked_apply_to_link(0, depth - 1, updates)?;
+                }
+            }
+        }
+        Some(aggregate)
+    }
+}
+// endregion
+
+// region spaced list checked removal
+/// What pushing the tags of some links of a list down computes, see
+/// [SpacedList::checked_push_down].
+struct PushedDown<A: Aggregate> {
+    /// The aggregates of the links before the zero-degree link after the node it pushes down from,
+    /// one of each degree whose bit is set in the index of that node, like the bits of a binary
+    /// number add up to it.
+    pieces: Vec<Option<A>>,
+    /// For the zero-degree links from there on, if it pushes down all of them: the value of the
+    /// node each ends at, and the update applied to the sublist in it.
+    gaps: Vec<(A::Value, A::Update)>,
+}
+
+impl<S: Spacing, A: CheckedAggregate> SpacedList<S, A> {
+    /// Like [Self::remove], but returns [Overflow] instead if an aggregate would overflow. Removing
+    /// a node can make an aggregate overflow as well, if the values around it add up to more
+    /// without it.
+    pub fn checked_remove(&mut self, position: S) -> Result<bool, Overflow> {
+        self.check_removal(position)?;
+        Ok(self.remove(position))
+    }
+
+    /// Returns [Overflow] if removing the node at `position` would make an aggregate overflow.
+    fn check_removal(&self, position: S) -> Result<(), Overflow> {
+        self.checked_removal(self.relative_or_zero(position), A::Update::identity()).map(drop)
+    }
+
+    /// Returns the aggregate of all nodes of this list after removing the node at `position`, with
+    /// the `pending` update from the links above it, computed like [Self::remove_with_handles]
+    /// does, or None if there is no node there, or [Overflow] if that would overflow.
+    fn checked_removal(&self, position: S, pending: A::Update) -> Result<Option<A>, Overflow> {
+        if position <= zero() || position > self.length {
+            return Ok(None);
+        }
+        let (node_position, index) = self.descend::<true>(position);
+        if node_position == position {
+            return self.checked_node_removal(index, pending).map(Some).ok_or(Overflow);
+        }
+        let (siblings, pending) = self.checked_path(index, pending).ok_or(Overflow)?;
+        let Some(sublist) = self.get_not_empty_sublist_at_index(index) else {
+            return Ok(None);
+        };
+        let Some(total) = sublist.checked_removal(position - node_position, pending)? else {
+            return Ok(None);
+        };
+        A::checked_apply(pending, self.values[index])
+            .and_then(|value| total.checked_combine(A::of(value)))
+            .and_then(|aggregate| Self::checked_up(index, aggregate, &siblings))
+            .map(Some)
+            .ok_or(Overflow)
+    }
+
+    /// Returns the aggregate of all nodes of this list after removing the node at `index`, with the
+    /// `pending` update from the links above it, computed like [Self::remove_node] does, or None if
+    /// that would overflow.
+    fn checked_node_removal(&self, index: usize, pending: A::Update) -> Option<A> {
+        let gap = index - 1;
+        let pushed = self.checked_push_down(gap, pending, true)?;
+        // the sublist in the gap `offset` after the one that ends at the removed node
+        let sublist = |offset: usize| {
+            let &(_, pending) = pushed.gaps.get(offset)?;
+            Some((self.get_not_empty_sublist_at_index(gap + offset)?, pending))
+        };
+        let size = self.size - 1;
+        if index == size {
+            // the nodes of the sublist before the last node are appended after the others
+            let total = Self::checked_rebuild(gap, size, &[], self.depth(), &pushed.pieces)?;
+            return match sublist(0) {
+                Some((before, pending)) =>
+                    before.checked_appended_to(pending, size, self.capacity, &pushed.pieces),
+                None => Some(total),
+            };
+        }
+        let merged = match (sublist(0), sublist(1)) {
+            (None, None) => None,
+            (Some((sublist, pending)), None) | (None, Some((sublist, pending))) =>
+                Some(sublist.checked_total(pending)?),
+            (Some((before, before_pending)), Some((after, after_pending))) => {
+                let first = before.size - 1;
+                let pieces = before.checked_push_down(first, before_pending, false)?.pieces;
+                Some(after.checked_appended_to(after_pending, before.size, before.capacity,
+                                               &pieces)?)
+            }
+        };
+        let (value, _) = pushed.gaps[1];
+        let mut gaps = vec![match merged {
+            Some(merged) => merged.checked_combine(A::of(value))?,
+            None => A::of(value),
+        }];
+        for (offset, &(value, pending)) in pushed.gaps.iter().enumerate().skip(2) {
+            gaps.push(self.checked_zero_degree(gap + offset, value, pending)?);
+        }
+        Self::checked_rebuild(gap, size, &gaps, self.depth(), &pushed.pieces)
+    }
+
+    /// Returns the aggregate of all nodes of a list of `size` nodes and `capacity` after appending
+    /// the nodes of this list, with the `pending` update from the links above it, like
+    /// [Self::append_nodes_of] does, given the aggregates `pieces` of the links of that list before
+    /// its last node, see [PushedDown], or None if that would overflow.
+    fn checked_appended_to(&self, pending: A::Update, size: usize, capacity: usize,
+                           pieces: &[Option<A>]) -> Option<A> {
+        let gaps = self.checked_push_down(0, pending, true)?.gaps.into_iter().enumerate()
+            .map(|(index, (value, pending))| self.checked_zero_degree(index, value, pending))
+            .collect::<Option<Vec<A>>>()?;
+        let first = size - 1;
+        let capacity = capacity.max(Self::capacity_for(size + gaps.len()));
+        let depth = if capacity > 1 { (capacity - 1).trailing_zeros() as usize + 1 } else { 0 };
+        Self::checked_rebuild(first, first + gaps.len(), &gaps, depth, pieces)
+    }
+
+    /// Returns what pushing the tags of the links over the zero-degree link after node `first`
+    /// down computes, like [Self::push_down_above] does, or of all links from there on, like
+    /// [Self::push_down_from] does, if `all` is true, after the `pending` update from the links
+    /// above this list, or None if that would overflow.
+    fn checked_push_down(&self, first: usize, pending: A::Update, all: bool)
+                         -> Option<PushedDown<A>> {
+        let mut pushed = PushedDown { pieces: vec![None; self.depth()], gaps: vec![] };
+        if let depth @ 1.. = self.depth() {
+            self.checked_push_down_link(0, depth - 1, first, pending, all, &mut pushed)?;
+        }
+        Some(pushed)
+    }
+
+    /// Like [Self::checked_push_down], for the link of degree `degree` after node `index`, which
+    /// `pending` is applied to.
+    fn checked_push_down_link(&self, index: usize, degree: usize, first: usize,
+                              pending: A::Update, all: bool, pushed: &mut PushedDown<A>)
+                              -> Option<()> {
+        let aggregate = self.checked_apply_to_link(index, degree, &[pending])?;
+        let start = first >> degree << degree;
+        if index < start {
+            pushed.pieces[degree] = Some(aggregate);
+        } else if degree == 0 {
+            if all && index + 1 < self.size {
+                pushed.gaps.push((A::checked_apply(pending, self.values[index])?, pending));
+            }
+        } else if all || index == start {
+            let tag = self.link_tags[self.link_index(index, degree)];
+            let pending = A::checked_compose(tag, pending)?;
+            let middle = index + (1 << (degree - 1));
+            self.checked_push_down_link(index, degree - 1, first, pending, all, pushed)?;
+            self.checked_push_down_link(middle, degree - 1, first, pending, all, pushed)?;
+        }
+        Some(())
+    }
+
+    /// Returns the aggregate of all nodes of a list with links of degrees `0..depth` after building
+    /// the links over the zero-degree links after nodes `first..end` anew, like
+    /// [Self::build_links_from] does, where `gaps` are the aggregates of the first of those and
+    /// `pieces` those of the links before them, see [PushedDown], or None if that would overflow.
+    fn checked_rebuild(first: usize, end: usize, gaps: &[A], depth: usize, pieces: &[Option<A>])
+                       -> Option<A> {
+        // the aggregates of the links of one degree from the one over the gap after node first on
+        let mut links: Vec<A> = (first..end)
+            .map(|index| gaps.get(index - first).copied().unwrap_or(A::identity()))
+            .collect();
+        for degree in 1..depth {
+            if first >> (degree - 1) & 1 == 1 {
+                links.insert(0, pieces[degree - 1].expect("the links before are pushed down"));
+            }
+            links = links.chunks(2)
+                .map(|pair| pair[0].checked_combine(pair.get(1).copied().unwrap_or(A::identity())))
+                .collect::<Option<_>>()?;
+        }
+        Some(links.first().copied().unwrap_or(A::identity()))
+    }
+
+    /// Returns the aggregate of all nodes of this list after applying `pending` to it, or None if
+    /// that would overflow.
+    fn checked_total(&self, pending: A::Update) -> Option<A> {
+        match self.depth() {
+            0 => Some(A::identity()),
+            depth => self.checked_apply_to_link(0, depth - 1, &[pending]),
+        }
+    }
+
+    /// Returns the aggregate of the zero-degree link after node `index` if the node it ends at has
+    /// `value` and `pending` is applied to the sublist in it, like [Self::zero_degree_aggregate]
+    /// computes it, or None if that would overflow.
+    fn checked_zero_degree(&self, index: usize, value: A::Value, pending: A::Update) -> Option<A> {
+        match &self.sublists[index] {
+            Some(sublist) => sublist.checked_total(pending)?.checked_combine(A::of(value)),
+            None => Some(A::of(value)),
+        }
+    }
+
+    /// Calls `f` with the list at the end of `path`, a sequence of sublist indices, and returns the
+    /// aggregate of all nodes of this list if that list changes to have the aggregate `f` returns,
+    /// like [Self::at_path] computes it, or None if that would overflow. The tags along `path` must
+    /// have been pushed down.
+    fn checked_at_path(&self, path: &[usize], f: impl FnOnce(&Self) -> Option<A>) -> Option<A> {
+        let Some((&index, rest)) = path.split_first() else {
+            return f(self);
+        };
+        let total = self.sublists[index].as_ref().unwrap().checked_at_path(rest, f)?;
+        let aggregate = total.checked_combine(A::of(self.values[index]))?;
+        self.checked_aggregates_above(index, aggregate)
+    }
+
+    /// Returns the aggregate of all nodes of this list after the aggregate of the zero-degree link
+    /// after node `index` changes to `aggregate`, or None if that would overflow. The tags above it
+    /// must have been pushed down.
+    fn checked_aggregates_above(&self, index: usize, aggregate: A) -> Option<A> {
+        let (siblings, _) = self.checked_path(index, A::Update::identity())?;
+        Self::checked_up(index, aggregate, &siblings)
+    }
+}
+// endregion
+
+// region checked entries
+impl<S: Spacing, A: CheckedAggregate> Entry<'_, S, A> {
+    /// Like [Self::or_insert], but returns [Overflow] instead if an aggregate would overflow.
+    pub fn checked_or_insert(self, value: A::Value) -> Result<A::Value, Overflow> {
+        self.checked_or_insert_with(|| value)
+    }
+
+    /// Like [Self::or_insert_with], but returns [Overflow] instead if an aggregate would overflow.
+    pub fn checked_or_insert_with(self, f: impl FnOnce() -> A::Value)
+                                  -> Result<A::Value, Overflow> {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.get()),
+            Entry::Vacant(entry) => entry.checked_insert(f()),
+        }
+    }
+
+    /// Like [Self::or_default], but returns [Overflow] instead if an aggregate would overflow.
+    pub fn checked_or_default(self) -> Result<A::Value, Overflow> {
+        self.checked_or_insert_with(A::Value::default)
+    }
+
+    /// Like [Self::and_modify], but returns [Overflow] and leaves the value as it was instead if an
+    /// aggregate would overflow.
+    pub fn checked_and_modify(self, f: impl FnOnce(&mut A::Value)) -> Result<Self, Overflow> {
+        match self {
+            Entry::Occupied(mut entry) => {
+                entry.checked_modify(f)?;
+                Ok(Entry::Occupied(entry))
+            }
+            vacant => Ok(vacant),
+        }
+    }
+}
+
+impl<S: Spacing, A: CheckedAggregate> OccupiedEntry<'_, S, A> {
+    /// Like [Self::modify], but returns [Overflow] and leaves the value as it was instead if an
+    /// aggregate would overflow. `f` changes a copy of the value, which replaces the value if it
+    /// fits.
+    pub fn checked_modify(&mut self, f: impl FnOnce(&mut A::Value)) -> Result<(), Overflow> {
+        let mut value = self.get();
+        f(&mut value);
+        let index = self.index - 1;
+        self.list.checked_at_path(&self.path, |list| {
+            let aggregate = list.checked_zero_degree(index, value, A::Update::identity())?;
+            list.checked_aggregates_above(index, aggregate)
+        }).ok_or(Overflow)?;
+        self.modify(|current| *current = value);
+        Ok(())
+    }
+
+    /// Like [Self::insert], but returns [Overflow] and leaves the value as it was instead if an
+    /// aggregate would overflow.
+    pub fn checked_insert(&mut self, value: A::Value) -> Result<A::Value, Overflow> {
+        let previous = self.get();
+        self.checked_modify(|current| *current = value)?;
+        Ok(previous)
+    }
+
+    /// Like [Self::remove], but returns [Overflow] and leaves the node as it was instead if an
+    /// aggregate would overflow.
+    pub fn checked_remove(self) -> Result<A::Value, Overflow> {
+        let value = self.get();
+        self.list.checked_remove(self.position)?;
+        Ok(value)
+    }
+}
+
+impl<S: Spacing, A: CheckedAggregate> VacantEntry<'_, S, A> {
+    /// Like [Self::insert], but returns [Overflow] instead if an aggregate would overflow.
+    pub fn checked_insert(self, value: A::Value) -> Result<A::Value, Overflow> {
+        let change = Change::Insert(self.relative_position, value);
+        self.list
+            .checked_at_path(&self.path, |list| list.checked_list(&change, A::Update::identity()))
+            .ok_or(Overflow)?;
+        Ok(self.insert(value))
+    }
+}
+// endregion
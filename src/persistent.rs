@@ -0,0 +1,477 @@
+//! A persistent variant of [SpacedList](crate::SpacedList): cloning it is O(1), and the clones
+//! share everything but the data that one of them changes afterwards.
+//!
+//! `link_lengths` is not one vector here, but an in-order layout cut into a tree: a
+//! [Branch] holds the link of the highest degree of its part of the list between the two halves
+//! below it, and a [Leaf] holds the links of up to [LEAF_DEPTH] degrees as a plain in-order block,
+//! together with the sublists after its nodes. Parts that contain nothing but zero-length links
+//! are not stored at all. Branches and leaves are shared through [Arc] and copied on write, so a
+//! mutation copies one branch per degree above the leaves, plus one leaf of bounded size.
+
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::default::default;
+use crate::{link_index, LinkIndicesAbove, Spacing, SpacedListBackend, zero};
+
+/// Number of degrees of links stored in one leaf, which therefore holds up to `2^LEAF_DEPTH - 1`
+/// links and `2^(LEAF_DEPTH - 1)` sublists.
+const LEAF_DEPTH: usize = 5;
+
+// region links
+#[derive(Clone)]
+struct Leaf<S: Spacing> {
+    link_lengths: Vec<S>,
+    sublists: Vec<Option<PersistentSpacedList<S>>>,
+}
+
+#[derive(Clone)]
+struct Branch<S: Spacing> {
+    left: Links<S>,
+    top: S,
+    right: Links<S>,
+}
+
+/// The links of degrees `0..depth` after `2^(depth - 1)` consecutive nodes, where `depth` is known
+/// from the context.
+#[derive(Clone)]
+enum Links<S: Spacing> {
+    /// All links are zero and there are no sublists.
+    Empty,
+    /// `depth <= LEAF_DEPTH`
+    Leaf(Arc<Leaf<S>>),
+    /// `depth > LEAF_DEPTH`
+    Branch(Arc<Branch<S>>),
+}
+
+impl<S: Spacing> Links<S> {
+    /// Returns the link of degree `degree` after node `node_index`, relative to the first node of
+    /// these links.
+    fn get(&self, depth: usize, node_index: usize, degree: usize) -> S {
+        match self {
+            Links::Empty => zero(),
+            Links::Leaf(leaf) => leaf.link_lengths[link_index(node_index, degree)],
+            Links::Branch(branch) => {
+                let half = 1 << (depth - 2);
+                if degree == depth - 1 {
+                    branch.top
+                } else if node_index < half {
+                    branch.left.get(depth - 1, node_index, degree)
+                } else {
+                    branch.right.get(depth - 1, node_index - half, degree)
+                }
+            }
+        }
+    }
+
+    fn sublist(&self, depth: usize, node_index: usize) -> Option<&PersistentSpacedList<S>> {
+        match self {
+            Links::Empty => None,
+            Links::Leaf(leaf) => leaf.sublists[node_index].as_ref(),
+            Links::Branch(branch) => {
+                let half = 1 << (depth - 2);
+                if node_index < half {
+                    branch.left.sublist(depth - 1, node_index)
+                } else {
+                    branch.right.sublist(depth - 1, node_index - half)
+                }
+            }
+        }
+    }
+
+    /// Replaces `Empty` by a leaf or branch of zero-length links, so that it can be written to.
+    fn materialize(&mut self, depth: usize) {
+        if let Links::Empty = self {
+            *self = if depth > LEAF_DEPTH {
+                Links::Branch(Arc::new(Branch { left: Links::Empty, top: zero(), right: Links::Empty }))
+            } else {
+                Links::Leaf(Arc::new(Leaf {
+                    link_lengths: vec![zero(); (1 << depth) - 1],
+                    sublists: vec![None; 1 << (depth - 1)],
+                }))
+            }
+        }
+    }
+
+    /// Applies `update` to all links above node `node_index`, copying the leaf and branches on the
+    /// way there if they are shared.
+    fn update_above(&mut self, depth: usize, node_index: usize, update: &impl Fn(&mut S)) {
+        self.materialize(depth);
+        match self {
+            Links::Empty => unreachable!(),
+            Links::Leaf(leaf) => {
+                let leaf = Arc::make_mut(leaf);
+                for link_index in LinkIndicesAbove::new(node_index).take(depth) {
+                    update(&mut leaf.link_lengths[link_index])
+                }
+            }
+            Links::Branch(branch) => {
+                let branch = Arc::make_mut(branch);
+                let half = 1 << (depth - 2);
+                update(&mut branch.top);
+                if node_index < half {
+                    branch.left.update_above(depth - 1, node_index, update)
+                } else {
+                    branch.right.update_above(depth - 1, node_index - half, update)
+                }
+            }
+        }
+    }
+
+    /// Returns the sublist after node `node_index`, creating an empty one if absent, and copying
+    /// the leaf and branches on the way there if they are shared.
+    fn sublist_mut(&mut self, depth: usize, node_index: usize) -> &mut PersistentSpacedList<S> {
+        self.materialize(depth);
+        match self {
+            Links::Empty => unreachable!(),
+            Links::Leaf(leaf) =>
+                Arc::make_mut(leaf).sublists[node_index].get_or_insert_default(),
+            Links::Branch(branch) => {
+                let branch = Arc::make_mut(branch);
+                let half = 1 << (depth - 2);
+                if node_index < half {
+                    branch.left.sublist_mut(depth - 1, node_index)
+                } else {
+                    branch.right.sublist_mut(depth - 1, node_index - half)
+                }
+            }
+        }
+    }
+
+    /// Returns the links one degree deeper, with these links as their first half, like
+    /// `SpacedList::grow` does for `link_lengths`.
+    fn grow(self, depth: usize, length: S) -> Links<S> {
+        if depth + 1 > LEAF_DEPTH {
+            return Links::Branch(Arc::new(Branch { left: self, top: length, right: Links::Empty }));
+        }
+        let (mut link_lengths, mut sublists) = match self {
+            Links::Empty => (vec![zero(); (1 << depth) - 1], vec![None; 1 << depth >> 1]),
+            Links::Leaf(leaf) => {
+                let leaf = Arc::unwrap_or_clone(leaf);
+                (leaf.link_lengths, leaf.sublists)
+            }
+            Links::Branch(_) => unreachable!(),
+        };
+        link_lengths.push(length);
+        link_lengths.resize((1 << (depth + 1)) - 1, zero());
+        sublists.resize(1 << depth, None);
+        Links::Leaf(Arc::new(Leaf { link_lengths, sublists }))
+    }
+}
+// endregion
+
+// region persistent spaced list
+/// A [SpacedList](crate::SpacedList) that can be cloned in O(1), for snapshots that stay valid
+/// while the original is changed further. Each mutation copies O(log n) data.
+///
+/// Removing a node does not move the nodes after it to lower indices, which would change every
+/// link after it. The node is moved back onto the node before it instead, as a zero-length link
+/// that the queries and [PersistentPositions] pass over. Its index is only freed by building a new
+/// list. Within a run of nodes at the same position, the sublist hangs off the last one, which is
+/// the one [Self::descend] finds.
+#[derive(Clone)]
+pub struct PersistentSpacedList<S: Spacing> {
+    size: usize,
+    depth: usize,
+    length: S,
+    links: Links<S>,
+}
+
+impl<S: Spacing> Default for PersistentSpacedList<S> {
+    fn default() -> Self {
+        Self {
+            size: 1,
+            depth: 0,
+            length: zero(),
+            links: Links::Empty,
+        }
+    }
+}
+
+impl<S: Spacing> PersistentSpacedList<S> {
+    fn new() -> Self {
+        default()
+    }
+
+    fn link(&self, node_index: usize, degree: usize) -> S {
+        self.links.get(self.depth, node_index, degree)
+    }
+
+    /// Returns the sublist at `index`, or None if there is no sublist at `index` or that sublist is
+    /// empty
+    fn get_not_empty_sublist_at_index(&self, index: usize) -> Option<&PersistentSpacedList<S>> {
+        if index + 1 >= self.size {
+            return None;
+        }
+        self.links.sublist(self.depth, index).filter(|sublist| sublist.size > 1)
+    }
+
+    fn append_node(&mut self, distance: S) {
+        assert!(distance > zero());
+        self.size += 1;
+        // the capacity of a list of depth d is 2^(d - 1) + 1, see SpacedList::grow
+        if self.depth == 0 || self.size > (1 << (self.depth - 1)) + 1 {
+            let links = core::mem::replace(&mut self.links, Links::Empty);
+            self.links = links.grow(self.depth, self.length);
+            self.depth += 1;
+        }
+        self.length += distance;
+        self.links.update_above(self.depth, self.size - 1 - 1, &|length| *length += distance)
+    }
+
+    /// Returns the position and index of the last node in this list, not in sublists, that is
+    /// before `target_position`, or at it if `inclusive` is true. Assumes there is such a node. Of
+    /// several nodes at the same position, this is the last one.
+    fn descend(&self, target_position: S, inclusive: bool) -> (S, usize) {
+        let mut position = S::zero();
+        let mut index = 0usize;
+        for degree in (0..self.depth).rev() {
+            let possibly_next_index = index + (1 << degree);
+            if possibly_next_index < self.size {
+                let possibly_next_position = position + self.link(index, degree);
+                if possibly_next_position < target_position
+                    || inclusive && possibly_next_position == target_position {
+                    position = possibly_next_position;
+                    index = possibly_next_index;
+                }
+            }
+        }
+        (position, index)
+    }
+
+    /// Removes the node at `position` and returns whether there was one. The nodes in the sublist
+    /// after it keep their positions and move into the sublist of the node before it. The node at
+    /// zero cannot be removed.
+    pub fn remove(&mut self, position: S) -> bool {
+        if position <= zero() || position > self.length {
+            return false;
+        }
+        let (node_position, index) = self.descend(position, true);
+        if node_position == position {
+            self.remove_node(position, index);
+            return true;
+        }
+        if self.get_not_empty_sublist_at_index(index).is_none() {
+            return false;
+        }
+        self.links.sublist_mut(self.depth, index).remove(position - node_position)
+    }
+
+    /// Removes the node at `position`, where `index` is the last node at it, by moving it back onto
+    /// the node before it. Copies O(log n) data plus the nodes of the sublist after it, which move
+    /// into the sublist before it.
+    fn remove_node(&mut self, position: S, index: usize) {
+        let (previous_position, previous) = self.descend(position, false);
+        let removed_length = position - previous_position;
+        let last = index + 1 == self.size;
+        self.links.update_above(self.depth, previous, &|length| *length = *length - removed_length);
+        if last {
+            self.length = self.length - removed_length
+        } else {
+            self.links.update_above(self.depth, index, &|length| *length += removed_length)
+        }
+        let before = self.get_not_empty_sublist_at_index(previous).is_some()
+            .then(|| core::mem::take(self.links.sublist_mut(self.depth, previous)));
+        let after = self.get_not_empty_sublist_at_index(index).is_some()
+            .then(|| core::mem::take(self.links.sublist_mut(self.depth, index)));
+        // if the removed node was the last one, the nodes before it end up after the last node
+        if last {
+            for position_in_sublist in before.iter().flat_map(|before| before.positions().skip(1)) {
+                self.append_node(previous_position + position_in_sublist - self.length)
+            }
+            return;
+        }
+        let merged = match (before, after) {
+            (None, None) => return,
+            (Some(before), None) => before,
+            (None, Some(mut after)) => {
+                after.shift_all_forward(removed_length);
+                after
+            }
+            (Some(mut before), Some(after)) => {
+                for position_in_sublist in after.positions().skip(1) {
+                    before.insert(removed_length + position_in_sublist)
+                }
+                before
+            }
+        };
+        *self.links.sublist_mut(self.depth, index) = merged
+    }
+
+    /// Moves all nodes after node zero forward by `distance`, including the ones in sublists.
+    fn shift_all_forward(&mut self, distance: S) {
+        if self.size > 1 {
+            self.length += distance;
+            self.links.update_above(self.depth, 0, &|length| *length += distance)
+        }
+        if self.get_not_empty_sublist_at_index(0).is_some() {
+            self.links.sublist_mut(self.depth, 0).shift_all_forward(distance)
+        }
+    }
+
+    /// Moves all nodes at or after `position` forward by `distance`. The node at zero cannot be
+    /// moved.
+    pub fn shift_forward(&mut self, position: S, distance: S) {
+        assert!(position > zero());
+        assert!(distance >= zero(), "cannot shift forward by a negative distance");
+        self.shift_links(position, distance, true)
+    }
+
+    /// Moves all nodes at or after `position` back by `distance`. Panics if that would move a node
+    /// to or before the last node before `position`.
+    pub fn shift_backward(&mut self, position: S, distance: S) {
+        assert!(position > zero());
+        assert!(distance >= zero(), "cannot shift back by a negative distance");
+        if let Some(next) = self.position_at_or_after(position) {
+            let previous = self.position_before(position).unwrap();
+            assert!(distance < next - previous, "shifting back would move a node past another");
+        }
+        self.shift_links(position, distance, false)
+    }
+
+    fn shift_links(&mut self, position: S, distance: S, forward: bool) {
+        let (node_position, index) = self.descend(position, false);
+        if index + 1 < self.size {
+            let shift = |length: &mut S| if forward {
+                *length += distance
+            } else {
+                *length = *length - distance
+            };
+            shift(&mut self.length);
+            self.links.update_above(self.depth, index, &shift)
+        }
+        if self.get_not_empty_sublist_at_index(index).is_some() {
+            self.links.sublist_mut(self.depth, index)
+                .shift_links(position - node_position, distance, forward)
+        }
+    }
+}
+
+impl<S: Spacing> SpacedListBackend<S> for PersistentSpacedList<S> {
+    type Positions<'a> = PersistentPositions<'a, S> where S: 'a;
+
+    fn insert(&mut self, position: S) {
+        assert!(position > zero());
+
+        if position >= self.length {
+            self.append_node(position - self.length)
+        } else {
+            // zero() < position < self.length
+            let (node_position, index) = self.descend(position, true);
+            let position_in_sublist = position - node_position;
+            assert!(position_in_sublist > zero());
+            self.links.sublist_mut(self.depth, index).insert(position_in_sublist)
+        }
+    }
+
+    fn position_before(&self, target_position: S) -> Option<S> {
+        if target_position <= zero() {
+            return None;
+        }
+        let (position, index) = self.descend(target_position, false);
+        match self.get_not_empty_sublist_at_index(index) {
+            Some(sublist) => Some(position + sublist.position_before(target_position - position)?),
+            None => Some(position),
+        }
+    }
+
+    fn position_at_or_before(&self, target_position: S) -> Option<S> {
+        if target_position < zero() {
+            return None;
+        }
+        let (position, index) = self.descend(target_position, true);
+        match self.get_not_empty_sublist_at_index(index) {
+            Some(sublist) if position != target_position =>
+                Some(position + sublist.position_at_or_before(target_position - position)?),
+            _ => Some(position),
+        }
+    }
+
+    fn position_at(&self, target_position: S) -> Option<S> {
+        if target_position < zero() {
+            return None;
+        }
+        let (position, index) = self.descend(target_position, true);
+        if position == target_position {
+            return Some(position);
+        }
+        let sublist = self.get_not_empty_sublist_at_index(index)?;
+        Some(position + sublist.position_at(target_position - position)?)
+    }
+
+    fn position_at_or_after(&self, target_position: S) -> Option<S> {
+        if target_position < zero() {
+            return Some(zero());
+        }
+        if target_position > self.length {
+            return None;
+        }
+        let (position, index) = self.descend(target_position, true);
+        if position == target_position {
+            return Some(position);
+        }
+        let in_sublist = self.get_not_empty_sublist_at_index(index)
+            .and_then(|sublist| sublist.position_at_or_after(target_position - position));
+        Some(position + in_sublist.unwrap_or_else(|| self.link(index, 0)))
+    }
+
+    fn position_after(&self, target_position: S) -> Option<S> {
+        if target_position < zero() {
+            return Some(zero());
+        }
+        if target_position >= self.length {
+            return None;
+        }
+        let (position, index) = self.descend(target_position, true);
+        let in_sublist = self.get_not_empty_sublist_at_index(index)
+            .and_then(|sublist| sublist.position_after(target_position - position));
+        Some(position + in_sublist.unwrap_or_else(|| self.link(index, 0)))
+    }
+
+    fn positions(&self) -> PersistentPositions<'_, S> {
+        PersistentPositions {
+            stack: vec![(self, 0, zero())],
+        }
+    }
+}
+
+/// Iterator over the positions of all nodes of a [PersistentSpacedList], including sublists.
+pub struct PersistentPositions<'a, S: Spacing> {
+    /// The lists being iterated, innermost last, each with the index and the absolute position of
+    /// the next node to visit in it.
+    stack: Vec<(&'a PersistentSpacedList<S>, usize, S)>,
+}
+
+impl<S: Spacing> Iterator for PersistentPositions<'_, S> {
+    type Item = S;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let nesting = self.stack.len();
+            let (list, index, position) = self.stack.last_mut()?;
+            if *index >= list.size {
+                self.stack.pop();
+                continue;
+            }
+            let list = *list;
+            let node_index = *index;
+            let node_position = *position;
+            // node zero of a sublist is the node it hangs off, which has been visited already, and
+            // a removed node lies on the node before it
+            let visited = node_index == 0 && nesting > 1
+                || node_index > 0 && list.link(node_index - 1, 0) == zero();
+            *index += 1;
+            if node_index + 1 < list.size {
+                *position += list.link(node_index, 0);
+            }
+            if let Some(sublist) = list.get_not_empty_sublist_at_index(node_index) {
+                self.stack.push((sublist, 0, node_position));
+            }
+            if visited {
+                continue;
+            }
+            return Some(node_position);
+        }
+    }
+}
+// endregion
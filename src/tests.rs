@@ -1,6 +1,12 @@
 use std::default::default;
-use num_traits::zero;
-use crate::{link_index, LinkIndicesAbove, SpacedList, TraversalResult};
+use std::time::{Duration, Instant};
+mod model;
+
+use crate::{ArenaSpacedList, ArraySpacedList, Bias, CapacityError, ChangeSet, child_link_indices, Component, diff,
+            Edit, Entry, eytzinger_link_index, History, InvariantViolation, LineIndex, link_index, LinkIndicesAbove,
+            Metrics, Overflow, PersistentSpacedList, PREFETCH_DEGREES, prefetched_link_indices, RangeUpdate,
+            SpacedList, SpacedListBackend, Sum, TraversalResult, zero};
+use crate::unchunked::unchunked_link_index;
 
 #[test]
 fn test_link_index() {
@@ -30,6 +36,78 @@ fn test_link_index() {
     assert_eq!(link_index(7, 2), 0b1011);
 }
 
+#[test]
+fn test_eytzinger_link_index() {
+    // the degrees of the links in the order they are stored, from the top link down
+    let degrees = "322111100000000";
+    let mut stored = vec![None; degrees.len()];
+    for degree in 0..4 {
+        for node_index in (0..8).step_by(1 << degree) {
+            let index = eytzinger_link_index(node_index, degree, 4);
+            assert_eq!(stored[index], None);
+            stored[index] = Some(degree);
+            for covered in node_index..node_index + (1 << degree) {
+                assert_eq!(eytzinger_link_index(covered, degree, 4), index);
+            }
+            if degree > 0 {
+                let half = 1 << (degree - 1);
+                assert_eq!(child_link_indices(index), [
+                    eytzinger_link_index(node_index, degree - 1, 4),
+                    eytzinger_link_index(node_index + half, degree - 1, 4),
+                ]);
+            }
+        }
+    }
+    let stored: String = stored.iter().map(|degree| char::from(b'0' + degree.unwrap() as u8)).collect();
+    assert_eq!(stored, degrees);
+}
+
+#[test]
+fn test_prefetched_link_indices() {
+    // the links a descent to each node reads, one per degree below the top, are prefetched
+    // PREFETCH_DEGREES degrees ahead
+    let depth = 8;
+    for node_index in 0..1 << (depth - 1) {
+        let read = |degree: usize| eytzinger_link_index(node_index >> (degree + 1) << (degree + 1), degree, depth);
+        for degree in PREFETCH_DEGREES..depth - 1 {
+            let prefetched = prefetched_link_indices(read(degree));
+            let read_later = read(degree - PREFETCH_DEGREES);
+            assert!(prefetched.contains(&read_later), "{} is not prefetched for {}", read_later, node_index);
+        }
+    }
+}
+
+#[test]
+fn test_descend_past_last_node() {
+    // descending to the last node must not add the link after it, which would overflow
+    let mut list = SpacedList::<u8>::default();
+    list.insert(200);
+    list.insert(250);
+    assert_eq!(list.position_at_or_after(250), Some(250));
+    assert_eq!(list.position_at_or_before(255), Some(250));
+    assert_eq!(list.position_after(250), None);
+}
+
+#[test]
+fn test_unchunked_link_index() {
+    // the degrees of the links in the order they are stored, as listed in unchunked.md
+    let degrees = "0010012001001230010012001001234";
+    let mut stored = vec![None; degrees.len()];
+    for degree in 0..5 {
+        for node_index in (0..16).step_by(1 << degree) {
+            let index = unchunked_link_index(node_index, degree);
+            assert_eq!(stored[index], None);
+            stored[index] = Some(degree);
+            // every node covered by a link finds the same link
+            for covered in node_index..node_index + (1 << degree) {
+                assert_eq!(unchunked_link_index(covered, degree), index);
+            }
+        }
+    }
+    let stored: String = stored.iter().map(|degree| degree.unwrap().to_string()).collect();
+    assert_eq!(stored, degrees);
+}
+
 #[test]
 fn test_link_indices_above() {
     let mut iterator = LinkIndicesAbove::new(0);
@@ -67,6 +145,10 @@ fn test_link_indices_above() {
     assert_eq!(iterator.next(), Some(link_index(4, 1)));
     assert_eq!(iterator.next(), Some(link_index(4, 2)));
     assert_eq!(iterator.next(), Some(link_index(0, 3)));
+
+    let list = SpacedList::<usize>::from_gaps(&[1; 8]);
+    let above: Vec<_> = list.link_indices_above(5).collect();
+    assert_eq!(above, vec![list.link_index(5, 0), list.link_index(4, 1), list.link_index(4, 2), list.link_index(0, 3)]);
 }
 
 #[test]
@@ -107,6 +189,49 @@ fn test_make_space() {
     assert_eq!(list.link_lengths.len(), 15);
 }
 
+#[test]
+fn test_capacity() {
+    let list = SpacedList::<usize>::with_capacity(1);
+    assert_eq!(list.capacity, 1);
+    assert_eq!(list.link_lengths.len(), 0);
+
+    let mut list = SpacedList::<usize>::with_capacity(6);
+    assert_eq!(list.capacity, 9);
+    assert_eq!(list.link_lengths.len(), 15);
+    assert!(list.sublists.capacity() >= 5);
+    for position in 1..9 {
+        list.insert(position * 2);
+    }
+    assert_eq!(list.capacity, 9);
+    assert_eq!(list.validate(), Ok(()));
+
+    list.reserve(1);
+    assert_eq!(list.capacity, 17);
+    assert_eq!(list.link_lengths.len(), 31);
+    assert_eq!(list.validate(), Ok(()));
+
+    list.insert(3);
+    let mut handles = list.take_handles();
+    list.get_sublist_at_index(3, &mut handles);
+    list.handles = Some(handles);
+    list.shrink_to_fit();
+    assert_eq!(list.capacity, 9);
+    assert_eq!(list.link_lengths.len(), 15);
+    assert_eq!(list.sublists[3], None);
+    assert_eq!(list.validate(), Ok(()));
+    assert_eq!(list.node_at(3).map(|path| path.len()), Some(2));
+    list.insert(18);
+    assert_eq!(list.capacity, 17);
+    assert_eq!(list.validate(), Ok(()));
+
+    let mut list = SpacedList::<usize>::with_capacity(100);
+    list.insert(1);
+    list.insert(2);
+    list.shrink_to_fit();
+    assert_eq!(list.capacity, 3);
+    assert_eq!(list.link_lengths, vec![2, 1, 1]);
+}
+
 #[test]
 fn test_append_node() {
     let mut list = SpacedList::<usize>::new();
@@ -122,32 +247,32 @@ fn test_append_node() {
     list.append_node(2);
     assert_eq!(list.size, 3);
     assert_eq!(list.length, 3);
-    assert_eq!(list.link_lengths, vec![1, 3, 2]);
+    assert_eq!(list.link_lengths, vec![3, 1, 2]);
 
     list.append_node(3);
     assert_eq!(list.size, 4);
     assert_eq!(list.length, 6);
-    assert_eq!(list.link_lengths, vec![1, 3, 2, 6, 3, 3, 0]);
+    assert_eq!(list.link_lengths, vec![6, 3, 3, 1, 2, 3, 0]);
 
     list.append_node(2);
     assert_eq!(list.size, 5);
     assert_eq!(list.length, 8);
-    assert_eq!(list.link_lengths, vec![1, 3, 2, 8, 3, 5, 2]);
+    assert_eq!(list.link_lengths, vec![8, 3, 5, 1, 2, 3, 2]);
 
     list.append_node(2);
     assert_eq!(list.size, 6);
     assert_eq!(list.length, 10);
-    assert_eq!(list.link_lengths, vec![1, 3, 2, 8, 3, 5, 2, 10, 2, 2, 0, 2, 0, 0, 0]);
+    assert_eq!(list.link_lengths, vec![10, 8, 2, 3, 5, 2, 0, 1, 2, 3, 2, 2, 0, 0, 0]);
 
     list.append_node(3);
     assert_eq!(list.size, 7);
     assert_eq!(list.length, 13);
-    assert_eq!(list.link_lengths, vec![1, 3, 2, 8, 3, 5, 2, 13, 2, 5, 3, 5, 0, 0, 0]);
+    assert_eq!(list.link_lengths, vec![13, 8, 5, 3, 5, 5, 0, 1, 2, 3, 2, 2, 3, 0, 0]);
 
     list.append_node(1);
     assert_eq!(list.size, 8);
     assert_eq!(list.length, 14);
-    assert_eq!(list.link_lengths, vec![1, 3, 2, 8, 3, 5, 2, 14, 2, 5, 3, 6, 1, 1, 0]);
+    assert_eq!(list.link_lengths, vec![14, 8, 6, 3, 5, 5, 1, 1, 2, 3, 2, 2, 3, 1, 0]);
 }
 
 #[test]
@@ -936,3 +1061,946 @@ fn test_insert_and_traversal() {
     assert_eq!(list.node_at_or_after(10), None);
     assert_eq!(list.node_after(10), None);
 }
+
+#[test]
+fn test_validate() {
+    let mut list = SpacedList::<isize>::new();
+    assert_eq!(list.validate(), Ok(()));
+    list.insert(2);
+    list.insert(6);
+    list.insert(3);
+    list.insert(5);
+    list.insert(4);
+    list.insert(7);
+    list.insert(9);
+    list.insert(8);
+    assert_eq!(list.validate(), Ok(()));
+
+    let mut broken = SpacedList::<isize>::new();
+    broken.insert(2);
+    broken.insert(6);
+    broken.insert(3);
+    broken.insert(9);
+    broken.length += 1;
+    assert_eq!(broken.validate(), Err(InvariantViolation::Length));
+
+    let mut broken = SpacedList::<isize>::new();
+    broken.insert(2);
+    broken.insert(6);
+    broken.insert(3);
+    broken.insert(9);
+    let link_index = broken.link_index(0, 1);
+    broken.link_lengths[link_index] += 1;
+    assert_eq!(broken.validate(), Err(InvariantViolation::LinkSum { index: 0, degree: 1 }));
+
+    let mut broken = SpacedList::<isize>::new();
+    broken.insert(2);
+    broken.insert(6);
+    broken.insert(9);
+    let link_index = broken.link_index(3, 0);
+    broken.link_lengths[link_index] = 1;
+    assert_eq!(broken.validate(), Err(InvariantViolation::LinkAfterLastNode { index: 3 }));
+
+    let mut broken = SpacedList::<isize>::new();
+    broken.insert(2);
+    broken.insert(6);
+    broken.insert(3);
+    broken.insert(9);
+    let link_index = broken.link_index(1, 0);
+    broken.link_node_counts[link_index] += 1;
+    assert_eq!(broken.validate(), Err(InvariantViolation::LinkNodeCount { index: 1, degree: 0 }));
+
+    let mut broken = SpacedList::<isize>::new();
+    broken.insert(2);
+    broken.insert(6);
+    broken.insert(5);
+    broken.get_not_empty_sublist_at_index_mut(1).unwrap().append_node(1);
+    assert_eq!(broken.validate(), Err(InvariantViolation::SublistTooLong { index: 1 }));
+
+    let mut broken = SpacedList::<isize>::new();
+    broken.insert(2);
+    broken.insert(6);
+    broken.insert(5);
+    broken.get_not_empty_sublist_at_index_mut(1).unwrap().length = 2;
+    assert_eq!(broken.validate(), Err(InvariantViolation::InSublist {
+        index: 1,
+        violation: Box::new(InvariantViolation::Length),
+    }));
+}
+
+#[test]
+fn test_positions() {
+    let mut list = SpacedList::<isize>::new();
+    assert_eq!(list.positions().collect::<Vec<_>>(), vec![0]);
+    list.insert(2);
+    list.insert(6);
+    list.insert(3);
+    list.insert(5);
+    list.insert(4);
+    list.insert(7);
+    list.insert(9);
+    list.insert(8);
+    list.insert(1);
+    assert_eq!(list.positions().collect::<Vec<_>>(), (0..=9).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_entry() {
+    let mut list = SpacedList::<u32, Sum<i32>>::new();
+    list.insert_with_value(10, 1);
+    list.insert_with_value(20, 2);
+    list.insert_with_value(15, 3);
+    assert!(matches!(list.entry(15), Entry::Occupied(_)));
+    assert!(matches!(list.entry(12), Entry::Vacant(_)));
+    assert_eq!(list.entry(15).and_modify(|value| *value *= 10).or_insert(0), 30);
+    assert_eq!(list.entry(12).and_modify(|value| *value *= 10).or_insert(4), 4);
+    assert_eq!(list.entry(30).or_insert_with(|| 5), 5);
+    assert_eq!(list.entry(30).or_default(), 5);
+    assert_eq!(list.entry(13).or_default(), 0);
+    assert!(list.positions().eq([0, 10, 12, 13, 15, 20, 30]));
+    assert_eq!(list.aggregate(0..40), Sum(42));
+    match list.entry(12) {
+        Entry::Occupied(mut entry) => {
+            assert_eq!(entry.insert(6), 4);
+            assert_eq!(entry.get(), 6);
+            assert_eq!(entry.remove(), 6);
+        }
+        Entry::Vacant(_) => panic!("no node at 12"),
+    }
+    assert_eq!(list.aggregate(0..40), Sum(38));
+    list.validate().unwrap();
+}
+
+#[test]
+fn test_range_update_more_nodes_than_values() {
+    let mut list = SpacedList::<u32, Sum<u8>>::new();
+    for position in 1..300 {
+        list.insert(position);
+    }
+    list.update(0..400, RangeUpdate::Set(0));
+    assert_eq!(list.aggregate(0..400), Sum(0));
+    list.update(100..185, RangeUpdate::Set(3));
+    assert_eq!(list.aggregate(0..400), Sum(255));
+    list.validate().unwrap();
+}
+
+#[test]
+fn test_array_capacity() {
+    let mut list = ArraySpacedList::<u32, 16>::new();
+    // the root list grows into blocks of 2, 4 and 8 slots, and would need 16 for a sixth node
+    for position in [10, 20, 30, 40] {
+        assert_eq!(list.insert(position), Ok(()));
+    }
+    assert_eq!(list.insert(50), Err(CapacityError));
+    // sublists take the blocks the root list outgrew, and the last 2 unused slots
+    for position in [15, 25, 17, 35] {
+        assert_eq!(list.insert(position), Ok(()));
+    }
+    assert_eq!(list.insert(16), Err(CapacityError));
+    assert_eq!(list.insert(45), Err(CapacityError));
+    assert!(list.positions().eq([0, 10, 15, 17, 20, 25, 30, 35, 40]));
+    assert_eq!(list.position_after(17), Some(20));
+    assert_eq!(list.position_before(20), Some(17));
+    assert_eq!(list.position_at_or_after(41), None);
+}
+
+#[test]
+fn test_persistent_snapshots() {
+    let mut list = PersistentSpacedList::<isize>::default();
+    let mut snapshots = vec![list.clone()];
+    for position in (2..=2000).step_by(2) {
+        list.insert(position);
+        snapshots.push(list.clone());
+    }
+    let mut branch = snapshots[500].clone();
+    branch.insert(1);
+    branch.insert(999);
+    for position in (2001..=4000).step_by(2) {
+        list.insert(position);
+    }
+    for (count, snapshot) in snapshots.iter().enumerate() {
+        assert!(snapshot.positions().eq((0..=2 * count as isize).step_by(2)));
+    }
+    assert!(list.positions().eq((0..=2000).step_by(2).chain((2001..=4000).step_by(2))));
+    let mut expected: Vec<_> = (0..=1000).step_by(2).chain([1, 999]).collect();
+    expected.sort();
+    assert!(branch.positions().eq(expected));
+    assert_eq!(branch.position_after(1), Some(2));
+    assert_eq!(snapshots[500].position_after(1), Some(2));
+    assert_eq!(snapshots[500].position_at(999), None);
+}
+
+#[test]
+fn test_persistent_remove_and_shift() {
+    let mut list = PersistentSpacedList::<isize>::default();
+    let mut nested = SpacedList::<isize>::new();
+    // enough nodes for branches above the leaves, with sublists between them
+    for position in (4..=400).step_by(4).chain((1..400).step_by(8)) {
+        list.insert(position);
+        nested.insert(position);
+    }
+    let mut snapshots = vec![(list.clone(), list.positions().collect::<Vec<_>>())];
+    let mut check = |list: &PersistentSpacedList<isize>, nested: &SpacedList<isize>| {
+        assert!(list.positions().eq(nested.positions()));
+        for target in -1..=410 {
+            assert_eq!(list.position_before(target), nested.position_before(target), "{}", target);
+            assert_eq!(list.position_at_or_after(target), nested.position_at_or_after(target));
+            assert_eq!(list.position_after(target), nested.position_after(target));
+        }
+        for (snapshot, positions) in &snapshots {
+            assert!(snapshot.positions().eq(positions.iter().copied()));
+        }
+        snapshots.push((list.clone(), list.positions().collect()));
+    };
+    assert!(!list.remove(0));
+    assert!(!list.remove(2));
+    assert!(!list.remove(401));
+    // a node with a sublist after it, one in a sublist, and the last node
+    for position in [4, 8, 1, 400, 396, 200, 204] {
+        assert!(list.remove(position));
+        assert!(nested.remove(position));
+        check(&list, &nested);
+    }
+    list.shift_forward(100, 7);
+    nested.shift_forward(100, 7);
+    check(&list, &nested);
+    list.shift_backward(105, 3);
+    nested.shift_backward(105, 3);
+    check(&list, &nested);
+    for position in (1..=406).rev() {
+        assert_eq!(list.remove(position), nested.remove(position));
+    }
+    check(&list, &nested);
+    list.insert(5);
+    nested.insert(5);
+    check(&list, &nested);
+}
+
+#[test]
+fn test_remove() {
+    let mut list = SpacedList::<isize>::new();
+    for position in [4, 8, 12, 1, 2, 3, 5, 6, 9, 10] {
+        list.insert(position);
+    }
+    assert!(!list.remove(0));
+    assert!(!list.remove(7));
+    assert!(!list.remove(13));
+    let id = list.node_id(6).unwrap();
+    // a node with a sublist after it
+    assert!(list.remove(4));
+    list.validate().unwrap();
+    assert_eq!(list.positions().collect::<Vec<_>>(), vec![0, 1, 2, 3, 5, 6, 8, 9, 10, 12]);
+    assert_eq!(list.position_of(id), Some(6));
+    // the last node, with a sublist before it
+    assert!(list.remove(12));
+    list.validate().unwrap();
+    assert_eq!(list.positions().collect::<Vec<_>>(), vec![0, 1, 2, 3, 5, 6, 8, 9, 10]);
+    // a node in a sublist
+    assert!(list.remove(2));
+    list.validate().unwrap();
+    assert_eq!(list.positions().collect::<Vec<_>>(), vec![0, 1, 3, 5, 6, 8, 9, 10]);
+    for position in [1, 3, 5, 6, 8, 9, 10] {
+        assert!(list.remove(position));
+        list.validate().unwrap();
+    }
+    assert_eq!(list.positions().collect::<Vec<_>>(), vec![0]);
+}
+
+#[test]
+fn test_shift() {
+    let mut list = SpacedList::<isize>::new();
+    for position in [4, 8, 12, 1, 2, 5, 6, 9] {
+        list.insert(position);
+    }
+    list.shift_forward(6, 10);
+    list.validate().unwrap();
+    assert_eq!(list.positions().collect::<Vec<_>>(), vec![0, 1, 2, 4, 5, 16, 18, 19, 22]);
+    list.shift_backward(16, 10);
+    list.validate().unwrap();
+    assert_eq!(list.positions().collect::<Vec<_>>(), vec![0, 1, 2, 4, 5, 6, 8, 9, 12]);
+    list.shift_backward(6, 0);
+    list.shift_forward(13, 3);
+    assert_eq!(list.positions().collect::<Vec<_>>(), vec![0, 1, 2, 4, 5, 6, 8, 9, 12]);
+    list.shift_backward(1, 0);
+    let result = std::panic::catch_unwind(move || list.shift_backward(6, 1));
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_history() {
+    let mut history = History::<isize>::default();
+    let positions = |history: &History<isize>| history.list().positions().collect::<Vec<_>>();
+    assert!(!history.undo());
+    history.insert(4);
+    history.insert(2);
+    history.begin_group();
+    history.insert(8);
+    history.begin_group();
+    history.shift_forward(3, 2);
+    history.end_group();
+    assert!(!history.remove(5));
+    history.end_group();
+    assert_eq!(positions(&history), vec![0, 2, 6, 10]);
+    assert!(history.remove(2));
+    assert!(history.undo());
+    assert_eq!(positions(&history), vec![0, 2, 6, 10]);
+    assert!(history.undo());
+    assert_eq!(positions(&history), vec![0, 2, 4]);
+    assert!(history.redo());
+    assert_eq!(positions(&history), vec![0, 2, 6, 10]);
+    assert!(history.undo());
+    history.shift_backward(4, 1);
+    assert!(!history.can_redo());
+    assert_eq!(positions(&history), vec![0, 2, 3]);
+    assert!(history.undo());
+    assert!(history.undo());
+    assert!(history.undo());
+    assert!(!history.can_undo());
+    assert_eq!(positions(&history), vec![0]);
+
+    // undoing a removal brings back the value and handle of the node
+    let mut list = SpacedList::<isize, Sum<i32>>::new();
+    list.insert_with_value(3, 30);
+    list.insert_with_value(7, 70);
+    let id = list.node_id(7).unwrap();
+    let mut history = History::new(list);
+    assert!(history.remove(7));
+    history.apply(Edit::Remove(3));
+    assert!(history.undo());
+    assert!(history.undo());
+    assert_eq!((history.list().value_at(3), history.list().value_at(7)), (Some(30), Some(70)));
+    assert_eq!(history.list().position_of(id), Some(7));
+    assert_eq!(history.list().total_aggregate(), Sum(100));
+    assert!(history.redo());
+    assert_eq!(history.list().position_of(id), None);
+    assert!(history.undo());
+    assert_eq!(history.list().position_of(id), Some(7));
+}
+
+#[test]
+fn test_change_set() {
+    let mut list = SpacedList::<isize>::new();
+    list.insert(2);
+    list.insert(5);
+    list.insert(9);
+    let mut changes = ChangeSet::new();
+    changes.apply(&mut list, Edit::ShiftForward { position: 5, distance: 3 });
+    changes.apply(&mut list, Edit::Insert(4));
+    changes.apply(&mut list, Edit::ShiftBackward { position: 12, distance: 2 });
+    // does not move any node, so it is not recorded
+    changes.apply(&mut list, Edit::ShiftForward { position: 11, distance: 1 });
+    assert_eq!(list.positions().collect::<Vec<_>>(), vec![0, 2, 4, 8, 10]);
+    let map = |position| (changes.map(position, Bias::Before), changes.map(position, Bias::After));
+    assert_eq!(map(2), (2, 2));
+    assert_eq!(map(5), (5, 8));
+    assert_eq!(map(9), (10, 10));
+    // 7 and 8 end up in the space that the shift back removes
+    assert_eq!(map(7), (10, 10));
+    assert_eq!(map(8), (10, 10));
+    assert_eq!(map(6), (9, 9));
+    assert_eq!(map(20), (21, 21));
+
+    let inverse = changes.invert();
+    assert_eq!(inverse.edits(), &[
+        Edit::ShiftForward { position: 10, distance: 2 },
+        Edit::Remove(4),
+        Edit::ShiftBackward { position: 8, distance: 3 },
+    ]);
+    for position in [0, 2, 5, 9] {
+        assert_eq!(inverse.map(changes.map(position, Bias::After), Bias::After), position);
+    }
+    inverse.apply_to(&mut list);
+    assert_eq!(list.positions().collect::<Vec<_>>(), vec![0, 2, 5, 9]);
+
+    let composed = changes.clone().compose(&inverse);
+    assert_eq!(composed.edits().len(), 6);
+    assert_eq!(composed.map(9, Bias::After), 9);
+    assert!(ChangeSet::<isize>::default().compose(&ChangeSet::new()).is_empty());
+}
+
+#[test]
+fn test_gaps() {
+    let mut list = SpacedList::<isize, Sum<i64>>::new();
+    for position in [8, 2, 5, 3, 12, 9] {
+        list.insert_with_value(position, 1);
+    }
+    assert_eq!(list.to_positions(), vec![2, 3, 5, 8, 9, 12]);
+    assert_eq!(list.to_gaps(), vec![2, 1, 2, 3, 1, 3]);
+    let imported = SpacedList::<isize, Sum<i64>>::from_gaps(&list.to_gaps());
+    imported.validate().unwrap();
+    assert_eq!(imported.to_positions(), list.to_positions());
+    assert_eq!(imported.node_count(), 6);
+    assert_eq!(imported.aggregate(0..100), Sum(0));
+    assert_eq!(imported.position_of_rank(3), Some(5));
+    let imported = SpacedList::<isize>::from_positions(&[1, 4, 6]);
+    imported.validate().unwrap();
+    assert_eq!(imported.to_gaps(), vec![1, 3, 2]);
+    assert_eq!(SpacedList::<isize>::from_gaps(&[]).to_positions(), vec![]);
+    assert!(std::panic::catch_unwind(|| SpacedList::<isize>::from_positions(&[1, 1])).is_err());
+    assert!(std::panic::catch_unwind(|| SpacedList::<isize>::from_gaps(&[1, 0])).is_err());
+}
+
+#[test]
+fn test_signed_shift() {
+    let mut list = SpacedList::<u8>::default();
+    list.insert(200);
+    list.insert(250);
+    list.shift(250, -30i8);
+    assert_eq!(list.positions().collect::<Vec<_>>(), vec![0, 200, 220]);
+    list.shift(200, 5i8);
+    list.shift(205, 10u8);
+    assert_eq!(list.positions().collect::<Vec<_>>(), vec![0, 215, 235]);
+    list.shift(236, i8::MIN);
+    list.validate().unwrap();
+    assert!(std::panic::catch_unwind(|| {
+        let mut list = SpacedList::<u8>::default();
+        list.insert(100);
+        list.shift(100, -i8::MAX)
+    }).is_err());
+
+    let mut list = SpacedList::<i8>::default();
+    list.insert(100);
+    list.shift(101, i8::MIN);
+    list.shift(100, -50i8);
+    assert_eq!(list.positions().collect::<Vec<_>>(), vec![0, 50]);
+    assert!(std::panic::catch_unwind(move || list.shift(50, i8::MIN)).is_err());
+}
+
+#[test]
+fn test_duration() {
+    let second = Duration::from_secs(1);
+    let mut list = SpacedList::<Duration>::default();
+    list.insert(2 * second);
+    list.insert(5 * second);
+    list.shift_backward(5 * second, second);
+    assert_eq!(list.to_positions(), vec![2 * second, 4 * second]);
+    assert_eq!(list.position_before(3 * second), Some(2 * second));
+    assert_eq!(SpacedList::<Duration>::from_gaps(&list.to_gaps()).to_positions(), list.to_positions());
+}
+
+#[test]
+fn test_overflow() {
+    let mut list = SpacedList::<u8>::checked_from_gaps(&[100, 100, 50]).unwrap();
+    assert_eq!(SpacedList::<u8>::checked_from_gaps(&[100, 100, 56]).err(), Some(Overflow));
+    assert_eq!(list.checked_shift_forward(200, 6), Err(Overflow));
+    assert_eq!(list.to_positions(), vec![100, 200, 250]);
+    list.validate().unwrap();
+    // nothing moves after the last node
+    assert_eq!(list.checked_shift_forward(251, 100), Ok(()));
+    assert_eq!(list.checked_shift_forward(100, 5), Ok(()));
+    assert_eq!(list.to_positions(), vec![105, 205, 255]);
+    list.shift_backward(205, 50);
+    assert_eq!(list.to_positions(), vec![105, 155, 205]);
+    let mut history = History::new(list);
+    assert_eq!(history.checked_shift_forward(155, 51), Err(Overflow));
+    assert!(!history.undo());
+    assert_eq!(history.checked_shift_forward(155, 50), Ok(()));
+    assert_eq!(history.list().to_positions(), vec![105, 205, 255]);
+    let mut list = SpacedList::<Metrics<(u8, u8)>>::new();
+    list.insert(Metrics((200, 10)));
+    assert_eq!(list.checked_shift_forward(Metrics((1, 1)), Metrics((10, 250))), Err(Overflow));
+    let mut list = SpacedList::<u8>::checked_from_gaps(&[100, 100]).unwrap();
+    assert_eq!(list.checked_shift(200, 56i8), Err(Overflow));
+    assert_eq!(list.checked_shift(200, -50i8), Ok(()));
+    assert_eq!(list.checked_shift(100, 100i8), Ok(()));
+    assert_eq!(list.checked_shift(250, i8::MAX), Err(Overflow));
+    assert_eq!(list.to_positions(), vec![200, 250]);
+}
+
+#[test]
+fn test_checked_edits() {
+    let mut history = History::new(SpacedList::<u8>::checked_from_gaps(&[100, 100]).unwrap());
+    let shift = |position, distance| Edit::ShiftForward { position, distance };
+    assert_eq!(history.checked_apply(shift(150, 56)), Err(Overflow));
+    assert!(!history.undo());
+    assert_eq!(history.checked_apply(Edit::Insert(250)), Ok(()));
+    assert_eq!(history.checked_apply(shift(250, 5)), Ok(()));
+    assert_eq!(history.list().to_positions(), vec![100, 200, 255]);
+    let mut list = SpacedList::<u8, Sum<u8>>::new();
+    list.insert_with_value(100, 7);
+    list.insert_with_value(200, 9);
+    let id = list.node_id(100).unwrap();
+    let mut changes = ChangeSet::new();
+    let mut other = SpacedList::<u8>::checked_from_gaps(&[100, 50]).unwrap();
+    assert_eq!(changes.checked_apply(&mut other, Edit::Remove(100)), Ok(()));
+    assert_eq!(changes.checked_apply(&mut other, shift(150, 100)), Ok(()));
+    assert_eq!(changes.checked_apply(&mut other, shift(1, 6)), Err(Overflow));
+    assert_eq!(changes.edits().len(), 2);
+    // the removal applies, but then 200 cannot move by 100
+    assert_eq!(changes.checked_apply_to(&mut list), Err(Overflow));
+    assert_eq!(list.to_positions(), vec![100, 200]);
+    assert_eq!(list.value_at(100), Some(7));
+    assert_eq!(list.position_of(id), Some(100));
+    list.validate().unwrap();
+    let mut list = SpacedList::<u8, Sum<u8>>::new();
+    list.insert_with_value(100, 7);
+    assert_eq!(changes.checked_apply_to(&mut list), Ok(()));
+    assert_eq!(list.to_positions(), vec![]);
+}
+
+#[test]
+fn test_checked_aggregates() {
+    let mut list = SpacedList::<u32, Sum<u8>>::new();
+    for position in 1..=300 {
+        list.insert(position);
+    }
+    assert_eq!(list.checked_update(0..400, RangeUpdate::Set(1)), Err(Overflow));
+    assert_eq!(list.aggregate(0..400), Sum(0));
+    assert_eq!(list.checked_update(0..256, RangeUpdate::Set(1)), Ok(()));
+    assert_eq!(list.checked_insert_with_value(301, 1), Err(Overflow));
+    assert_eq!(list.checked_set_value(300, 1), Err(Overflow));
+    assert_eq!(list.checked_set_value(1, 0), Ok(true));
+    assert_eq!(list.checked_set_value(1000, 0), Ok(false));
+    assert_eq!(list.checked_set_value(300, 1), Ok(true));
+    assert_eq!(list.checked_update(250..300, RangeUpdate::Add(1)), Err(Overflow));
+    assert_eq!(list.aggregate(0..400), Sum(255));
+    list.validate().unwrap();
+}
+
+#[test]
+fn test_checked_removal() {
+    let list = |positions: &[u8]| {
+        let mut list = SpacedList::<u8, Sum<i8>>::new();
+        for &position in positions {
+            list.insert(position);
+        }
+        list.set_value(20, -100);
+        list.set_value(10, 100);
+        list.set_value(30, 100);
+        list
+    };
+    // 20 is in the sublist after 10 when it is inserted last
+    for positions in [[10, 20, 30], [10, 30, 20], [30, 20, 10]] {
+        let mut list = list(&positions);
+        assert_eq!(list.checked_remove(20), Err(Overflow));
+        assert_eq!(list.to_positions(), vec![10, 20, 30]);
+        assert_eq!(list.aggregate(0..40), Sum(100));
+        assert_eq!(list.checked_remove(25), Ok(false));
+        assert_eq!(list.checked_remove(30), Ok(true));
+        assert_eq!(list.checked_remove(20), Ok(true));
+        assert_eq!(list.aggregate(0..40), Sum(100));
+        list.validate().unwrap();
+    }
+    let mut list = list(&[10, 20, 30]);
+    list.set_value(30, 27);
+    assert_eq!(list.checked_remove(20), Ok(true));
+    assert_eq!(list.aggregate(0..40), Sum(127));
+    assert_eq!(list.checked_append_node(10, 1), Err(Overflow));
+    assert_eq!(list.checked_append_node(226, -1), Err(Overflow));
+    assert_eq!(list.checked_append_node(225, -1), Ok(()));
+    assert_eq!(list.to_positions(), vec![10, 30, 255]);
+    assert_eq!(list.checked_append_node(1, 0), Err(Overflow));
+    let list = SpacedList::<u8>::checked_from_positions(&[1, 255]).unwrap();
+    assert_eq!(list.to_positions(), vec![1, 255]);
+}
+
+#[test]
+fn test_checked_entries() {
+    let mut list = SpacedList::<u8, Sum<i8>>::new();
+    list.insert_with_value(10, 100);
+    list.insert_with_value(30, 27);
+    assert_eq!(list.checked_entry(20).unwrap().checked_or_insert(1), Err(Overflow));
+    assert_eq!(list.checked_entry(20).unwrap().checked_or_default(), Ok(0));
+    assert_eq!(list.checked_entry(10).unwrap().checked_and_modify(|value| *value += 1).err(),
+               Some(Overflow));
+    assert_eq!(list.value_at(10), Some(100));
+    let Ok(Entry::Occupied(mut entry)) = list.checked_entry(20) else {
+        panic!("20 is occupied")
+    };
+    assert_eq!(entry.checked_insert(1), Err(Overflow));
+    assert_eq!(entry.checked_insert(-1), Ok(0));
+    assert_eq!(list.checked_entry(30).unwrap().checked_and_modify(|value| *value = 28)
+                   .map(|entry| entry.checked_or_insert(0)), Ok(Ok(28)));
+    assert_eq!(list.checked_entry(20).unwrap().checked_and_modify(|value| *value = 0).err(),
+               Some(Overflow));
+    let Ok(Entry::Occupied(entry)) = list.checked_entry(20) else {
+        panic!("20 is occupied")
+    };
+    assert_eq!(entry.checked_remove(), Err(Overflow));
+    assert_eq!(list.to_positions(), vec![10, 20, 30]);
+    assert_eq!(list.checked_entry(30).unwrap().checked_or_insert_with(|| unreachable!()), Ok(28));
+    assert_eq!(list.aggregate(0..40), Sum(127));
+    list.validate().unwrap();
+}
+
+#[test]
+fn test_checked_removal_edits() {
+    let mut list = SpacedList::<u8, Sum<i8>>::new();
+    list.insert_with_value(10, 100);
+    list.insert_with_value(20, -100);
+    list.insert_with_value(30, 100);
+    let mut changes = ChangeSet::new();
+    assert_eq!(changes.checked_apply(&mut list, Edit::Remove(20)), Err(Overflow));
+    assert_eq!(changes.edits().len(), 0);
+    let mut history = History::new(list);
+    assert_eq!(history.checked_apply(Edit::Remove(20)), Err(Overflow));
+    assert_eq!(history.checked_remove(20), Err(Overflow));
+    assert!(!history.undo());
+    assert_eq!(history.checked_insert(25), Ok(()));
+    assert_eq!(history.checked_shift_backward(25, 4), Ok(()));
+    assert_eq!(history.checked_remove(21), Ok(true));
+    assert_eq!(history.list().to_positions(), vec![10, 20, 26]);
+    assert!(history.undo());
+    assert_eq!(history.list().to_positions(), vec![10, 20, 21, 26]);
+}
+
+#[test]
+fn test_diff() {
+    let list = |positions: &[isize]| {
+        let mut list = SpacedList::<isize>::new();
+        for &position in positions {
+            list.insert(position);
+        }
+        list
+    };
+    let old = list(&[5, 10, 15, 20, 25]);
+    assert_eq!(diff(&old, &old), vec![]);
+    assert_eq!(diff(&old, &list(&[5, 13, 18, 23, 28])),
+               vec![Edit::ShiftForward { position: 10, distance: 3 }]);
+    assert_eq!(diff(&old, &list(&[5, 8, 13, 18, 23])),
+               vec![Edit::ShiftBackward { position: 10, distance: 2 }]);
+    assert_eq!(diff(&old, &list(&[5, 15, 20, 25, 27])), vec![Edit::Remove(10), Edit::Insert(27)]);
+    assert_eq!(diff(&old, &list(&[5, 10, 11, 16, 21, 30])), vec![
+        Edit::ShiftBackward { position: 15, distance: 4 },
+        Edit::Insert(30),
+    ]);
+    assert_eq!(diff(&list(&[]), &list(&[1, 2])), vec![Edit::Insert(1), Edit::Insert(2)]);
+    assert_eq!(diff(&old, &list(&[])).len(), 5);
+    // shifting forward first would move the last node past u8::MAX
+    let list = |positions: &[u8]| {
+        let mut list = SpacedList::<u8>::new();
+        for &position in positions {
+            list.insert(position);
+        }
+        list
+    };
+    let (old, new) = (list(&[1, 250]), list(&[100, 101]));
+    let edits = diff(&old, &new);
+    assert_eq!(edits, vec![
+        Edit::ShiftBackward { position: 250, distance: 248 },
+        Edit::ShiftForward { position: 1, distance: 99 },
+    ]);
+    let mut list = list(&[1, 250]);
+    for edit in edits {
+        edit.apply(&mut list);
+    }
+    assert_eq!(list, new);
+    assert_eq!(diff(&new, &old), vec![
+        Edit::ShiftBackward { position: 100, distance: 99 },
+        Edit::ShiftForward { position: 2, distance: 248 },
+    ]);
+}
+
+#[test]
+fn test_node_ids() {
+    let mut list = SpacedList::<isize>::new();
+    list.insert(10);
+    list.insert(20);
+    list.insert(15);
+    let id = list.node_id(15).unwrap();
+    assert_eq!(list.node_id(15), Some(id));
+    assert_eq!(list.node_id(12), None);
+    assert_eq!(list.node_id(0), None);
+    let other = list.node_id(20).unwrap();
+    assert_ne!(id, other);
+    list.shift_forward(12, 5);
+    assert_eq!(list.position_of(id), Some(20));
+    assert_eq!(list.position_of(other), Some(25));
+    // moves the node with the handle out of the sublist of the removed node
+    assert!(list.remove(10));
+    assert_eq!(list.position_of(id), Some(20));
+    assert!(list.remove(20));
+    assert_eq!(list.position_of(id), None);
+    assert_eq!(list.position_of(other), Some(25));
+    list.insert(20);
+    assert_ne!(list.node_id(20), Some(id));
+    // a node in the sublist of a sublist, whose handle stays valid when the list moves
+    list.insert(22);
+    list.insert(21);
+    let nested = list.node_id(21).unwrap();
+    let moved = Box::new(list);
+    assert_eq!(moved.position_of(nested), Some(21));
+    assert_eq!(moved.position_of(other), Some(25));
+    moved.validate().unwrap();
+}
+
+#[test]
+fn test_instant_positions() {
+    let before = Instant::now();
+    let origin = before + Duration::from_millis(5);
+    let at = |millis| origin + Duration::from_millis(millis);
+    let mut list = SpacedList::<Instant, Sum<u32>, Duration>::with_origin(origin);
+    assert_eq!(list.origin(), origin);
+    list.insert_with_value(at(30), 3);
+    list.insert_with_value(at(10), 1);
+    list.insert_with_value(at(20), 2);
+    assert_eq!(list.to_positions(), vec![at(10), at(20), at(30)]);
+    assert_eq!(list.to_gaps(), vec![Duration::from_millis(10); 3]);
+    assert_eq!(list.position_before(at(20)), Some(at(10)));
+    assert_eq!(list.position_at_or_before(at(25)), Some(at(20)));
+    assert_eq!(list.position_at(at(30)), Some(at(30)));
+    assert_eq!(list.position_at_or_after(at(11)), Some(at(20)));
+    assert_eq!(list.position_after(at(30)), None);
+    // there are no nodes before node zero, like there are none before zero in a list of distances
+    assert_eq!(list.position_at_or_before(before), None);
+    assert_eq!(list.position_at(before), None);
+    assert_eq!(list.position_after(before), Some(origin));
+    assert_eq!(list.batch_before(&[before, at(15), at(40)]),
+               vec![None, Some(at(10)), Some(at(30))]);
+    assert_eq!(list.batch_at_or_after(&[before, at(15), at(31)]),
+               vec![Some(origin), Some(at(20)), None]);
+    assert_eq!(list.positions().collect::<Vec<_>>(), [origin, at(10), at(20), at(30)]);
+    assert_eq!(list.rank_at_or_before(at(25)), Some((at(20), 2)));
+    assert_eq!(list.position_of_rank(3), Some(at(30)));
+    assert_eq!(list.value_at(at(20)), Some(2));
+    assert_eq!(list.aggregate(before..at(25)), Sum(3));
+    list.update(at(15)..at(35), RangeUpdate::Add(10));
+    assert_eq!(list.aggregate(at(15)..at(35)), Sum(25));
+
+    let id = list.node_id(at(20)).unwrap();
+    list.shift_forward(at(15), Duration::from_millis(5));
+    assert_eq!(list.position_of(id), Some(at(25)));
+    list.shift_backward(at(35), Duration::from_millis(2));
+    list.shift(at(25), Duration::from_millis(1));
+    assert_eq!(list.to_positions(), vec![at(10), at(26), at(34)]);
+    assert!(!list.remove(before));
+    assert!(list.remove(at(10)));
+    assert_eq!(list.position_of(id), Some(at(26)));
+    assert_eq!(list.entry(at(40)).or_insert(4), 4);
+    assert_eq!(list.entry(at(34)).or_insert(0), 13);
+    assert_eq!(list.to_positions(), vec![at(26), at(34), at(40)]);
+    list.validate().unwrap();
+}
+
+#[test]
+fn test_origin() {
+    let mut list = SpacedList::<u8>::with_origin(200);
+    list.insert(250);
+    list.insert(220);
+    assert_eq!(list.to_positions(), vec![220, 250]);
+    assert_eq!(list.to_gaps(), vec![20, 30]);
+    assert_eq!(list.position_at_or_before(210), Some(200));
+    assert_eq!(list.position_at_or_after(100), Some(200));
+    assert_eq!(list.position_at(100), None);
+    // the last node is at 250, not 50 after zero
+    assert_eq!(list.checked_shift_forward(230, 5), Ok(()));
+    assert_eq!(list.checked_shift_forward(230, 1), Err(Overflow));
+    assert_eq!(list.to_positions(), vec![220, 255]);
+    assert_eq!(list.checked_append_node(1, ()), Err(Overflow));
+    assert!(list.remove(220));
+    assert!(!list.remove(200));
+    list.validate().unwrap();
+}
+
+#[cfg(all(feature = "validate-mutations", debug_assertions))]
+#[test]
+fn test_validate_mutations_with_handles() {
+    // every mutation below checks the handle locations of the sublists it changes
+    let mut list = SpacedList::<isize>::new();
+    for position in [40, 80, 20, 30, 25, 60, 70, 65, 27] {
+        list.insert(position);
+    }
+    let ids: Vec<_> = [20, 25, 27, 30, 65, 70].map(|position| list.node_id(position).unwrap()).into();
+    list.shift_forward(26, 3);
+    list.shift_backward(62, 1);
+    // a node with a sublist of sublists after it, and one in a sublist
+    assert!(list.remove(20));
+    assert!(list.remove(67));
+    assert!(list.remove(82));
+    let positions = ids.iter().map(|&id| list.position_of(id)).collect::<Vec<_>>();
+    assert_eq!(positions, vec![None, Some(25), Some(30), Some(33), None, Some(72)]);
+    list.shrink_to_fit();
+    list.validate().unwrap();
+
+    let mut list = SpacedList::<Metrics<(isize, isize)>>::new();
+    for position in [40, 80, 20, 30, 25, 60] {
+        list.insert_full(position);
+    }
+    let node = |list: &SpacedList<Metrics<(isize, isize)>>, position|
+        list.position_at_by::<Component<0>>(position).unwrap();
+    let kept = list.node_id(node(&list, 30)).unwrap();
+    let hidden = list.node_id(node(&list, 25)).unwrap();
+    for position in [20, 25, 60] {
+        list.hide(position);
+    }
+    assert_eq!(list.compact(|position| position == 25), 2);
+    assert_eq!(list.position_of(kept).map(|position| position.0.0), Some(30));
+    assert_eq!(list.position_of(hidden).map(|position| position.0.0), Some(25));
+    assert!(list.remove_full(25));
+    assert_eq!(list.position_of(hidden), None);
+    list.validate().unwrap();
+}
+
+#[cfg(all(feature = "validate-mutations", debug_assertions))]
+#[test]
+#[should_panic(expected = "invariant violated after mutation")]
+fn test_validate_mutations_catches_lost_handles() {
+    let mut list = SpacedList::<isize>::new();
+    list.insert(10);
+    list.insert(5);
+    let id = list.node_id(5).unwrap();
+    list.handles.as_mut().unwrap().forget_id(id);
+    list.shift_forward(1, 1);
+}
+
+#[test]
+fn test_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SpacedList<u64>>();
+    assert_send_sync::<PersistentSpacedList<u64>>();
+    assert_send_sync::<ArenaSpacedList<u64>>();
+    assert_send_sync::<History<u64>>();
+    assert_send_sync::<TraversalResult<u64>>();
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_parallel() {
+    let mut list = SpacedList::<u64>::new();
+    for position in (1..=1000).map(|n| n * 10) {
+        list.insert(position);
+    }
+    for position in (1..1000).map(|n| n * 10 + n % 7 + 1) {
+        list.insert(position);
+    }
+    let positions: Vec<_> = list.positions().collect();
+    for threads in 1..=9 {
+        let parts = list.split_positions(threads);
+        assert!(parts.len() <= threads);
+        assert_eq!(parts.into_iter().flatten().collect::<Vec<_>>(), positions);
+        assert_eq!(list.par_positions(threads), positions);
+        assert_eq!(list.par_count(threads), positions.len());
+        let sum = std::sync::atomic::AtomicU64::new(0);
+        list.par_for_each_position(threads, |position| {
+            sum.fetch_add(position, std::sync::atomic::Ordering::Relaxed);
+        });
+        assert_eq!(sum.into_inner(), positions.iter().sum());
+    }
+    assert_eq!(SpacedList::<u64>::new().par_positions(4), vec![0]);
+}
+
+#[test]
+fn test_rank() {
+    let mut list = SpacedList::<isize>::new();
+    for position in [4, 8, 12, 1, 2, 5, 6, 9, 3] {
+        list.insert(position);
+    }
+    let positions = [0, 1, 2, 3, 4, 5, 6, 8, 9, 12];
+    assert_eq!(list.node_count(), positions.len() - 1);
+    for (rank, &position) in positions.iter().enumerate() {
+        assert_eq!(list.position_of_rank(rank), Some(position));
+        assert_eq!(list.rank_at_or_before(position), Some((position, rank)));
+    }
+    assert_eq!(list.position_of_rank(positions.len()), None);
+    assert_eq!(list.rank_at_or_before(-1), None);
+    assert_eq!(list.rank_at_or_before(7), Some((6, 6)));
+    assert_eq!(list.rank_at_or_before(100), Some((12, 9)));
+}
+
+fn assert_line_index(index: &LineIndex, text: &str) {
+    index.line_starts().validate().unwrap();
+    let expected = LineIndex::new(text);
+    assert_eq!(index.text_len(), text.len());
+    assert_eq!(index.line_count(), expected.line_count());
+    for offset in 0..=text.len() + 1 {
+        assert_eq!(index.offset_to_line_col(offset), expected.offset_to_line_col(offset));
+    }
+    for line in 0..=index.line_count() {
+        for col in 0..=text.len() + 1 {
+            assert_eq!(index.line_col_to_offset(line, col), expected.line_col_to_offset(line, col));
+        }
+    }
+}
+
+#[test]
+fn test_line_index() {
+    let mut text = String::from("fn main() {\n    println!(\"hi\");\n}\n");
+    let mut index = LineIndex::new(&text);
+    assert_eq!(index.line_count(), 4);
+    assert_eq!(index.offset_to_line_col(0), Some((0, 0)));
+    assert_eq!(index.offset_to_line_col(11), Some((0, 11)));
+    assert_eq!(index.offset_to_line_col(12), Some((1, 0)));
+    assert_eq!(index.offset_to_line_col(text.len()), Some((3, 0)));
+    assert_eq!(index.offset_to_line_col(text.len() + 1), None);
+    assert_eq!(index.line_col_to_offset(1, 4), Some(16));
+    assert_eq!(index.line_col_to_offset(2, 1), Some(text.len() - 1));
+    assert_eq!(index.line_col_to_offset(2, 2), None);
+    assert_eq!(index.line_col_to_offset(4, 0), None);
+
+    let mut seed = 1u64;
+    let mut random = |bound: usize| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) as usize % bound
+    };
+    for _ in 0..300 {
+        let start = random(text.len() + 1);
+        let end = start + random(text.len() - start + 1).min(8);
+        let new_text: String = (0..random(6)).map(|_| ['a', '\n', 'b', '\n', ' '][random(5)]).collect();
+        text.replace_range(start..end, &new_text);
+        index.apply_edit(start..end, &new_text);
+        assert_line_index(&index, &text);
+    }
+}
+
+#[test]
+fn test_metric_ties() {
+    type Visible = Component<1>;
+    // every third node has the same visible position as the node before it, like a hidden node
+    let mut nodes = vec![Metrics((0, 0))];
+    for full in 1..=60 {
+        let visible = nodes.last().unwrap().0.1 + if full % 3 == 0 { 0 } else { 2 };
+        nodes.push(Metrics((full * 2, visible)));
+    }
+    let mut list = SpacedList::<Metrics<(i32, i32)>>::new();
+    // insert out of order, so that runs of ties span sublists
+    for &node in nodes[1..].iter().rev().step_by(2).chain(nodes[1..].iter().step_by(2)) {
+        list.insert(node);
+    }
+    list.validate().unwrap();
+    for target in -1..=nodes.last().unwrap().0.1 + 1 {
+        let first = |found: &dyn Fn(i32) -> bool| nodes.iter().find(|node| found(node.0.1)).copied();
+        let last = |found: &dyn Fn(i32) -> bool| nodes.iter().rev().find(|node| found(node.0.1)).copied();
+        assert_eq!(list.position_before_by::<Visible>(target), last(&|visible| visible < target));
+        assert_eq!(list.position_at_or_before_by::<Visible>(target), last(&|visible| visible <= target));
+        assert_eq!(list.position_at_by::<Visible>(target), first(&|visible| visible == target));
+        assert_eq!(list.position_at_or_after_by::<Visible>(target), first(&|visible| visible >= target));
+        assert_eq!(list.position_after_by::<Visible>(target), first(&|visible| visible > target));
+    }
+}
+
+#[test]
+fn test_metrics() {
+    type Bytes = Component<0>;
+    type Utf16 = Component<1>;
+    type Chars = Component<2>;
+    let text = "aé€😀\nb😀\n€€\né\n";
+    let mut list = SpacedList::<Metrics<(usize, usize, usize)>>::new();
+    let mut position = Metrics((0, 0, 0));
+    let mut line_starts = vec![position];
+    for char in text.chars() {
+        position += Metrics((char.len_utf8(), char.len_utf16(), 1));
+        if char == '\n' {
+            line_starts.push(position);
+        }
+    }
+    // insert out of order, to get sublists
+    let odd = line_starts[1..].iter().rev().step_by(2);
+    let even = line_starts[1..].iter().step_by(2);
+    for &line_start in odd.chain(even) {
+        if list.position_at(line_start).is_none() {
+            list.insert(line_start);
+        }
+    }
+    list.validate().unwrap();
+    for target in 0..=text.len() + 1 {
+        let expected = |metric: fn(&Metrics<(usize, usize, usize)>) -> usize|
+            line_starts.iter().rev().find(|&it| metric(it) <= target).copied();
+        assert_eq!(list.position_at_or_before_by::<Bytes>(target), expected(|it| it.0.0));
+        assert_eq!(list.position_at_or_before_by::<Utf16>(target), expected(|it| it.0.1));
+        assert_eq!(list.position_at_or_before_by::<Chars>(target), expected(|it| it.0.2));
+        assert_eq!(list.position_after_by::<Chars>(target),
+                   line_starts.iter().find(|&it| it.0.2 > target).copied());
+        assert_eq!(list.position_at_by::<Utf16>(target),
+                   line_starts.iter().find(|&it| it.0.1 == target).copied());
+    }
+}
@@ -0,0 +1,924 @@
+//! Randomized tests that run the same operations on a list of every layout and on a [BTreeSet] of
+//! node positions, and compare every query result and the positions of all nodes. Batch queries
+//! are compared with the single queries they stand for, and parallel ones with those batches.
+//!
+//! Runs are deterministic: each one is fully determined by its seed. Set `SPACED_LIST_SEED` to
+//! repeat a single seed, and `SPACED_LIST_OPERATIONS` to change the number of operations per run.
+//! Failing runs are shrunk to a minimal sequence of operations before being reported, unless
+//! `SPACED_LIST_NO_SHRINK` is set.
+
+use std::collections::{btree_map, BTreeMap, BTreeSet};
+use std::fmt::Debug;
+use std::env;
+use std::ops::Bound::{Excluded, Unbounded};
+use std::ops::Range;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use crate::{Aggregate, ArenaSpacedList, ArraySpacedList, Bias, ChangeSet, CheckedAggregate, Component, diff, Edit, History,
+            Max, Metrics, Min, NodeId, Overflow, PersistentSpacedList, RangeUpdate, SpacedList, SpacedListBackend, Sum,
+            TraversalResult, UnchunkedSpacedList};
+
+// region random number generator
+/// SplitMix64, which is all the randomness these tests need, without a dependency.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `from..=to`.
+    fn range(&mut self, from: isize, to: isize) -> isize {
+        from + (self.next() % (to - from + 1) as u64) as isize
+    }
+}
+// endregion
+
+// region operations
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Query {
+    Before,
+    AtOrBefore,
+    At,
+    AtOrAfter,
+    After,
+}
+
+const QUERIES: [Query; 5] = [Query::Before, Query::AtOrBefore, Query::At, Query::AtOrAfter, Query::After];
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Operation {
+    /// Inserts a node at the position, which is skipped if there already is a node there.
+    Insert(isize),
+    Query(Query, isize),
+}
+
+/// Generates `count` operations on positions up to `limit`. Small limits produce many sublists and
+/// many queries that hit nodes exactly.
+fn generate(seed: u64, count: usize, limit: isize) -> Vec<Operation> {
+    let mut random = Random(seed);
+    (0..count).map(|_| {
+        if random.next().is_multiple_of(3) {
+            Operation::Insert(random.range(1, limit))
+        } else {
+            let query = QUERIES[random.next() as usize % QUERIES.len()];
+            Operation::Query(query, random.range(-2, limit + 2))
+        }
+    }).collect()
+}
+// endregion
+
+// region model comparison
+fn expected(model: &BTreeSet<isize>, query: Query, target: isize) -> Option<isize> {
+    match query {
+        Query::Before => model.range(..target).next_back().copied(),
+        Query::AtOrBefore => model.range(..=target).next_back().copied(),
+        Query::At => model.get(&target).copied(),
+        Query::AtOrAfter => model.range(target..).next().copied(),
+        Query::After => model.range((Excluded(target), Unbounded)).next().copied(),
+    }
+}
+
+fn actual<B: SpacedListBackend<isize>>(list: &B, query: Query, target: isize) -> Option<isize> {
+    match query {
+        Query::Before => list.position_before(target),
+        Query::AtOrBefore => list.position_at_or_before(target),
+        Query::At => list.position_at(target),
+        Query::AtOrAfter => list.position_at_or_after(target),
+        Query::After => list.position_after(target),
+    }
+}
+
+/// Compares a query on `list` with the model, naming the `layout` of the list in the error.
+fn compare<B: SpacedListBackend<isize>>(layout: &str, list: &B, model: &BTreeSet<isize>,
+                                         step: usize, operation: Operation) -> Result<(), String> {
+    let (expected, actual) = match operation {
+        Operation::Query(query, target) =>
+            (expected(model, query, target), actual(list, query, target)),
+        Operation::Insert(_) => return Ok(()),
+    };
+    if expected != actual {
+        return Err(format!("step {}: {:?} returned {:?} on the {} list, expected {:?}",
+                           step, operation, actual, layout, expected));
+    }
+    Ok(())
+}
+
+/// Compares the positions of all nodes of `list` with the model.
+fn compare_positions<B: SpacedListBackend<isize>>(layout: &str, list: &B, model: &BTreeSet<isize>)
+                                                  -> Result<(), String> {
+    let positions: Vec<_> = list.positions().collect();
+    if !positions.iter().eq(model) {
+        return Err(format!("the {} list iterated over {:?}, expected {:?}",
+                           layout, positions, model));
+    }
+    Ok(())
+}
+
+fn path(path: Option<Vec<TraversalResult<isize>>>) -> Option<Vec<(isize, usize)>> {
+    path.map(|path| path.iter().map(|result| (result.position, result.index)).collect())
+}
+
+/// Compares every batch query for all targets around the nodes of `list` with the single queries.
+fn compare_batches(list: &SpacedList<isize>, model: &BTreeSet<isize>) -> Result<(), String> {
+    let targets: BTreeSet<_> =
+        model.iter().flat_map(|&position| [position - 2, position - 1, position, position + 1]).collect();
+    let targets: Vec<_> = targets.into_iter().collect();
+    for query in QUERIES {
+        let batch = match query {
+            Query::Before => list.batch_node_before(&targets, list.batch_start()),
+            Query::AtOrBefore => list.batch_node_at_or_before(&targets, list.batch_start()),
+            Query::At => list.batch_node_at(&targets, list.batch_start()),
+            Query::AtOrAfter => list.batch_node_at_or_after(&targets, list.batch_start()),
+            Query::After => list.batch_node_after(&targets, list.batch_start()),
+        };
+        if batch.len() != targets.len() {
+            return Err(format!("batch {:?} returned {} results for {} targets",
+                               query, batch.len(), targets.len()));
+        }
+        let batch: Vec<_> = batch.into_iter().map(path).collect();
+        let positions = match query {
+            Query::Before => list.batch_before(&targets),
+            Query::AtOrBefore => list.batch_at_or_before(&targets),
+            Query::At => list.batch_at(&targets),
+            Query::AtOrAfter => list.batch_at_or_after(&targets),
+            Query::After => list.batch_after(&targets),
+        };
+        for ((&target, batch), &position) in targets.iter().zip(&batch).zip(&positions) {
+            let expected = batch.as_ref().map(|path| path.iter().map(|&(position, _)| position).sum());
+            if position != expected {
+                return Err(format!("batch {:?} returned position {:?} for {}, expected {:?}",
+                                   query, position, target, expected));
+            }
+        }
+        // each thread starts at the link its targets lie in, so try splits at several degrees
+        #[cfg(feature = "std")]
+        for threads in [2, 3, 8] {
+            let parallel = match query {
+                Query::Before => list.par_batch_before(&targets, threads),
+                Query::AtOrBefore => list.par_batch_at_or_before(&targets, threads),
+                Query::At => list.par_batch_at(&targets, threads),
+                Query::AtOrAfter => list.par_batch_at_or_after(&targets, threads),
+                Query::After => list.par_batch_after(&targets, threads),
+            };
+            for ((&target, position), parallel) in targets.iter().zip(&positions).zip(parallel) {
+                if &parallel != position {
+                    return Err(format!("parallel batch {:?} on {} threads returned {:?} for {}, batch returned {:?}",
+                                       query, threads, parallel, target, position));
+                }
+            }
+        }
+        for (&target, batch) in targets.iter().zip(batch) {
+            let single = match query {
+                Query::Before => list.node_before(target),
+                Query::AtOrBefore => list.node_at_or_before(target),
+                Query::At => list.node_at(target),
+                Query::AtOrAfter => list.node_at_or_after(target),
+                Query::After => list.node_after(target),
+            };
+            let single = path(single);
+            if batch != single {
+                return Err(format!("batch {:?} returned {:?} for {}, single query returned {:?}",
+                                   query, batch, target, single));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs `operations` against a list of every layout and the model. Returns a description of the first
+/// divergence, including panics inside the list.
+fn check(operations: &[Operation]) -> Result<(), String> {
+    let run = catch_unwind(AssertUnwindSafe(|| {
+        let mut list = SpacedList::<isize>::new();
+        let mut arena = ArenaSpacedList::<isize>::new();
+        let mut unchunked = UnchunkedSpacedList::<isize>::default();
+        let mut persistent = PersistentSpacedList::<isize>::default();
+        // compared as long as it has room, and it must not change when it has not
+        let mut array = Some(ArraySpacedList::<isize, 2048>::new());
+        let mut model = BTreeSet::from([0]);
+        for (step, &operation) in operations.iter().enumerate() {
+            match operation {
+                Operation::Insert(position) => {
+                    let snapshot = (persistent.clone(), model.clone());
+                    if model.insert(position) {
+                        list.insert(position);
+                        arena.insert(position);
+                        unchunked.insert(position);
+                        persistent.insert(position);
+                        compare_positions("persistent snapshot", &snapshot.0, &snapshot.1)
+                            .map_err(|error| format!("step {}: {:?}: {}", step, operation, error))?;
+                        if let Some(full) = array.take_if(|array| array.insert(position).is_err()) {
+                            compare_positions("full array", &full, &snapshot.1)
+                                .map_err(|error| format!("step {}: {:?}: {}", step, operation, error))?;
+                        }
+                        list.validate().map_err(|violation|
+                            format!("step {}: {:?} left an invalid list: {}", step, operation, violation))?;
+                    }
+                }
+                Operation::Query(..) => {
+                    compare("nested", &list, &model, step, operation)?;
+                    compare("arena", &arena, &model, step, operation)?;
+                    compare("unchunked", &unchunked, &model, step, operation)?;
+                    compare("persistent", &persistent, &model, step, operation)?;
+                    if let Some(array) = &array {
+                        compare("array", array, &model, step, operation)?;
+                    }
+                }
+            }
+        }
+        compare_positions("nested", &list, &model)?;
+        #[cfg(feature = "std")]
+        for threads in [1, 2, 3, 8] {
+            if !list.par_positions(threads).iter().eq(&model) {
+                return Err(format!("par_positions on {} threads differs from the model", threads));
+            }
+        }
+        compare_batches(&list, &model)?;
+        compare_positions("arena", &arena, &model)?;
+        compare_positions("unchunked", &unchunked, &model)?;
+        if let Some(array) = &array {
+            compare_positions("array", array, &model)?;
+        }
+        compare_positions("persistent", &persistent, &model)?;
+        let imported = SpacedList::<isize>::from_gaps(&list.to_gaps());
+        imported.validate().map_err(|violation| format!("imported list is invalid: {}", violation))?;
+        compare_positions("imported", &imported, &model)?;
+        for (step, &operation) in operations.iter().enumerate() {
+            if let Operation::Query(..) = operation {
+                compare("imported", &imported, &model, step, operation)?;
+            }
+        }
+        Ok(())
+    }));
+    match run {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>().map(|it| it.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(format!("panicked: {}", message))
+        }
+    }
+}
+// endregion
+
+// region shrinking
+/// Removes operations from a sequence for as long as it keeps failing, first in large chunks and
+/// then one by one, and moves the remaining positions towards zero, until neither changes anything.
+fn shrink(mut operations: Vec<Operation>, fails: impl Fn(&[Operation]) -> bool) -> Vec<Operation> {
+    loop {
+        let before = operations.clone();
+
+        let mut chunk_size = operations.len() / 2;
+        while chunk_size > 0 {
+            let mut start = 0;
+            while start < operations.len() {
+                let end = (start + chunk_size).min(operations.len());
+                let candidate: Vec<_> =
+                    operations[..start].iter().chain(&operations[end..]).copied().collect();
+                if fails(&candidate) {
+                    operations = candidate;
+                } else {
+                    start += chunk_size;
+                }
+            }
+            chunk_size /= 2;
+        }
+
+        for index in 0..operations.len() {
+            loop {
+                let mut candidate = operations.clone();
+                match &mut candidate[index] {
+                    Operation::Insert(position) if *position > 1 => *position -= 1,
+                    Operation::Query(_, target) if *target > -2 => *target -= 1,
+                    _ => break,
+                }
+                if fails(&candidate) {
+                    operations = candidate;
+                } else {
+                    break;
+                }
+            }
+        }
+
+        if operations == before {
+            return operations;
+        }
+    }
+}
+
+fn env_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    env::var(name).ok()?.parse().ok()
+}
+
+fn run(seed: u64, count: usize, limit: isize) {
+    let operations = generate(seed, count, limit);
+    if let Err(message) = check(&operations) {
+        if env::var_os("SPACED_LIST_NO_SHRINK").is_some() {
+            panic!("seed {} failed: {}", seed, message);
+        }
+        // silence the panic messages of the many failing candidates
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| {}));
+        let operations = shrink(operations, |operations| check(operations).is_err());
+        std::panic::set_hook(hook);
+        panic!("seed {} failed: {}\nminimal reproduction: {:?}",
+               seed, check(&operations).unwrap_err(), operations);
+    }
+}
+
+fn run_seeds(seeds: std::ops::Range<u64>, limit: isize) {
+    let count = env_var("SPACED_LIST_OPERATIONS").unwrap_or(500);
+    match env_var("SPACED_LIST_SEED") {
+        Some(seed) => run(seed, count, limit),
+        None => seeds.for_each(|seed| run(seed, count, limit)),
+    }
+}
+// endregion
+
+// region history
+/// Makes random edits through a [History], checking the list against the model after each one,
+/// then undoes all of them and redoes them again, checking every step on the way.
+fn run_history(seed: u64, count: usize, limit: isize) {
+    let mut random = Random(seed);
+    let mut history = History::<isize>::default();
+    let mut models = vec![BTreeSet::from([0])];
+    for step in 0..count {
+        let mut model = models.last().unwrap().clone();
+        let grouped = random.next().is_multiple_of(4);
+        if grouped {
+            history.begin_group();
+        }
+        for _ in 0..if grouped { 3 } else { 1 } {
+            let position = random.range(1, limit);
+            match random.next() % 6 {
+                0 | 1 => if model.insert(position) {
+                    history.insert(position)
+                },
+                2 => assert_eq!(history.remove(position), model.remove(&position)),
+                _ => {
+                    let previous = *model.range(..position).next_back().unwrap();
+                    let next = model.range(position..).next().copied();
+                    let distance = random.range(0, 3);
+                    let moved: Vec<_> = model.split_off(&position).into_iter().collect();
+                    if random.next().is_multiple_of(2) {
+                        history.shift_forward(position, distance);
+                        model.extend(moved.iter().map(|it| it + distance));
+                    } else if next.is_none_or(|next| next - distance > previous) {
+                        history.shift_backward(position, distance);
+                        model.extend(moved.iter().map(|it| it - distance));
+                    } else {
+                        model.extend(moved);
+                    }
+                }
+            }
+        }
+        if grouped {
+            history.end_group();
+        }
+        history.list().validate().unwrap();
+        assert!(history.list().positions().eq(model.iter().copied()),
+                "seed {} step {}: {:?}, expected {:?}",
+                seed, step, history.list().positions().collect::<Vec<_>>(), model);
+        if models.last() != Some(&model) {
+            models.push(model);
+        }
+    }
+    for forward in [false, true] {
+        let steps: Vec<_> = if forward {
+            models.iter().skip(1).collect()
+        } else {
+            models.iter().rev().skip(1).collect()
+        };
+        for model in steps {
+            // steps that left the positions unchanged do not have a model of their own
+            loop {
+                assert!(if forward { history.redo() } else { history.undo() });
+                history.list().validate().unwrap();
+                if history.list().positions().eq(model.iter().copied()) {
+                    break;
+                }
+            }
+        }
+    }
+    assert!(history.list().positions().eq(models.last().unwrap().iter().copied()));
+}
+// endregion
+
+// region aggregates
+/// Makes random insertions, removals and value changes, and compares random range aggregates
+/// with the aggregates of the values in a [BTreeMap].
+fn run_aggregates<A>(seed: u64, count: usize, limit: isize)
+    where A: Aggregate<Value=i64, Update=RangeUpdate<i64>> + Debug {
+    let mut random = Random(seed);
+    let mut list = SpacedList::<isize, A>::new();
+    let mut model = BTreeMap::new();
+    for step in 0..count {
+        let position = random.range(1, limit);
+        let value = random.range(-100, 100) as i64;
+        match random.next() % 7 {
+            0 => if model.insert(position, value).is_none() {
+                list.insert_with_value(position, value)
+            } else {
+                assert!(list.set_value(position, value))
+            },
+            1 => assert_eq!(list.remove(position), model.remove(&position).is_some()),
+            2 => assert_eq!(list.set_value(position, value),
+                            model.get_mut(&position).map(|it| *it = value).is_some()),
+            operation @ (3 | 4) => {
+                let end = random.range(-2, limit + 2);
+                let start = random.range(-2, end);
+                let update = if operation == 3 {
+                    RangeUpdate::Add(value)
+                } else {
+                    RangeUpdate::Set(value)
+                };
+                list.update(start..end, update);
+                for (_, model_value) in model.range_mut(start..end) {
+                    *model_value = match update {
+                        RangeUpdate::Add(addend) => *model_value + addend,
+                        RangeUpdate::Set(value) => value,
+                    }
+                }
+                if step % 16 == 0 {
+                    list.shrink_to_fit()
+                }
+            }
+            5 => {
+                let expected = *model.entry(position).and_modify(|it| *it += value).or_insert(value);
+                let entry = list.entry(position);
+                assert_eq!(entry.position(), position);
+                assert_eq!(entry.and_modify(|it| *it += value).or_insert(value), expected,
+                           "seed {} step {}: entry at {}", seed, step, position);
+            }
+            _ => {
+                let end = random.range(-2, limit + 2);
+                let start = random.range(-2, end);
+                let expected = model.range(start..end)
+                    .fold(A::identity(), |aggregate, (_, &value)| aggregate.combine(A::of(value)));
+                assert_eq!(list.aggregate(start..end), expected,
+                           "seed {} step {}: aggregate of {}..{}", seed, step, start, end);
+                assert_eq!(list.value_at(position), model.get(&position).copied());
+            }
+        }
+        list.validate().unwrap();
+    }
+    for (&position, &value) in &model {
+        assert_eq!(list.value_at(position), Some(value), "seed {}: value at {}", seed, position);
+    }
+}
+
+/// A change of values, made with the checked operations on one list and with the unchecked ones on
+/// another.
+#[derive(Clone, Debug)]
+enum ValueChange {
+    Insert(isize, i8),
+    SetValue(isize, i8),
+    Update(Range<isize>, RangeUpdate<i8>),
+    Remove(isize),
+    /// Sets the value of the node at the position through an entry, or inserts it there with it.
+    Entry(isize, i8),
+    /// Takes the entry at the position, which pushes the updates on the way down to it.
+    Visit(isize),
+}
+
+impl ValueChange {
+    fn apply<A>(&self, list: &mut SpacedList<isize, A>)
+        where A: Aggregate<Value=i8, Update=RangeUpdate<i8>> {
+        match *self {
+            ValueChange::Insert(position, value) => list.insert_with_value(position, value),
+            ValueChange::SetValue(position, value) => {
+                list.set_value(position, value);
+            }
+            ValueChange::Update(ref range, update) => list.update(range.clone(), update),
+            ValueChange::Remove(position) => {
+                list.remove(position);
+            }
+            ValueChange::Entry(position, value) => {
+                list.entry(position).and_modify(|current| *current = value).or_insert(value);
+            }
+            ValueChange::Visit(position) => {
+                list.entry(position);
+            }
+        }
+    }
+
+    fn checked_apply<A>(&self, list: &mut SpacedList<isize, A>) -> Result<(), Overflow>
+        where A: CheckedAggregate<Value=i8, Update=RangeUpdate<i8>> {
+        match *self {
+            ValueChange::Insert(position, value) => list.checked_insert_with_value(position, value),
+            ValueChange::SetValue(position, value) => list.checked_set_value(position, value).map(drop),
+            ValueChange::Update(ref range, update) => list.checked_update(range.clone(), update),
+            ValueChange::Remove(position) => list.checked_remove(position).map(drop),
+            ValueChange::Entry(position, value) => list.checked_entry(position)?
+                .checked_and_modify(|current| *current = value)?
+                .checked_or_insert(value)
+                .map(drop),
+            ValueChange::Visit(position) => list.checked_entry(position).map(drop),
+        }
+    }
+}
+
+/// Makes random changes of `i8` values with the checked operations, which must return [Overflow]
+/// exactly when the unchecked operations overflow, and leave the list as it was then.
+fn run_checked_aggregates<A>(seed: u64, count: usize, limit: isize)
+    where A: CheckedAggregate<Value=i8, Update=RangeUpdate<i8>> + Debug {
+    let mut random = Random(seed);
+    let mut list = SpacedList::<isize, A>::new();
+    // the same list, changed with the unchecked operations, and built anew after they overflow
+    let mut unchecked = SpacedList::<isize, A>::new();
+    let mut applied: Vec<ValueChange> = vec![];
+    let mut positions = BTreeSet::new();
+    for step in 0..count {
+        let position = random.range(1, limit);
+        let value = random.range(-128, 127) as i8;
+        let change = match random.next() % 6 {
+            0 if !positions.contains(&position) => ValueChange::Insert(position, value),
+            0 | 1 => ValueChange::SetValue(position, value),
+            4 => ValueChange::Remove(position),
+            5 => ValueChange::Entry(position, value),
+            operation => {
+                let end = random.range(-2, limit + 2);
+                let start = random.range(-2, end);
+                let small_value = value / 16;
+                ValueChange::Update(start..end, if operation == 2 {
+                    RangeUpdate::Add(small_value)
+                } else {
+                    RangeUpdate::Set(small_value)
+                })
+            }
+        };
+        let overflowed = catch_unwind(AssertUnwindSafe(|| change.apply(&mut unchecked))).is_err();
+        assert_eq!(change.checked_apply(&mut list).is_err(), overflowed,
+                   "seed {} step {}: {:?}", seed, step, change);
+        let rebuild = |applied: &[ValueChange]| {
+            let mut unchecked = SpacedList::new();
+            for change in applied {
+                change.apply(&mut unchecked)
+            }
+            unchecked
+        };
+        if overflowed {
+            unchecked = rebuild(&applied);
+            // the checked entry has pushed the updates down before it overflows, unless that did
+            if let ValueChange::Entry(position, _) = change {
+                let visit = ValueChange::Visit(position);
+                if catch_unwind(AssertUnwindSafe(|| visit.apply(&mut unchecked))).is_ok() {
+                    applied.push(visit);
+                } else {
+                    unchecked = rebuild(&applied);
+                }
+            }
+        } else {
+            match change {
+                ValueChange::Insert(position, _) | ValueChange::Entry(position, _) => {
+                    positions.insert(position);
+                }
+                ValueChange::Remove(position) => {
+                    positions.remove(&position);
+                }
+                _ => {}
+            }
+            applied.push(change);
+        }
+        assert!(list == unchecked, "seed {} step {}: the lists differ", seed, step);
+    }
+}
+// endregion
+
+// region change sets
+/// Makes random edits through a [ChangeSet], and checks that it maps the position of every node
+/// from before the edits to where the node is after them, and that its inverse maps them back.
+fn run_change_sets(seed: u64, count: usize, limit: isize) {
+    let mut random = Random(seed);
+    let mut list = SpacedList::<isize>::new();
+    for _ in 0..limit / 4 {
+        let position = random.range(1, limit);
+        if list.position_at(position).is_none() {
+            list.insert(position)
+        }
+    }
+    let initial: Vec<_> = list.positions().collect();
+    // the initial positions of the nodes that are still there, by their current positions
+    let mut nodes: BTreeMap<_, _> = initial.iter().map(|&position| (position, position)).collect();
+    let mut changes = ChangeSet::new();
+    for _ in 0..count {
+        let position = random.range(1, limit);
+        let distance = random.range(0, 3);
+        let previous = list.position_before(position).unwrap();
+        let next = list.position_at_or_after(position);
+        let edit = match random.next() % 4 {
+            0 if next != Some(position) => Edit::Insert(position),
+            1 if next == Some(position) => Edit::Remove(position),
+            2 => Edit::ShiftForward { position, distance },
+            3 if next.is_none_or(|next| next - distance > previous) =>
+                Edit::ShiftBackward { position, distance },
+            _ => continue,
+        };
+        let mut step = ChangeSet::new();
+        step.apply(&mut list, edit);
+        nodes = match edit {
+            // inserted nodes are not in the initial list, so they do not need to be tracked
+            Edit::Insert(_) => nodes,
+            Edit::Remove(position) => {
+                nodes.remove(&position);
+                nodes
+            }
+            Edit::ShiftForward { .. } | Edit::ShiftBackward { .. } => nodes.into_iter()
+                .map(|(current, initial)| (step.map(current, Bias::After), initial))
+                .collect(),
+        };
+        changes = changes.compose(&step);
+    }
+    for (&current, &initial) in &nodes {
+        assert_eq!(changes.map(initial, Bias::After), current, "seed {}: node at {}", seed, initial);
+        assert_eq!(changes.invert().map(current, Bias::After), initial,
+                   "seed {}: node at {}", seed, current);
+        assert_eq!(list.position_at(current), Some(current));
+    }
+    changes.invert().apply_to(&mut list);
+    assert!(list.positions().eq(initial.iter().copied()), "seed {}", seed);
+}
+// endregion
+
+// region diff
+/// Makes random edits to a copy of a random list, and checks that the diff between the two turns
+/// the first into the second, with no more edits than were made.
+fn run_diff(seed: u64, count: usize, limit: isize) {
+    let mut random = Random(seed);
+    let mut old = SpacedList::<isize>::new();
+    let mut new = SpacedList::<isize>::new();
+    for _ in 0..limit / 4 {
+        let position = random.range(1, limit);
+        if old.position_at(position).is_none() {
+            old.insert(position);
+            new.insert(position);
+        }
+    }
+    let mut edits = 0;
+    for _ in 0..count {
+        let position = random.range(1, limit);
+        let distance = random.range(1, 3);
+        let edit = match random.next() % 4 {
+            0 if new.position_at(position).is_none() => Edit::Insert(position),
+            1 if new.position_at(position).is_some() => Edit::Remove(position),
+            2 => Edit::ShiftForward { position, distance },
+            3 => Edit::ShiftBackward { position, distance },
+            _ => continue,
+        };
+        if let Edit::ShiftBackward { .. } = edit {
+            let previous = new.position_before(position).unwrap();
+            if new.position_at_or_after(position).is_some_and(|next| previous + distance >= next) {
+                continue;
+            }
+        }
+        let mut changes = ChangeSet::new();
+        changes.apply(&mut new, edit);
+        edits += changes.edits().len();
+    }
+    let script = diff(&old, &new);
+    assert!(script.len() <= edits, "seed {}: {} edits for {}: {:?}", seed, script.len(), edits, script);
+    let mut changes = ChangeSet::new();
+    for edit in script {
+        changes.apply(&mut old, edit);
+    }
+    assert!(old.positions().eq(new.positions()), "seed {}", seed);
+}
+// endregion
+
+// region node ids
+/// Makes random insertions, removals, shifts and compactions of hidden nodes, hands out handles to
+/// random nodes, and checks after each step that every handle finds its node, or nothing once the
+/// node is removed.
+fn run_node_ids(seed: u64, count: usize, limit: isize) {
+    let mut random = Random(seed);
+    let mut list = SpacedList::<Metrics<(isize, isize)>>::new();
+    // the handle of every node that has one, by position
+    let mut model: BTreeMap<isize, Option<NodeId>> = BTreeMap::new();
+    let mut removed = vec![];
+    for step in 0..count {
+        let position = random.range(1, limit);
+        match random.next() % 7 {
+            0 | 1 => if let btree_map::Entry::Vacant(entry) = model.entry(position) {
+                entry.insert(None);
+                list.insert_full(position)
+            },
+            2 => {
+                let expected = model.remove(&position);
+                removed.extend(expected.flatten());
+                assert_eq!(list.remove_full(position), expected.is_some());
+            }
+            3 => {
+                let distance = random.range(0, 3);
+                let moved = model.split_off(&position);
+                let previous = model.keys().next_back().copied().unwrap_or(0);
+                if random.next().is_multiple_of(2) {
+                    list.shift_forward_full(position, distance);
+                    model.extend(moved.into_iter().map(|(it, id)| (it + distance, id)));
+                } else if moved.keys().next().is_none_or(|&next| next - distance > previous) {
+                    list.shift_backward_full(position, distance);
+                    model.extend(moved.into_iter().map(|(it, id)| (it - distance, id)));
+                } else {
+                    model.extend(moved);
+                }
+            }
+            4 => {
+                // hides the node and compacts it away if it has no handle
+                if list.hide(position) && model[&position].is_none() {
+                    model.remove(&position);
+                }
+                list.compact(|position| model.contains_key(&position));
+                list.show(position);
+            }
+            _ => {
+                let node = list.position_at_by::<Component<0>>(position);
+                let id = node.and_then(|node| list.node_id(node));
+                match model.get_mut(&position) {
+                    Some(Some(expected)) => assert_eq!(id, Some(*expected)),
+                    Some(handle) => *handle = Some(id.unwrap()),
+                    None => assert_eq!(id, None),
+                }
+            }
+        }
+        list.validate().unwrap();
+        for (&position, id) in &model {
+            if let Some(id) = *id {
+                let actual = list.position_of(id).map(|Metrics((position, _))| position);
+                assert_eq!(actual, Some(position), "seed {} step {}: {:?}", seed, step, id);
+            }
+        }
+        for &id in &removed {
+            assert_eq!(list.position_of(id), None, "seed {} step {}: {:?}", seed, step, id);
+        }
+    }
+}
+// endregion
+
+// region tombstones
+/// Returns the visible and full positions of the visible nodes of `model`, a map from node
+/// positions to whether they are hidden, starting with node zero.
+fn visible_nodes(model: &BTreeMap<isize, bool>) -> Vec<(isize, isize)> {
+    let mut nodes = vec![(0, 0)];
+    let mut visible_position = 0;
+    let mut previous = 0;
+    for (&position, &hidden) in model {
+        if !hidden {
+            visible_position += position - previous;
+            nodes.push((visible_position, position));
+        }
+        previous = position;
+    }
+    nodes
+}
+
+/// Makes random insertions, removals, shifts, hides, shows and compactions, and compares the
+/// queries in visible coordinates with the visible nodes of a [BTreeMap].
+fn run_tombstones(seed: u64, count: usize, limit: isize) {
+    let mut random = Random(seed);
+    let mut list = SpacedList::<Metrics<(isize, isize)>>::new();
+    let mut model = BTreeMap::new();
+    for step in 0..count {
+        let position = random.range(1, limit);
+        match random.next() % 8 {
+            0 | 1 => if let btree_map::Entry::Vacant(entry) = model.entry(position) {
+                entry.insert(false);
+                list.insert_full(position)
+            },
+            2 => assert_eq!(list.remove_full(position), model.remove(&position).is_some()),
+            3 | 4 => {
+                let hide = random.next().is_multiple_of(2);
+                let expected = model.get_mut(&position).map(|hidden| *hidden = hide).is_some();
+                let actual = if hide { list.hide(position) } else { list.show(position) };
+                assert_eq!(actual, expected, "seed {} step {}: hide {}", seed, step, position);
+            }
+            5 => {
+                let distance = random.range(0, 3);
+                let moved = model.split_off(&position);
+                let previous = model.keys().next_back().copied().unwrap_or(0);
+                if random.next().is_multiple_of(2) {
+                    list.shift_forward_full(position, distance);
+                    model.extend(moved.into_iter().map(|(it, hidden)| (it + distance, hidden)));
+                } else if moved.keys().next().is_none_or(|&next| next - distance > previous) {
+                    list.shift_backward_full(position, distance);
+                    model.extend(moved.into_iter().map(|(it, hidden)| (it - distance, hidden)));
+                } else {
+                    model.extend(moved);
+                }
+            }
+            6 => {
+                let divisor = random.range(2, 4);
+                let referenced = |position: isize| position % divisor == 0;
+                let before = model.len();
+                model.retain(|&position, &mut hidden| !hidden || referenced(position));
+                assert_eq!(list.compact(referenced), before - model.len());
+            }
+            _ => {
+                let nodes = visible_nodes(&model);
+                let target = random.range(-2, limit + 2);
+                let full = |node: Option<&(isize, isize)>| node.map(|&(_, position)| position);
+                let before = nodes.iter().rev().find(|(visible, _)| *visible < target);
+                let at_or_before = nodes.iter().rev().find(|(visible, _)| *visible <= target);
+                let at = nodes.iter().find(|(visible, _)| *visible == target);
+                let at_or_after = nodes.iter().find(|(visible, _)| *visible >= target);
+                let after = nodes.iter().find(|(visible, _)| *visible > target);
+                let message = format!("seed {} step {}: visible target {}", seed, step, target);
+                assert_eq!(list.position_before_visible(target), full(before), "{}", message);
+                assert_eq!(list.position_at_or_before_visible(target), full(at_or_before), "{}", message);
+                assert_eq!(list.position_at_visible(target), full(at), "{}", message);
+                assert_eq!(list.position_at_or_after_visible(target), full(at_or_after), "{}", message);
+                assert_eq!(list.position_after_visible(target), full(after), "{}", message);
+                assert_eq!(list.is_hidden(position), model.get(&position).copied());
+                let visible_position = model.contains_key(&position).then(|| nodes.iter()
+                    .rev()
+                    .find(|&&(_, node_position)| node_position <= position)
+                    .unwrap().0);
+                assert_eq!(list.visible_position_of(position), visible_position, "{}", message);
+            }
+        }
+        list.validate().unwrap();
+        let positions = list.positions().map(|Metrics((position, _))| position);
+        assert!(positions.eq([0].into_iter().chain(model.keys().copied())),
+                "seed {} step {}: {:?}", seed, step, list.positions().collect::<Vec<_>>());
+        assert_eq!(list.visible_length(), visible_nodes(&model).last().unwrap().0);
+    }
+}
+// endregion
+
+#[test]
+fn test_model_aggregates() {
+    for seed in 0..32 {
+        run_aggregates::<Sum<i64>>(seed, 300, 64);
+        run_aggregates::<Min<i64>>(seed, 300, 64);
+        run_aggregates::<Max<i64>>(seed, 300, 1 << 10);
+    }
+}
+
+#[test]
+fn test_model_checked_aggregates() {
+    for seed in 0..16 {
+        run_checked_aggregates::<Sum<i8>>(seed, 200, 64);
+        run_checked_aggregates::<Min<i8>>(seed, 200, 64);
+        run_checked_aggregates::<Max<i8>>(seed, 200, 1 << 10);
+    }
+}
+
+#[test]
+fn test_model_tombstones() {
+    for seed in 0..32 {
+        run_tombstones(seed, 300, 64);
+        run_tombstones(seed, 300, 1 << 10);
+    }
+}
+
+#[test]
+fn test_model_change_sets() {
+    for seed in 0..32 {
+        run_change_sets(seed, 200, 64);
+    }
+}
+
+#[test]
+fn test_model_diff() {
+    for seed in 0..64 {
+        run_diff(seed, seed as usize % 8, 64);
+        run_diff(seed, 16, 1 << 10);
+    }
+}
+
+#[test]
+fn test_model_node_ids() {
+    for seed in 0..32 {
+        run_node_ids(seed, 300, 64);
+        run_node_ids(seed, 300, 1 << 10);
+    }
+}
+
+#[test]
+fn test_model_history() {
+    for seed in 0..32 {
+        run_history(seed, 200, 64);
+    }
+}
+
+#[test]
+fn test_model_dense() {
+    run_seeds(0..64, 32);
+}
+
+#[test]
+fn test_model_sparse() {
+    run_seeds(64..128, 1 << 12);
+}
+
+#[test]
+fn test_shrink() {
+    let operations = generate(0, 200, 32);
+    let fails = |operations: &[Operation]|
+        operations.iter().any(|it| matches!(it, Operation::Insert(position) if *position >= 10));
+    assert!(fails(&operations));
+    assert_eq!(shrink(operations, fails), vec![Operation::Insert(10)]);
+}
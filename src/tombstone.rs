@@ -0,0 +1,243 @@
+//! Hidden nodes, tombstones that keep their position, value and handle, but take up no visible
+//! length, as collaborative editors keep deleted elements.
+//!
+//! Only lists with a [Metrics] spacing of two components support them. The first component is
+//! the full position, which all nodes have, and the second one is the visible position, which is
+//! the part of the full position that ends at visible nodes. A node is hidden if it has the same
+//! visible position as the node before it. All other lists store nothing for tombstones.
+
+use alloc::vec::Vec;
+use core::default::default;
+use crate::{Aggregate, Component, Metrics, SpacedList, Spacing, zero};
+
+// region spaced list tombstones
+/// The tombstone operations of a list of [Metrics] of the full and visible positions. All positions
+/// passed in and returned are full positions, unless the method says otherwise.
+///
+/// ```
+/// # use spaced_list_3::{Metrics, SpacedList};
+/// let mut list = SpacedList::<Metrics<(u32, u32)>>::default();
+/// list.insert_full(10);
+/// list.insert_full(15);
+/// list.hide(10);
+/// assert_eq!(list.visible_position_of(15), Some(5));
+/// assert_eq!(list.position_at_visible(5), Some(15));
+/// ```
+impl<S: Spacing, A: Aggregate> SpacedList<Metrics<(S, S)>, A> {
+    /// Returns the length of the visible nodes of this list, the visible position of its last node.
+    pub fn visible_length(&self) -> S {
+        self.length.0.1
+    }
+
+    /// Returns the full and visible position of the node at `position`.
+    fn node_metrics(&self, position: S) -> Option<Metrics<(S, S)>> {
+        self.position_at_by::<Component<0>>(position)
+    }
+
+    /// Returns `position` in full coordinates with the visible position of node zero, which is at
+    /// or before any node at `position` and after all nodes before it, to shift the nodes from.
+    fn at_full(&self, position: S) -> Metrics<(S, S)> {
+        Metrics((position, self.origin.0.1))
+    }
+
+    /// Returns the visible length of the gap before the node at `position`, which is zero if the
+    /// node is hidden and the full length of the gap if it is not, or None if there is no node
+    /// there or it is node zero.
+    fn visible_gap(&self, position: S) -> Option<S> {
+        let node = self.node_metrics(position)?;
+        let previous = self.position_before_by::<Component<0>>(position)?;
+        Some(node.0.1 - previous.0.1)
+    }
+
+    /// Inserts a visible node at `position`, see [Self::insert_full_with_value].
+    pub fn insert_full(&mut self, position: S) {
+        self.insert_full_with_value(position, default())
+    }
+
+    /// Inserts a visible node with `value` at `position`, which must be positive. The visible
+    /// nodes after it move forward in visible coordinates by the gap before it.
+    pub fn insert_full_with_value(&mut self, position: S, value: A::Value) {
+        assert!(position > self.origin.0.0);
+        let previous = self.position_at_or_before_by::<Component<0>>(position).unwrap();
+        let gap = position - previous.0.0;
+        if let Some(next) = self.position_after_by::<Component<0>>(position) {
+            if next.0.1 == previous.0.1 {
+                // the next node is hidden, so it moves with the new node in visible coordinates
+                self.shift_forward(self.at_full(position), Metrics((zero(), gap)))
+            }
+        }
+        self.insert_with_value(Metrics((position, previous.0.1 + gap)), value)
+    }
+
+    /// Removes the node at `position`, hidden or not, and returns whether there was one. The nodes
+    /// after it keep their positions, and whether they are hidden.
+    pub fn remove_full(&mut self, position: S) -> bool {
+        let (Some(removed), Some(removed_gap)) = (self.node_metrics(position), self.visible_gap(position))
+        else {
+            return false;
+        };
+        let next = self.position_after_by::<Component<0>>(position);
+        self.remove(removed);
+        if let Some(next) = next {
+            let previous = self.position_before_by::<Component<0>>(next.0.0).unwrap();
+            let next_hidden = next.0.1 - removed.0.1 == zero();
+            let at_next = self.at_full(next.0.0);
+            if next_hidden && removed_gap != zero() {
+                self.shift_backward(at_next, Metrics((zero(), removed_gap)))
+            } else if !next_hidden && removed_gap == zero() {
+                // the next node now also takes up the full gap before the removed one
+                self.shift_forward(at_next, Metrics((zero(), removed.0.0 - previous.0.0)))
+            }
+        }
+        true
+    }
+
+    /// Moves all nodes at or after `position` forward by `distance`, in full coordinates, and in
+    /// visible coordinates if the first of them is visible.
+    pub fn shift_forward_full(&mut self, position: S, distance: S) {
+        let visible_distance = self.shifted_visible_distance(position, distance);
+        self.shift_forward(self.at_full(position), Metrics((distance, visible_distance)))
+    }
+
+    /// Moves all nodes at or after `position` back by `distance`, in full coordinates, and in
+    /// visible coordinates if the first of them is visible. Panics if that would move a node to or
+    /// before the last node before `position`.
+    pub fn shift_backward_full(&mut self, position: S, distance: S) {
+        let visible_distance = self.shifted_visible_distance(position, distance);
+        self.shift_backward(self.at_full(position), Metrics((distance, visible_distance)))
+    }
+
+    /// Returns how far shifting the nodes at or after `position` by `distance` moves them in
+    /// visible coordinates, which is the full distance if the first of them is visible and zero
+    /// otherwise.
+    fn shifted_visible_distance(&self, position: S, distance: S) -> S {
+        assert!(position > self.origin.0.0);
+        match self.position_at_or_after_by::<Component<0>>(position) {
+            Some(next) if self.visible_gap(next.0.0) == Some(zero()) => zero(),
+            _ => distance,
+        }
+    }
+
+    /// Hides the node at `position` and returns whether there is a node there that can be hidden.
+    /// A hidden node is a tombstone: it keeps its position and value, but the gap before it adds no
+    /// visible length, so the nodes after it move back in visible coordinates. The node at zero
+    /// cannot be hidden.
+    pub fn hide(&mut self, position: S) -> bool {
+        let Some(gap) = self.visible_gap(position) else {
+            return false;
+        };
+        if gap != zero() {
+            self.shift_backward(self.at_full(position), Metrics((zero(), gap)))
+        }
+        true
+    }
+
+    /// Shows the node at `position` again if it is hidden, see [Self::hide], and returns whether
+    /// there is a node there.
+    pub fn show(&mut self, position: S) -> bool {
+        let Some(gap) = self.visible_gap(position) else {
+            return false;
+        };
+        if gap == zero() {
+            let previous = self.position_before_by::<Component<0>>(position).unwrap();
+            self.shift_forward(self.at_full(position), Metrics((zero(), position - previous.0.0)))
+        }
+        true
+    }
+
+    /// Returns whether the node at `position` is hidden, or None if there is no node there.
+    pub fn is_hidden(&self, position: S) -> Option<bool> {
+        if position == self.origin.0.0 {
+            return Some(false);
+        }
+        self.visible_gap(position).map(|gap| gap == zero())
+    }
+
+    /// Returns the visible position of the node at `position`, or None if there is no node there.
+    /// A hidden node has the same visible position as the node before it.
+    pub fn visible_position_of(&self, position: S) -> Option<S> {
+        self.node_metrics(position).map(|node| node.0.1)
+    }
+
+    /// Returns the position of the last visible node before `target_position` in visible
+    /// coordinates. Node zero counts as visible.
+    pub fn position_before_visible(&self, target_position: S) -> Option<S> {
+        // the first node at the greatest visible position before the target is visible
+        let before = self.position_before_by::<Component<1>>(target_position)?;
+        self.position_at_by::<Component<1>>(before.0.1).map(|node| node.0.0)
+    }
+
+    /// Returns the position of the last visible node at or before `target_position` in visible
+    /// coordinates.
+    pub fn position_at_or_before_visible(&self, target_position: S) -> Option<S> {
+        let at_or_before = self.position_at_or_before_by::<Component<1>>(target_position)?;
+        self.position_at_by::<Component<1>>(at_or_before.0.1).map(|node| node.0.0)
+    }
+
+    /// Returns the position of the visible node at `target_position` in visible coordinates.
+    pub fn position_at_visible(&self, target_position: S) -> Option<S> {
+        self.position_at_by::<Component<1>>(target_position).map(|node| node.0.0)
+    }
+
+    /// Returns the position of the first visible node at or after `target_position` in visible
+    /// coordinates.
+    pub fn position_at_or_after_visible(&self, target_position: S) -> Option<S> {
+        self.position_at_or_after_by::<Component<1>>(target_position).map(|node| node.0.0)
+    }
+
+    /// Returns the position of the first visible node after `target_position` in visible
+    /// coordinates.
+    pub fn position_after_visible(&self, target_position: S) -> Option<S> {
+        self.position_after_by::<Component<1>>(target_position).map(|node| node.0.0)
+    }
+
+    /// Removes the hidden nodes for which `referenced` returns false, the tombstones nothing refers
+    /// to any more, and returns how many were removed. `referenced` is called with the position of
+    /// each hidden node. All other nodes keep their positions, values, handles and whether they are
+    /// hidden.
+    ///
+    /// Builds the list anew without sublists like [Self::from_gaps] if anything is removed, which
+    /// takes O(n). Removing the tombstones in place would take up to O(n) for each of them instead,
+    /// as the nodes after a removed node move to lower indices, see [Self::remove], and the
+    /// sublists only make the queries slower.
+    pub fn compact(&mut self, mut referenced: impl FnMut(S) -> bool) -> usize {
+        let entries = self.entries();
+        let node_count = entries.len();
+        let mut previous_visible = zero();
+        let kept: Vec<_> = entries.into_iter()
+            .filter(|entry| {
+                let Metrics((position, visible)) = entry.position;
+                let hidden = visible == previous_visible;
+                previous_visible = visible;
+                !hidden || referenced(self.origin.0.0 + position)
+            })
+            .collect();
+        let removed = node_count - kept.len();
+        if removed == 0 {
+            return 0;
+        }
+        let mut gaps = Vec::with_capacity(kept.len());
+        let mut values = Vec::with_capacity(kept.len());
+        let mut ids = Vec::with_capacity(kept.len());
+        let mut previous: Metrics<(S, S)> = zero();
+        for entry in kept {
+            let Metrics((position, visible)) = entry.position;
+            let gap = position - previous.0.0;
+            // the removed nodes are hidden, so the kept node before a hidden node has the same
+            // visible position as it
+            gaps.push(Metrics((gap, if visible == previous.0.1 { zero() } else { gap })));
+            values.push(entry.value);
+            ids.push(entry.id);
+            previous = entry.position;
+        }
+        let mut handles = self.take_handles();
+        *self = Self::from_nodes(self.origin, &gaps, values, ids);
+        // the handles and sublist keys handed out so far must not be handed out again
+        handles.clear();
+        self.relink(0, &mut handles);
+        self.handles = Some(handles);
+        self.validate_after_mutation();
+        removed
+    }
+}
+// endregion
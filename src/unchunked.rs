@@ -0,0 +1,235 @@
+//! The "unchunked" link layout sketched in `unchunked.md`.
+//!
+//! [SpacedList](crate::SpacedList) stores its links breadth-first (`2110000`), and the other
+//! layouts in order (`0102010`), both of which need the whole `link_lengths` to be laid out anew,
+//! one degree higher, whenever the capacity is exceeded. This layout stores them in post-order
+//! instead (`0010012`): every link of a degree above zero directly follows the two links it is
+//! the sum of. The links of a list with `n` zero-degree links are
+//! therefore always a prefix of the links of a longer list, and appending a node only ever pushes
+//! to the end of `link_lengths`.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::default::default;
+use crate::{Spacing, SpacedListBackend, zero};
+
+// region helper functions
+/// Index of the link of degree `degree` that starts at or before the node at `node_index`, in
+/// post-order.
+///
+/// The zero-degree link after node `k` is preceded by `k` zero-degree links and by one higher-degree
+/// link per completed pair, quadruple, ... of them, `k - k.count_ones()` in total. A link of degree
+/// `degree` directly follows its last zero-degree link and the `degree` links above that one.
+pub(crate) const fn unchunked_link_index(node_index: usize, degree: usize) -> usize {
+    let last = (node_index >> degree << degree) + (1 << degree) - 1;
+    2 * last - last.count_ones() as usize + degree
+}
+// endregion
+
+// region unchunked spaced list
+/// A [SpacedList](crate::SpacedList) with its links in post-order, which makes appending cheaper.
+pub struct UnchunkedSpacedList<S: Spacing> {
+    size: usize,
+    length: S,
+    link_lengths: Vec<S>,
+    sublists: Vec<Option<UnchunkedSpacedList<S>>>,
+}
+
+impl<S: Spacing> Default for UnchunkedSpacedList<S> {
+    fn default() -> Self {
+        Self {
+            size: 1,
+            length: zero(),
+            link_lengths: vec![],
+            sublists: vec![],
+        }
+    }
+}
+
+impl<S: Spacing> UnchunkedSpacedList<S> {
+    fn new() -> Self {
+        default()
+    }
+
+    fn link(&self, node_index: usize, degree: usize) -> S {
+        self.link_lengths[unchunked_link_index(node_index, degree)]
+    }
+
+    /// Number of degrees of links, i.e. the number of binary digits of the number of zero-degree
+    /// links.
+    fn depth(&self) -> usize {
+        (usize::BITS - (self.size - 1).leading_zeros()) as usize
+    }
+
+    fn append_node(&mut self, distance: S) {
+        assert!(distance > zero());
+        let links = self.size - 1;
+        self.link_lengths.push(distance);
+        // every trailing one of the number of links completes a link one degree higher, which is
+        // the sum of the link just pushed and the one of the same degree before it
+        let mut completed = links;
+        let mut degree = 0;
+        while completed & 1 == 1 {
+            let len = self.link_lengths.len();
+            let sum = self.link_lengths[len - 1] + self.link_lengths[len - (2 << degree)];
+            self.link_lengths.push(sum);
+            completed >>= 1;
+            degree += 1;
+        }
+        self.size += 1;
+        self.length += distance;
+        self.sublists.push(None)
+    }
+
+    /// Returns the sublist at `index`, or None if there is no sublist at `index` or that sublist is
+    /// empty
+    fn get_not_empty_sublist_at_index(&self, index: usize) -> Option<&UnchunkedSpacedList<S>> {
+        self.sublists.get(index)?.as_ref().filter(|sublist| sublist.size > 1)
+    }
+
+    /// Returns the position and index of the last node in this list, not in sublists, that is
+    /// before `target_position`, or at it if `inclusive` is true. Assumes there is such a node.
+    fn descend(&self, target_position: S, inclusive: bool) -> (S, usize) {
+        let mut position = S::zero();
+        let mut index = 0usize;
+        for degree in (0..self.depth()).rev() {
+            let possibly_next_index = index + (1 << degree);
+            if possibly_next_index < self.size {
+                let possibly_next_position = position + self.link(index, degree);
+                if possibly_next_position < target_position
+                    || inclusive && possibly_next_position == target_position {
+                    position = possibly_next_position;
+                    index = possibly_next_index;
+                }
+            }
+        }
+        (position, index)
+    }
+}
+
+impl<S: Spacing> SpacedListBackend<S> for UnchunkedSpacedList<S> {
+    type Positions<'a> = UnchunkedPositions<'a, S> where S: 'a;
+
+    fn insert(&mut self, position: S) {
+        assert!(position > zero());
+
+        if position >= self.length {
+            self.append_node(position - self.length)
+        } else {
+            // zero() < position < self.length
+            let (node_position, index) = self.descend(position, true);
+            let position_in_sublist = position - node_position;
+            assert!(position_in_sublist > zero());
+            self.sublists[index].get_or_insert_default().insert(position_in_sublist)
+        }
+    }
+
+    fn position_before(&self, target_position: S) -> Option<S> {
+        if target_position <= zero() {
+            return None;
+        }
+        let (position, index) = self.descend(target_position, false);
+        match self.get_not_empty_sublist_at_index(index) {
+            Some(sublist) => Some(position + sublist.position_before(target_position - position)?),
+            None => Some(position),
+        }
+    }
+
+    fn position_at_or_before(&self, target_position: S) -> Option<S> {
+        if target_position < zero() {
+            return None;
+        }
+        let (position, index) = self.descend(target_position, true);
+        match self.get_not_empty_sublist_at_index(index) {
+            Some(sublist) if position != target_position =>
+                Some(position + sublist.position_at_or_before(target_position - position)?),
+            _ => Some(position),
+        }
+    }
+
+    fn position_at(&self, target_position: S) -> Option<S> {
+        if target_position < zero() {
+            return None;
+        }
+        let (position, index) = self.descend(target_position, true);
+        if position == target_position {
+            return Some(position);
+        }
+        let sublist = self.get_not_empty_sublist_at_index(index)?;
+        Some(position + sublist.position_at(target_position - position)?)
+    }
+
+    fn position_at_or_after(&self, target_position: S) -> Option<S> {
+        if target_position < zero() {
+            return Some(zero());
+        }
+        if target_position > self.length {
+            return None;
+        }
+        let (position, index) = self.descend(target_position, true);
+        if position == target_position {
+            return Some(position);
+        }
+        let in_sublist = self.get_not_empty_sublist_at_index(index)
+            .and_then(|sublist| sublist.position_at_or_after(target_position - position));
+        Some(position + in_sublist.unwrap_or_else(|| self.link(index, 0)))
+    }
+
+    fn position_after(&self, target_position: S) -> Option<S> {
+        if target_position < zero() {
+            return Some(zero());
+        }
+        if target_position >= self.length {
+            return None;
+        }
+        let (position, index) = self.descend(target_position, true);
+        let in_sublist = self.get_not_empty_sublist_at_index(index)
+            .and_then(|sublist| sublist.position_after(target_position - position));
+        Some(position + in_sublist.unwrap_or_else(|| self.link(index, 0)))
+    }
+
+    fn positions(&self) -> UnchunkedPositions<'_, S> {
+        UnchunkedPositions {
+            stack: vec![(self, 0, zero())],
+        }
+    }
+}
+
+/// Iterator over the positions of all nodes of an [UnchunkedSpacedList], including sublists.
+pub struct UnchunkedPositions<'a, S: Spacing> {
+    /// The lists being iterated, innermost last, each with the index and the absolute position of
+    /// the next node to visit in it.
+    stack: Vec<(&'a UnchunkedSpacedList<S>, usize, S)>,
+}
+
+impl<S: Spacing> Iterator for UnchunkedPositions<'_, S> {
+    type Item = S;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let nesting = self.stack.len();
+            let (list, index, position) = self.stack.last_mut()?;
+            if *index >= list.size {
+                self.stack.pop();
+                continue;
+            }
+            let list = *list;
+            let node_index = *index;
+            let node_position = *position;
+            // node zero of a sublist is the node it hangs off, which has been visited already
+            let visited = node_index == 0 && nesting > 1;
+            *index += 1;
+            if node_index + 1 < list.size {
+                *position += list.link(node_index, 0);
+            }
+            if let Some(sublist) = list.get_not_empty_sublist_at_index(node_index) {
+                self.stack.push((sublist, 0, node_position));
+            }
+            if visited {
+                continue;
+            }
+            return Some(node_position);
+        }
+    }
+}
+// endregion